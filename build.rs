@@ -0,0 +1,29 @@
+//! Embeds every message catalog in `locales/`, so adding a language is a
+//! matter of adding a file.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=locales");
+
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("locales");
+    let mut catalogs: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("Cannot read {}: {}", dir.display(), e))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    catalogs.sort();
+
+    let mut code = String::from("/// Language code and TOML source of each catalog in `locales/`\n");
+    code.push_str("static CATALOG_SOURCES: &[(&str, &str)] = &[\n");
+    for path in &catalogs {
+        let language = path.file_stem().and_then(|stem| stem.to_str()).expect("catalog names are UTF-8");
+        code.push_str(&format!("    ({:?}, include_str!({:?})),\n", language, path.display().to_string()));
+    }
+    code.push_str("];\n");
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("locales.rs");
+    fs::write(out, code).expect("failed to write the catalog list");
+}
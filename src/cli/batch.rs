@@ -0,0 +1,382 @@
+use anyhow::{Result, anyhow};
+use log::warn;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+use small_mp4::compression::CompressionResult;
+use small_mp4::utils::is_video_file;
+
+use crate::i18n::t;
+
+/// A file to compress and where it sits relative to the path it was found
+/// through, so `--output-dir` can mirror the source layout.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchInput {
+    pub path: PathBuf,
+    pub relative: PathBuf,
+}
+
+impl BatchInput {
+    /// Output path under `output_dir`, keeping the relative layout and
+    /// switching the extension to `.mp4`
+    pub fn output_path(&self, output_dir: &Path) -> PathBuf {
+        let mut output = output_dir.join(&self.relative);
+        output.set_extension("mp4");
+
+        // Never write over the source when compressing in place
+        if output == self.path {
+            let stem = output.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            output.set_file_name(format!("{}_compressed.mp4", stem));
+        }
+
+        output
+    }
+
+    /// Like [`output_path`](Self::output_path), with the source extension
+    /// kept in the name: `a.mov` becomes `a.mov.mp4`
+    fn output_path_with_extension(&self, output_dir: &Path) -> PathBuf {
+        let mut file_name = self.relative.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".mp4");
+        output_dir.join(&self.relative).with_file_name(file_name)
+    }
+}
+
+/// Output paths under `output_dir` for `inputs`, in order.
+///
+/// Inputs that would share an output, such as `a.mov` and `a.mkv`, or that
+/// would write over another input, keep their source extension in the name.
+/// Outputs still shared after that (the same relative path found through two
+/// inputs) are an error listing them.
+pub fn output_paths(inputs: &[BatchInput], output_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut outputs: Vec<_> = inputs.iter().map(|input| input.output_path(output_dir)).collect();
+
+    let sources: HashSet<_> = inputs.iter().map(|input| input.path.as_path()).collect();
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for output in &outputs {
+        *counts.entry(output.clone()).or_default() += 1;
+    }
+    for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+        if counts[output.as_path()] > 1 || sources.contains(output.as_path()) {
+            *output = input.output_path_with_extension(output_dir);
+        }
+    }
+
+    let mut sharing: HashMap<&Path, Vec<&Path>> = HashMap::new();
+    for (input, output) in inputs.iter().zip(&outputs) {
+        sharing.entry(output.as_path()).or_default().push(input.path.as_path());
+    }
+    let mut conflicts: Vec<String> = sharing.into_iter()
+        .filter(|(output, sources)| sources.len() > 1 || inputs.iter().any(|input| input.path == *output))
+        .map(|(output, sources)| {
+            let sources: Vec<_> = sources.iter().map(|source| source.display().to_string()).collect();
+            format!("{} <- {}", output.display(), sources.join(", "))
+        })
+        .collect();
+    if !conflicts.is_empty() {
+        conflicts.sort();
+        return Err(anyhow!("Several inputs would be compressed to the same file:\n  {}", conflicts.join("\n  ")));
+    }
+
+    Ok(outputs)
+}
+
+/// Expand files, directories and glob patterns into the videos to compress.
+///
+/// Files named directly are always included. Directories (descended into
+/// only with `recursive`) and glob matches are filtered with
+/// [`is_video_file`]. Duplicates are dropped, keeping the first occurrence.
+pub fn collect_inputs(inputs: &[PathBuf], recursive: bool) -> Result<Vec<BatchInput>> {
+    let mut collected = Vec::new();
+
+    for input in inputs {
+        let pattern = input.to_string_lossy();
+
+        if input.is_file() {
+            collected.push(BatchInput {
+                path: input.clone(),
+                relative: PathBuf::from(input.file_name().unwrap_or_default()),
+            });
+        } else if input.is_dir() {
+            let max_depth = if recursive { usize::MAX } else { 1 };
+            let mut found: Vec<_> = WalkDir::new(input)
+                .max_depth(max_depth)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file() && is_video_file(entry.path()))
+                .map(|entry| entry.into_path())
+                .collect();
+            found.sort();
+
+            for path in found {
+                let relative = path.strip_prefix(input).unwrap_or(&path).to_path_buf();
+                collected.push(BatchInput { path, relative });
+            }
+        } else if is_glob(&pattern) {
+            let base = glob_base(input);
+            let mut found: Vec<_> = glob::glob(&pattern)
+                .map_err(|e| anyhow!("Invalid pattern '{}': {}", pattern, e))?
+                .filter_map(|entry| entry.ok())
+                .filter(|path| path.is_file() && is_video_file(path))
+                .collect();
+            found.sort();
+
+            if found.is_empty() {
+                warn!("Pattern '{}' matched no video files", pattern);
+            }
+            for path in found {
+                let relative = path.strip_prefix(&base).unwrap_or(&path).to_path_buf();
+                collected.push(BatchInput { path, relative });
+            }
+        } else {
+            return Err(anyhow!("Input does not exist: {}", input.display()));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    collected.retain(|input| seen.insert(input.path.clone()));
+    Ok(collected)
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Leading directories of a glob pattern that contain no wildcards
+fn glob_base(pattern: &Path) -> PathBuf {
+    pattern.components()
+        .take_while(|component| match component {
+            Component::Normal(part) => !is_glob(&part.to_string_lossy()),
+            _ => true,
+        })
+        .collect()
+}
+
+/// What happened to one file in a batch
+pub enum BatchOutcome {
+    Compressed(Box<CompressionResult>),
+    /// Already compressed by an earlier run, according to the journal
+    Skipped { output: PathBuf },
+    Failed(anyhow::Error),
+}
+
+impl From<Result<CompressionResult>> for BatchOutcome {
+    fn from(result: Result<CompressionResult>) -> Self {
+        match result {
+            Ok(result) => BatchOutcome::Compressed(Box::new(result)),
+            Err(e) => BatchOutcome::Failed(e),
+        }
+    }
+}
+
+/// Outcome of one file in a batch
+pub struct BatchEntry {
+    pub input: PathBuf,
+    pub outcome: BatchOutcome,
+}
+
+/// One line of `--format json` output
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum BatchRecord<'a> {
+    Compressed { input: &'a Path, result: &'a CompressionResult },
+    Skipped { input: &'a Path, output: &'a Path },
+    Failed { input: &'a Path, error: String },
+}
+
+impl BatchEntry {
+    /// The entry as a single-line JSON record
+    pub fn json_record(&self) -> String {
+        let input = self.input.as_path();
+        let record = match &self.outcome {
+            BatchOutcome::Compressed(result) => BatchRecord::Compressed { input, result },
+            BatchOutcome::Skipped { output } => BatchRecord::Skipped { input, output },
+            BatchOutcome::Failed(e) => BatchRecord::Failed { input, error: format!("{:#}", e) },
+        };
+        serde_json::to_string(&record).expect("batch records serialize to JSON")
+    }
+
+    /// Failed outright, or missed the target or broke a platform limit when
+    /// `strict` is set
+    pub fn is_failure(&self, strict: bool) -> bool {
+        match &self.outcome {
+            BatchOutcome::Compressed(result) => strict && (!result.target_met || !result.platform_violations.is_empty()),
+            BatchOutcome::Skipped { .. } => false,
+            BatchOutcome::Failed(_) => true,
+        }
+    }
+}
+
+/// One-line description of a finished compression, in the message language
+pub fn describe_result(result: &CompressionResult) -> String {
+    t!("result_summary",
+        input = result.input_path.file_name().unwrap_or_default().to_string_lossy(),
+        input_size = format!("{:.1}", result.input_size_mb),
+        output = result.output_path.file_name().unwrap_or_default().to_string_lossy(),
+        output_size = format!("{:.1}", result.output_size_mb),
+        seconds = format!("{:.1}", result.encoding_time.as_secs_f64()),
+        encoder = format!("{:?}", result.encoder_used),
+        ratio = format!("{:.1}", result.compression_ratio))
+}
+
+/// Per-file summary table followed by totals
+pub fn summary_table(entries: &[BatchEntry], strict: bool) -> String {
+    let mut lines = vec![t!("summary_title")];
+    let warning = if strict { "❌" } else { "⚠️ " };
+
+    for entry in entries {
+        let line = match &entry.outcome {
+            BatchOutcome::Compressed(result) if !result.platform_violations.is_empty() => format!(
+                "  {} {}",
+                warning,
+                t!("summary_platform_violations", result = describe_result(result), violations = result.platform_violations.join("; ")),
+            ),
+            BatchOutcome::Compressed(result) if result.target_met => format!("  ✅ {}", describe_result(result)),
+            BatchOutcome::Compressed(result) => format!(
+                "  {} {}",
+                warning,
+                t!("summary_over_target", result = describe_result(result), target = result.target_size),
+            ),
+            BatchOutcome::Skipped { output } => format!(
+                "  ⏭️  {}",
+                t!("summary_skipped", input = entry.input.display(), output = output.display()),
+            ),
+            BatchOutcome::Failed(e) => format!("  ❌ {}: {}", entry.input.display(), e),
+        };
+        lines.push(line);
+    }
+
+    let failed = entries.iter().filter(|entry| entry.is_failure(strict)).count();
+    let skipped = entries.iter().filter(|entry| matches!(entry.outcome, BatchOutcome::Skipped { .. })).count();
+    let succeeded = entries.len() - failed - skipped;
+    let totals = if skipped > 0 {
+        t!("summary_totals_skipped", count = entries.len(), succeeded = succeeded, skipped = skipped, failed = failed)
+    } else {
+        t!("summary_totals", count = entries.len(), succeeded = succeeded, failed = failed)
+    };
+    lines.push(format!("  {}", totals));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn test_directories_filter_videos_and_respect_recursion() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(&dir.path().join("a.mov"));
+        touch(&dir.path().join("notes.txt"));
+        touch(&dir.path().join("trip/b.mkv"));
+
+        let flat = collect_inputs(&[dir.path().to_path_buf()], false).unwrap();
+        assert_eq!(flat.len(), 1);
+        assert_eq!(flat[0].relative, PathBuf::from("a.mov"));
+
+        let deep = collect_inputs(&[dir.path().to_path_buf()], true).unwrap();
+        let relative: Vec<_> = deep.iter().map(|input| input.relative.clone()).collect();
+        assert_eq!(relative, [PathBuf::from("a.mov"), PathBuf::from("trip/b.mkv")]);
+    }
+
+    #[test]
+    fn test_globs_mirror_from_pattern_base() {
+        let dir = tempfile::tempdir().unwrap();
+        touch(&dir.path().join("clips/one/a.mp4"));
+        touch(&dir.path().join("clips/two/b.mp4"));
+
+        let pattern = dir.path().join("clips/*/*.mp4");
+        let inputs = collect_inputs(&[pattern], false).unwrap();
+        let relative: Vec<_> = inputs.iter().map(|input| input.relative.clone()).collect();
+        assert_eq!(relative, [PathBuf::from("one/a.mp4"), PathBuf::from("two/b.mp4")]);
+    }
+
+    #[test]
+    fn test_duplicates_and_missing_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.mov");
+        touch(&file);
+
+        let inputs = collect_inputs(&[file.clone(), dir.path().to_path_buf()], false).unwrap();
+        assert_eq!(inputs.len(), 1);
+
+        assert!(collect_inputs(&[dir.path().join("missing.mov")], false).is_err());
+    }
+
+    #[test]
+    fn test_output_path_mirrors_and_avoids_source() {
+        let input = BatchInput {
+            path: PathBuf::from("videos/trip/b.mkv"),
+            relative: PathBuf::from("trip/b.mkv"),
+        };
+        assert_eq!(input.output_path(Path::new("out")), PathBuf::from("out/trip/b.mp4"));
+
+        let in_place = BatchInput {
+            path: PathBuf::from("videos/a.mp4"),
+            relative: PathBuf::from("a.mp4"),
+        };
+        assert_eq!(in_place.output_path(Path::new("videos")), PathBuf::from("videos/a_compressed.mp4"));
+    }
+
+    #[test]
+    fn test_output_paths_keep_extension_on_conflict() {
+        let input = |path: &str, relative: &str| BatchInput { path: PathBuf::from(path), relative: PathBuf::from(relative) };
+        let inputs = [input("x/a.mov", "a.mov"), input("x/a.mkv", "a.mkv"), input("x/b.mov", "b.mov")];
+        assert_eq!(
+            output_paths(&inputs, Path::new("out")).unwrap(),
+            [PathBuf::from("out/a.mov.mp4"), PathBuf::from("out/a.mkv.mp4"), PathBuf::from("out/b.mp4")]
+        );
+
+        // Compressing in place never writes over another input
+        let in_place = [input("x/a.mov", "a.mov"), input("x/a.mp4", "a.mp4")];
+        assert_eq!(
+            output_paths(&in_place, Path::new("x")).unwrap(),
+            [PathBuf::from("x/a.mov.mp4"), PathBuf::from("x/a_compressed.mp4")]
+        );
+
+        let same_relative = [input("vids2/a.mov", "a.mov"), input("vids/a.mov", "a.mov")];
+        let error = output_paths(&same_relative, Path::new("out")).unwrap_err().to_string();
+        assert!(error.contains("out/a.mov.mp4 <- vids2/a.mov, vids/a.mov"), "{}", error);
+    }
+
+    #[test]
+    fn test_summary_counts_skipped_files() {
+        let entries = [
+            BatchEntry {
+                input: PathBuf::from("a.mov"),
+                outcome: BatchOutcome::Skipped { output: PathBuf::from("out/a.mp4") },
+            },
+            BatchEntry {
+                input: PathBuf::from("b.mov"),
+                outcome: BatchOutcome::Failed(anyhow!("ffprobe failed")),
+            },
+        ];
+
+        let table = summary_table(&entries, false);
+        assert!(table.contains("a.mov: already compressed to out/a.mp4"));
+        assert!(table.ends_with("2 file(s): 0 succeeded, 1 skipped, 1 failed"));
+    }
+
+    #[test]
+    fn test_json_records() {
+        let skipped = BatchEntry {
+            input: PathBuf::from("a.mov"),
+            outcome: BatchOutcome::Skipped { output: PathBuf::from("out/a.mp4") },
+        };
+        assert_eq!(skipped.json_record(), r#"{"status":"skipped","input":"a.mov","output":"out/a.mp4"}"#);
+
+        let failed = BatchEntry {
+            input: PathBuf::from("b.mov"),
+            outcome: BatchOutcome::Failed(anyhow!("ffprobe failed")),
+        };
+        let record: serde_json::Value = serde_json::from_str(&failed.json_record()).unwrap();
+        assert_eq!(record["status"], "failed");
+        assert_eq!(record["error"], "ffprobe failed");
+    }
+}
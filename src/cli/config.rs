@@ -0,0 +1,240 @@
+//! The TOML config file shared by the CLI and the GUI.
+//!
+//! ```toml
+//! [defaults]
+//! size = "25mb"
+//! hw-preset = "fast"
+//!
+//! [profiles.work-chat]
+//! size = "8mb"
+//! compatibility = true
+//!
+//! [platforms.team-wiki]
+//! max-size = "20mb"
+//! max-resolution = "1280x720"
+//! ```
+//!
+//! Keys are the long flag names of [`CompressionCliSettings`]. Settings are
+//! resolved in the order CLI flags > profile > `[defaults]` > built-in defaults.
+//! `[platforms.*]` tables add platform presets, or replace built-in ones of
+//! the same name.
+
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use small_mp4::compression::{PlatformPreset, MAX_QUALITY_LEVEL};
+
+use super::CompressionCliSettings;
+use crate::i18n;
+
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Used for every run
+    pub defaults: CompressionCliSettings,
+    /// Selected with `--profile <name>`, overriding `defaults`
+    pub profiles: BTreeMap<String, CompressionCliSettings>,
+    /// Selected with `--platform <name>` alongside the built-in presets
+    pub platforms: BTreeMap<String, PlatformPreset>,
+}
+
+impl Config {
+    /// Load `path`, or the file in the user's config directory when `None`.
+    ///
+    /// A missing default file is an empty config; a missing file given
+    /// explicitly is an error.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        if !required && !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let mut config: Self = config::Config::builder()
+            .add_source(config::File::from(path.as_path()).format(config::FileFormat::Toml).required(required))
+            .build()
+            .and_then(config::Config::try_deserialize)
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))?;
+
+        validate(&config.defaults, "[defaults]")
+            .and_then(|_| config.profiles.iter().try_for_each(|(name, profile)| validate(profile, &format!("[profiles.{}]", name))))
+            .map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))?;
+
+        for (name, platform) in &mut config.platforms {
+            platform.name = name.clone();
+        }
+
+        log::debug!("Loaded config from {}", path.display());
+        Ok(config)
+    }
+
+    /// Layer `flags` over `profile` (if any) and the config defaults
+    pub fn resolve(&self, flags: &CompressionCliSettings, profile: Option<&str>) -> Result<CompressionCliSettings> {
+        let mut settings = flags.clone();
+
+        if let Some(name) = profile {
+            let profile = self.profiles.get(name).ok_or_else(|| {
+                let known: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+                if known.is_empty() {
+                    anyhow!("Unknown profile '{}': the config file defines no profiles", name)
+                } else {
+                    anyhow!("Unknown profile '{}'; available profiles: {}", name, known.join(", "))
+                }
+            })?;
+            settings = settings.or(profile.clone());
+        }
+
+        let mut settings = settings.or(self.defaults.clone());
+        if let Some(name) = &settings.platform {
+            settings.platform_preset = Some(self.platform(name)?);
+        }
+        Ok(settings)
+    }
+
+    /// Platform preset called `name`, from the config file or built in
+    pub fn platform(&self, name: &str) -> Result<PlatformPreset> {
+        self.platforms.get(name).cloned()
+            .or_else(|| PlatformPreset::find_built_in(name))
+            .ok_or_else(|| {
+                let mut known: Vec<_> = PlatformPreset::built_in().into_iter().map(|preset| preset.name).collect();
+                known.extend(self.platforms.keys().cloned());
+                anyhow!("Unknown platform '{}'; available platforms: {}", name, known.join(", "))
+            })
+    }
+}
+
+/// `$XDG_CONFIG_HOME/small-mp4/config.toml`, falling back to `~/.config`
+/// (`%APPDATA%` on Windows)
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(target_os = "windows") {
+                std::env::var_os("APPDATA").map(PathBuf::from)
+            } else {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config"))
+            }
+        })?;
+
+    Some(config_home.join("small-mp4").join(CONFIG_FILE_NAME))
+}
+
+/// Apply the range checks clap applies to the same flags
+fn validate(settings: &CompressionCliSettings, section: &str) -> Result<()> {
+    if settings.quality.is_some_and(|quality| quality > MAX_QUALITY_LEVEL) {
+        return Err(anyhow!("{}: quality must be between 0 and {}", section, MAX_QUALITY_LEVEL));
+    }
+    if settings.max_iterations == Some(0) {
+        return Err(anyhow!("{}: max-iterations must be at least 1", section));
+    }
+    if let Some(language) = &settings.lang {
+        i18n::parse_language(language).map_err(|e| anyhow!("{}: {}", section, e))?;
+    }
+    Ok(())
+}
+
+/// Deserialize a clap value enum from the same name its flag accepts
+pub fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let name = String::deserialize(deserializer)?;
+    T::from_str(&name, true).map(Some).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::hardware_cli::HardwarePresetCli;
+    use small_mp4::compression::FileSize;
+
+    fn write_config(contents: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        std::fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_flags_override_profile_override_defaults() {
+        let (_dir, path) = write_config(r#"
+            [defaults]
+            size = "25mb"
+            hw-preset = "fast"
+            quality = 30
+
+            [profiles.work-chat]
+            size = "8mb"
+            compatibility = true
+        "#);
+        let config = Config::load(Some(&path)).unwrap();
+
+        let flags = CompressionCliSettings { quality: Some(20), ..Default::default() };
+        let settings = config.resolve(&flags, Some("work-chat")).unwrap();
+        assert_eq!(settings.size, Some(FileSize::from_mb(8.0)));
+        assert_eq!(settings.compatibility, Some(true));
+        assert!(matches!(settings.hw_preset, Some(HardwarePresetCli::Fast)));
+        assert_eq!(settings.quality, Some(20));
+
+        let settings = config.resolve(&CompressionCliSettings::default(), None).unwrap();
+        assert_eq!(settings.size, Some(FileSize::from_mb(25.0)));
+        assert_eq!(settings.compatibility, None);
+
+        let error = config.resolve(&flags, Some("discord")).unwrap_err().to_string();
+        assert!(error.contains("available profiles: work-chat"), "{}", error);
+    }
+
+    #[test]
+    fn test_platforms_from_config_and_built_in() {
+        let (_dir, path) = write_config(r#"
+            [defaults]
+            platform = "team-wiki"
+
+            [platforms.team-wiki]
+            max-size = "20mb"
+            max-resolution = "1280x720"
+        "#);
+        let config = Config::load(Some(&path)).unwrap();
+
+        let settings = config.resolve(&CompressionCliSettings::default(), None).unwrap();
+        let preset = settings.platform_preset.unwrap();
+        assert_eq!(preset.name, "team-wiki");
+        assert_eq!(preset.max_size, FileSize::from_mb(20.0));
+
+        let flags = CompressionCliSettings { platform: Some("email-25mb".to_string()), ..Default::default() };
+        let settings = config.resolve(&flags, None).unwrap();
+        assert_eq!(settings.platform_preset.unwrap().max_size, FileSize::from_mb(25.0));
+
+        let flags = CompressionCliSettings { platform: Some("fax".to_string()), ..Default::default() };
+        let error = config.resolve(&flags, None).unwrap_err().to_string();
+        assert!(error.contains("chat-8mb, email-25mb, short-video, team-wiki"), "{}", error);
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        let (_dir, path) = write_config("[defaults]\nhw-preset = \"turbo\"\n");
+        assert!(Config::load(Some(&path)).is_err());
+
+        let (_dir, path) = write_config("[profiles.tiny]\nquality = 80\n");
+        let error = Config::load(Some(&path)).unwrap_err().to_string();
+        assert!(error.contains("[profiles.tiny]"), "{}", error);
+
+        let (_dir, path) = write_config("[defaults]\nlang = \"tlh\"\n");
+        let error = Config::load(Some(&path)).unwrap_err().to_string();
+        assert!(error.contains("unknown language 'tlh'"), "{}", error);
+
+        assert!(Config::load(Some(Path::new("/nonexistent/config.toml"))).is_err());
+    }
+}
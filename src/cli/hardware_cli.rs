@@ -1,5 +1,5 @@
 use clap::ValueEnum;
-use crate::compression::hardware::{HardwareEncoder, HardwarePreset, HardwareQuality};
+use small_mp4::compression::hardware::{HardwareEncoder, HardwarePreset, HardwareQuality};
 
 #[derive(Clone, ValueEnum, Debug)]
 pub enum HardwareEncoderCli {
@@ -82,12 +82,16 @@ impl HardwarePresetCli {
 
 #[derive(Clone, ValueEnum, Debug)]
 pub enum HardwareQualityCli {
+    /// Hit the target size with a steady bitrate
     #[value(name = "auto")]
     Auto,
+    /// Constant quality (--quality), ignores the target size
     #[value(name = "constant")]
     Constant,
+    /// Hit the target size, allowing bitrate peaks in complex scenes
     #[value(name = "variable")]
     Variable,
+    /// Constant quality (--quality), but never larger than the target size
     #[value(name = "constrained")]
     Constrained,
 }
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+use small_mp4::compression::hardware::{HardwareCapabilities, HardwareEncoder};
+use small_mp4::compression::{
+    get_video_metadata, CompressionSettings, ContentComplexity, FileSize, SizeEstimator, TargetEstimate, TargetSize, VideoMetadata,
+};
+use small_mp4::utils::{format_duration, format_file_size};
+
+use super::OutputFormat;
+use crate::i18n::t;
+
+/// Print what ffprobe reports about `input`
+pub async fn probe(input: &Path, format: OutputFormat) -> Result<()> {
+    let metadata = get_video_metadata(input).await?;
+
+    if format == OutputFormat::Json {
+        println!("{}", serde_json::to_string(&metadata)?);
+        return Ok(());
+    }
+
+    let file_size = std::fs::metadata(input)?.len();
+    let bitrate = match metadata.bitrate_kbps {
+        Some(bitrate) => t!("bitrate_kbps", bitrate = bitrate),
+        None => t!("unknown"),
+    };
+    let complexity = match metadata.estimated_complexity {
+        ContentComplexity::Low => t!("complexity_low"),
+        ContentComplexity::Medium => t!("complexity_medium"),
+        ContentComplexity::High => t!("complexity_high"),
+    };
+    println!("🎞️  {}", input.display());
+    for (label, value) in [
+        ("file_size", format_file_size(file_size)),
+        ("resolution", describe_video(&metadata)),
+        ("duration", format_duration(metadata.duration_seconds)),
+        ("codec", metadata.codec.clone()),
+        ("bitrate", bitrate),
+        ("probe_audio", if metadata.has_audio { t!("yes") } else { t!("no") }),
+        ("probe_complexity", complexity),
+    ] {
+        println!("   {:<12}{}", t!(label), value);
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct EstimateReport<'a> {
+    input: &'a Path,
+    metadata: &'a VideoMetadata,
+    estimates: &'a [TargetEstimate],
+}
+
+/// Print the planned bitrates, quality and encode time for each target
+/// size without encoding. Without `sizes`, every preset smaller than the
+/// input is estimated.
+pub async fn estimate(
+    input: &Path,
+    sizes: &[FileSize],
+    keep_resolution: bool,
+    capabilities: &HardwareCapabilities,
+    format: OutputFormat,
+) -> Result<()> {
+    let metadata = get_video_metadata(input).await?;
+    let targets = if sizes.is_empty() {
+        default_targets(FileSize::from_bytes(std::fs::metadata(input)?.len()))
+    } else {
+        sizes.to_vec()
+    };
+
+    let settings = CompressionSettings::builder()
+        .remove_audio(false)
+        .keep_resolution(keep_resolution)
+        .build();
+    let mut encoders = capabilities.available_encoders.clone();
+    if !encoders.contains(&HardwareEncoder::Software) {
+        encoders.push(HardwareEncoder::Software);
+    }
+    let estimates = SizeEstimator::new().estimate_targets(&metadata, &settings, &targets, &encoders);
+
+    if format == OutputFormat::Json {
+        let report = EstimateReport { input, metadata: &metadata, estimates: &estimates };
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
+
+    println!("{}", t!("estimate_title", input = input.display(), video = describe_video(&metadata),
+        duration = format_duration(metadata.duration_seconds)));
+    for estimate in &estimates {
+        let plan = &estimate.plan;
+        println!("\n  {}", t!("estimate_target", target = estimate.target_size, plan = plan.summary(),
+            quality = format!("{:.0}", plan.estimated_quality * 100.0)));
+
+        let times: Vec<_> = estimate.encode_times.iter()
+            .map(|time| format!("{:?} ~{}", time.encoder, format_duration(time.seconds)))
+            .collect();
+        println!("     {}", t!("estimate_encode_time", times = times.join(" · ")));
+
+        if !plan.feasible {
+            println!("     {}", t!("estimate_below_minimum", minimum = plan.minimum_size));
+        }
+    }
+
+    Ok(())
+}
+
+fn describe_video(metadata: &VideoMetadata) -> String {
+    format!("{}x{} @ {:.2} fps", metadata.width, metadata.height, metadata.fps)
+}
+
+/// Presets smaller than the input, or the smallest preset for tiny inputs
+fn default_targets(input_size: FileSize) -> Vec<FileSize> {
+    let targets: Vec<FileSize> = TargetSize::ALL.iter()
+        .map(|&preset| FileSize::from(preset))
+        .filter(|&target| target < input_size)
+        .collect();
+
+    if targets.is_empty() {
+        vec![TargetSize::Size1MB.into()]
+    } else {
+        targets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_targets_are_smaller_than_input() {
+        let targets = default_targets(FileSize::from_mb(40.0));
+        assert_eq!(targets, [TargetSize::Size1MB, TargetSize::Size5MB, TargetSize::Size10MB, TargetSize::Size30MB].map(FileSize::from));
+
+        assert_eq!(default_targets(FileSize::from_mb(0.5)), [FileSize::from(TargetSize::Size1MB)]);
+    }
+}
@@ -0,0 +1,251 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use small_mp4::compression::hardware::{HardwareEncoder, HardwarePreset, HardwareQuality};
+use small_mp4::compression::{CompressionSettings, FileSize, PlatformPreset, VideoEdit};
+
+use super::json_file;
+
+/// Default name of the journal kept next to a batch's outputs
+pub const JOURNAL_FILE_NAME: &str = ".small-mp4-journal.json";
+
+/// Where a journaled input stands
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JournalStatus {
+    /// Started but never finished; retried on the next run
+    Pending,
+    Done { output_size_mb: f64 },
+    /// Retried on the next run
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// SHA-256 of the input file
+    pub content_hash: String,
+    /// SHA-256 of the compression settings used
+    pub settings_hash: String,
+    /// Output path chosen on the first attempt and reused on every retry
+    pub output: PathBuf,
+    #[serde(flatten)]
+    pub status: JournalStatus,
+}
+
+/// Record of a batch run, so re-running the same command skips what is
+/// already done and retries the rest into the same output files.
+///
+/// Entries are keyed by the canonical input path and written to disk after
+/// every change, so the journal survives a crash or power loss mid-batch.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchJournal {
+    entries: BTreeMap<PathBuf, JournalEntry>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl BatchJournal {
+    /// Load the journal at `path`, or start an empty one
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut journal: Self = json_file::load(path, "batch journal")?.unwrap_or_default();
+        journal.path = path.to_path_buf();
+        Ok(journal)
+    }
+
+    /// Write the journal, replacing the old file only once the new one is complete
+    pub fn save(&self) -> Result<()> {
+        json_file::save(&self.path, self, "batch journal")
+    }
+
+    pub fn entry(&self, input: &Path) -> Option<&JournalEntry> {
+        self.entries.get(&journal_key(input))
+    }
+
+    /// Finished with this exact content and settings, and the output is
+    /// still where it was written (and where `expected_output` wants it, if given)
+    pub fn is_done(&self, input: &Path, content_hash: &str, settings_hash: &str, expected_output: Option<&Path>) -> bool {
+        self.entry(input).is_some_and(|entry| {
+            matches!(entry.status, JournalStatus::Done { .. })
+                && entry.content_hash == content_hash
+                && entry.settings_hash == settings_hash
+                && expected_output.is_none_or(|expected| expected == entry.output)
+                && entry.output.exists()
+        })
+    }
+
+    /// Whether `path` is the output of a journaled input, which a re-run
+    /// must not pick up as a new input
+    pub fn is_output(&self, path: &Path) -> bool {
+        let key = journal_key(path);
+        self.entries.values().any(|entry| journal_key(&entry.output) == key)
+    }
+
+    /// Every output path recorded, finished or not
+    pub fn outputs(&self) -> impl Iterator<Item = &Path> {
+        self.entries.values().map(|entry| entry.output.as_path())
+    }
+
+    pub fn record(&mut self, input: &Path, entry: JournalEntry) {
+        self.entries.insert(journal_key(input), entry);
+    }
+
+    /// Update the status of an input recorded earlier
+    pub fn set_status(&mut self, input: &Path, status: JournalStatus) {
+        if let Some(entry) = self.entries.get_mut(&journal_key(input)) {
+            entry.status = status;
+        }
+    }
+}
+
+/// The same file reached through different relative paths maps to one entry
+fn journal_key(input: &Path) -> PathBuf {
+    input.canonicalize().unwrap_or_else(|_| input.to_path_buf())
+}
+
+/// SHA-256 of a file's contents, as lowercase hex
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)
+        .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Version of [`SettingsFingerprint`]; bump it when its fields or their
+/// meaning change, so files journaled before are redone
+const SETTINGS_FINGERPRINT_VERSION: u32 = 1;
+
+/// The settings that shape an output, as hashed into the journal. Those that
+/// only change how the encode runs (GPU device, threads, pipeline) are left
+/// out, so changing them does not redo a batch.
+#[derive(Serialize)]
+struct SettingsFingerprint<'a> {
+    version: u32,
+    target_size: FileSize,
+    encoder: HardwareEncoder,
+    preset: HardwarePreset,
+    quality: HardwareQuality,
+    quality_level: u8,
+    compatibility_mode: bool,
+    remove_audio: bool,
+    keep_resolution: bool,
+    max_iterations: u32,
+    tolerance: f32,
+    platform: Option<&'a PlatformPreset>,
+    edit: VideoEdit,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+}
+
+impl<'a> From<&'a CompressionSettings> for SettingsFingerprint<'a> {
+    fn from(settings: &'a CompressionSettings) -> Self {
+        Self {
+            version: SETTINGS_FINGERPRINT_VERSION,
+            target_size: settings.target_size,
+            encoder: settings.hardware_encoder,
+            preset: settings.hardware_preset,
+            quality: settings.hardware_quality,
+            quality_level: settings.quality_level,
+            compatibility_mode: settings.compatibility_mode,
+            remove_audio: settings.remove_audio,
+            keep_resolution: settings.keep_resolution,
+            max_iterations: settings.size_convergence.max_iterations,
+            tolerance: settings.size_convergence.tolerance,
+            platform: settings.platform.as_ref(),
+            edit: settings.edit,
+            max_width: settings.max_width,
+            max_height: settings.max_height,
+        }
+    }
+}
+
+/// SHA-256 identifying the settings that shape an output, so changing any
+/// of them redoes the batch
+pub fn hash_settings(settings: &CompressionSettings) -> String {
+    let fingerprint = serde_json::to_vec(&SettingsFingerprint::from(settings))
+        .expect("settings fingerprint serializes");
+    format!("{:x}", Sha256::digest(&fingerprint))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_done_entries_are_skipped_until_something_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("a.mov");
+        let output = dir.path().join("a_compressed.mp4");
+        fs::write(&input, b"video").unwrap();
+        fs::write(&output, b"small").unwrap();
+
+        let journal_path = dir.path().join(JOURNAL_FILE_NAME);
+        let content_hash = hash_file(&input).unwrap();
+        let settings_hash = hash_settings(&CompressionSettings::default());
+
+        let mut journal = BatchJournal::open(&journal_path).unwrap();
+        journal.record(&input, JournalEntry {
+            content_hash: content_hash.clone(),
+            settings_hash: settings_hash.clone(),
+            output: output.clone(),
+            status: JournalStatus::Done { output_size_mb: 0.5 },
+        });
+        journal.save().unwrap();
+
+        let journal = BatchJournal::open(&journal_path).unwrap();
+        assert!(journal.is_done(&input, &content_hash, &settings_hash, None));
+        assert!(journal.is_done(&input, &content_hash, &settings_hash, Some(&output)));
+
+        let other_settings = hash_settings(&CompressionSettings::builder().quality_level(30).build());
+        assert!(!journal.is_done(&input, &content_hash, &other_settings, None));
+        // Settings that leave the output alone keep the entry done
+        let same_output = hash_settings(&CompressionSettings::builder().cuda_device(1).memory_optimization(true).build());
+        assert!(journal.is_done(&input, &content_hash, &same_output, None));
+        assert!(!journal.is_done(&input, &content_hash, &settings_hash, Some(&dir.path().join("out/a.mp4"))));
+
+        fs::write(&input, b"edited video").unwrap();
+        assert!(!journal.is_done(&input, &hash_file(&input).unwrap(), &settings_hash, None));
+
+        fs::remove_file(&output).unwrap();
+        assert!(!journal.is_done(&input, &content_hash, &settings_hash, None));
+    }
+
+    #[test]
+    fn test_failed_and_pending_entries_are_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("a.mov");
+        let output = dir.path().join("a_compressed.mp4");
+        fs::write(&input, b"video").unwrap();
+        fs::write(&output, b"partial").unwrap();
+
+        let mut journal = BatchJournal::open(&dir.path().join(JOURNAL_FILE_NAME)).unwrap();
+        journal.record(&input, JournalEntry {
+            content_hash: "hash".to_string(),
+            settings_hash: "settings".to_string(),
+            output: output.clone(),
+            status: JournalStatus::Pending,
+        });
+        assert!(!journal.is_done(&input, "hash", "settings", None));
+
+        journal.set_status(&input, JournalStatus::Failed { error: "boom".to_string() });
+        assert!(!journal.is_done(&input, "hash", "settings", None));
+        // The retry reuses the recorded output instead of picking a new name
+        assert_eq!(journal.entry(&input).unwrap().output, output);
+        assert!(journal.is_output(&output));
+        assert!(!journal.is_output(&input));
+    }
+}
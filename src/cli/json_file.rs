@@ -0,0 +1,60 @@
+use anyhow::{Result, anyhow};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// Read the JSON file at `path`, or `None` if there is none yet.
+///
+/// `what` names the file in error messages, e.g. "watch state file".
+pub fn load<T: DeserializeOwned>(path: &Path, what: &str) -> Result<Option<T>> {
+    match fs::read_to_string(path) {
+        Ok(json) => serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| anyhow!("Invalid {} {}: {}", what, path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow!("Failed to read {} {}: {}", what, path.display(), e)),
+    }
+}
+
+/// Write `value` as JSON, replacing the old file only once the new one is
+/// complete so a crash never leaves a truncated file behind
+pub fn save<T: Serialize>(path: &Path, value: &T, what: &str) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_string_pretty(value)?)?;
+    fs::rename(&temp_path, path)
+        .map_err(|e| anyhow!("Failed to write {} {}: {}", what, path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_save_replaces_the_file_and_load_reads_it_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("state.json");
+        assert_eq!(load::<BTreeMap<String, u32>>(&path, "state file").unwrap(), None);
+
+        save(&path, &BTreeMap::from([("a".to_string(), 1)]), "state file").unwrap();
+        save(&path, &BTreeMap::from([("b".to_string(), 2)]), "state file").unwrap();
+
+        let loaded: BTreeMap<String, u32> = load(&path, "state file").unwrap().unwrap();
+        assert_eq!(loaded, BTreeMap::from([("b".to_string(), 2)]));
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn test_load_names_the_file_when_it_is_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(&path, "{").unwrap();
+
+        let error = load::<BTreeMap<String, u32>>(&path, "state file").unwrap_err();
+        assert!(error.to_string().starts_with("Invalid state file"));
+    }
+}
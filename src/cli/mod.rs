@@ -1,9 +1,25 @@
 use clap::{Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
 use std::path::PathBuf;
 
+use small_mp4::compression::{
+    parse_timestamp, CompressionSettings, CompressionSettingsBuilder, CropRect, FileSize, PlatformPreset, Rotation,
+    SizeConvergence, VideoEdit, MAX_QUALITY_LEVEL,
+};
+use small_mp4::compression::hardware::HardwareCapabilities;
+
+use crate::i18n;
+
+pub mod batch;
+pub mod config;
 pub mod hardware_cli;
+pub mod inspect;
+pub mod journal;
+pub mod json_file;
+pub mod watch;
 
 use hardware_cli::{HardwareEncoderCli, HardwarePresetCli, HardwareQualityCli};
+use watch::OriginalsAction;
 
 #[derive(Parser)]
 #[command(name = "small-mp4")]
@@ -12,120 +28,398 @@ use hardware_cli::{HardwareEncoderCli, HardwarePresetCli, HardwareQualityCli};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+    
+    /// Output format: text for people, json for scripts (one JSON record per line)
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    pub format: OutputFormat,
+    
+    /// Shorthand for --format json
+    #[arg(long, global = true)]
+    pub json: bool,
+    
+    /// Config file with default settings and profiles
+    /// (defaults to $XDG_CONFIG_HOME/small-mp4/config.toml)
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    
+    /// Named profile from the config file, e.g. --profile work-chat
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+}
+
+impl Cli {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json { OutputFormat::Json } else { self.format }
+    }
+}
+
+/// How results are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable messages and tables
+    Text,
+    /// JSON only, one record per line; messages and logs go to stderr
+    Json,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Compress a video file
+    /// Compress one or more video files
     Compress {
-        /// Input video file
-        input: PathBuf,
+        /// Input video files, directories or glob patterns (e.g. "clips/*.mov")
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
         
-        /// Output file (optional, defaults to input_compressed.mp4)
-        #[arg(short, long)]
+        /// Output file for a single input (optional, defaults to input_compressed.mp4)
+        #[arg(short, long, conflicts_with = "output_dir")]
         output: Option<PathBuf>,
         
+        /// Write outputs to this directory, mirroring the layout of the inputs
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        
+        /// Include videos in subdirectories of directory inputs
+        #[arg(short, long)]
+        recursive: bool,
+        
+        /// Number of files to compress at once (defaults to what the system can handle)
+        #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+        jobs: Option<u16>,
+        
+        /// Journal of finished files for batches, so re-running skips them
+        /// (defaults to .small-mp4-journal.json in the output dir or current dir)
+        #[arg(long, conflicts_with = "no_journal")]
+        journal: Option<PathBuf>,
+        
+        /// Don't keep a journal; compress every file again
+        #[arg(long)]
+        no_journal: bool,
+        
+        /// Print the plan, fallback encoders and ffmpeg commands for each file without running them
+        #[arg(long)]
+        dry_run: bool,
+        
+        #[command(flatten)]
+        edit: EditArgs,
+        
         #[command(flatten)]
         settings: CompressionCliSettings,
     },
     
+    /// Watch a folder and compress new videos once they finish writing
+    Watch {
+        /// Folder to watch
+        dir: PathBuf,
+        
+        /// Where compressed files go (defaults to <dir>/compressed)
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+        
+        /// Also watch subdirectories
+        #[arg(short, long)]
+        recursive: bool,
+        
+        /// What to do with originals once they are compressed
+        #[arg(long, value_enum, default_value = "mark")]
+        originals: OriginalsAction,
+        
+        /// Where `--originals move` puts originals (defaults to <dir>/originals)
+        #[arg(long)]
+        archive_dir: Option<PathBuf>,
+        
+        /// File recording what has been processed (defaults to <dir>/.small-mp4-watch.json)
+        #[arg(long)]
+        state_file: Option<PathBuf>,
+        
+        /// Seconds between folder scans
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u64).range(1..))]
+        poll_interval: u64,
+        
+        /// Seconds a file must stay unchanged before it is compressed
+        #[arg(long, default_value_t = 10)]
+        settle: u64,
+        
+        #[command(flatten)]
+        settings: CompressionCliSettings,
+    },
+    
+    /// Show what ffprobe reports about a video, including its complexity class
+    Probe {
+        /// Input video file
+        input: PathBuf,
+        
+        /// Language for output messages, e.g. ko or ja [default: en]
+        #[arg(short, long, value_parser = i18n::parse_language)]
+        lang: Option<String>,
+    },
+    
+    /// Estimate bitrates, quality and encode time per target size, without encoding
+    Estimate {
+        /// Input video file
+        input: PathBuf,
+        
+        /// Target size to estimate; repeat for several (defaults to every preset smaller than the input)
+        #[arg(short, long = "size")]
+        sizes: Vec<FileSize>,
+        
+        /// Plan for the original resolution instead of downscaling
+        #[arg(long)]
+        keep_resolution: bool,
+        
+        /// Language for output messages, e.g. ko or ja [default: en]
+        #[arg(short, long, value_parser = i18n::parse_language)]
+        lang: Option<String>,
+    },
+    
     /// Launch GUI interface
     Gui,
     
     /// List available hardware encoders
     #[command(name = "list-hw")]
-    ListHardware,
+    ListHardware {
+        /// Language for output messages, e.g. ko or ja [default: en]
+        #[arg(short, long, value_parser = i18n::parse_language)]
+        lang: Option<String>,
+    },
+}
+
+impl Commands {
+    /// `--lang` of the commands that take no compression settings
+    pub fn lang(&self) -> Option<&str> {
+        match self {
+            Commands::Probe { lang, .. }
+            | Commands::Estimate { lang, .. }
+            | Commands::ListHardware { lang } => lang.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// Editing flags of `compress`, applied to every input before encoding.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct EditArgs {
+    /// Start of the part to keep, in seconds or [h:]m:ss, e.g. 1:30
+    #[arg(long, value_parser = parse_timestamp)]
+    pub start: Option<f64>,
+    
+    /// End of the part to keep, in seconds or [h:]m:ss; the size target covers only the kept part
+    #[arg(long, value_parser = parse_timestamp)]
+    pub end: Option<f64>,
+    
+    /// Keep only WIDTH:HEIGHT:X:Y of the frame, in source pixels, e.g. 1080:1080:420:0
+    #[arg(long)]
+    pub crop: Option<CropRect>,
+    
+    /// Rotate clockwise by 90, 180 or 270 degrees, after cropping
+    #[arg(long)]
+    pub rotate: Option<Rotation>,
 }
 
-#[derive(clap::Args)]
+impl EditArgs {
+    pub fn to_video_edit(&self) -> VideoEdit {
+        VideoEdit {
+            start: self.start,
+            end: self.end,
+            crop: self.crop,
+            rotation: self.rotate.unwrap_or_default(),
+        }
+    }
+}
+
+/// Compression flags shared by `compress` and `watch`.
+///
+/// Every field is optional so a flag left out falls through to the selected
+/// profile, then the config file's defaults, then the built-in default. The
+/// same struct is read from the config file, with the flag names as keys.
+#[derive(clap::Args, Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct CompressionCliSettings {
-    /// Target size preset
-    #[arg(short, long, value_enum, default_value = "10mb")]
-    pub size: SizePreset,
+    /// Target size, e.g. 8mb, 24.5MB, 10MiB, 750kb (MB = 1000^2 bytes, MiB = 1024^2).
+    /// Presets: 1mb, 5mb, 10mb, 30mb, 50mb, 100mb, 250mb, 500mb, 1gb [default: 10mb]
+    #[arg(short, long)]
+    pub size: Option<FileSize>,
+    
+    /// Sharing platform preset: chat-8mb, email-25mb, short-video, or one from the config file.
+    /// Sets the size limit, resolution, frame rate, codec and container, and checks the output
+    #[arg(short, long)]
+    pub platform: Option<String>,
+    
+    /// The preset `platform` names, looked up by [`Config::resolve`](config::Config::resolve)
+    #[arg(skip)]
+    #[serde(skip)]
+    pub platform_preset: Option<PlatformPreset>,
     
     /// Hardware encoder to use
     #[arg(long, value_enum)]
+    #[serde(deserialize_with = "config::value_enum")]
     pub hw_encoder: Option<HardwareEncoderCli>,
     
-    /// Hardware encoding preset
-    #[arg(long, value_enum, default_value = "medium")]
-    pub hw_preset: HardwarePresetCli,
+    /// Hardware encoding preset [default: medium]
+    #[arg(long, value_enum)]
+    #[serde(deserialize_with = "config::value_enum")]
+    pub hw_preset: Option<HardwarePresetCli>,
+    
+    /// Rate control: size target (auto, variable) or constant quality (constant, constrained) [default: auto]
+    #[arg(long, value_enum)]
+    #[serde(deserialize_with = "config::value_enum")]
+    pub hw_quality: Option<HardwareQualityCli>,
     
-    /// Hardware quality control
-    #[arg(long, value_enum, default_value = "auto")]
-    pub hw_quality: HardwareQualityCli,
+    /// Quality level for constant/constrained rate control (0-51, lower is better) [default: 23]
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=MAX_QUALITY_LEVEL as i64))]
+    pub quality: Option<u8>,
     
     /// Specific CUDA device ID (0, 1, 2, etc.)
     #[arg(long)]
     pub cuda_device: Option<u32>,
     
     /// Force software encoding (disable hardware acceleration)
-    #[arg(long)]
-    pub force_software: bool,
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub force_software: Option<bool>,
     
     /// Enable memory optimization for hardware encoding
-    #[arg(long)]
-    pub memory_opt: bool,
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub memory_opt: Option<bool>,
     
     /// Compatibility mode - Force x264 codec for maximum compatibility
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub compatibility: Option<bool>,
+    
+    /// Keep the source resolution and frame rate even when the target is tight
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub keep_resolution: Option<bool>,
+    
+    /// Scale wider videos down to this many pixels, keeping the aspect ratio
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+    pub max_width: Option<u32>,
+    
+    /// Scale taller videos down to this many pixels, keeping the aspect ratio, e.g. 720
+    #[arg(long, value_parser = clap::value_parser!(u32).range(2..))]
+    pub max_height: Option<u32>,
+    
+    /// Exit with an error if the output is still larger than the target
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub strict: Option<bool>,
+    
+    /// Maximum number of encodes used to bring the output under the target (1 = no re-encoding) [default: 4]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_iterations: Option<u32>,
+    
+    /// Allowed overshoot above the target size, in percent [default: 0]
     #[arg(long)]
-    pub compatibility: bool,
+    pub size_tolerance: Option<f32>,
     
-    /// Language for output messages
-    #[arg(short, long, value_enum, default_value = "en")]
-    pub lang: Language,
-}
-
-#[derive(Clone, ValueEnum, Debug)]
-pub enum SizePreset {
-    #[value(name = "1mb")]
-    Size1MB,
-    #[value(name = "5mb")] 
-    Size5MB,
-    #[value(name = "10mb")]
-    Size10MB,
-    #[value(name = "30mb")]
-    Size30MB,
-    #[value(name = "50mb")]
-    Size50MB,
-    #[value(name = "100mb")]
-    Size100MB,
-    #[value(name = "250mb")]
-    Size250MB,
-    #[value(name = "500mb")]
-    Size500MB,
-    #[value(name = "1gb")]
-    Size1GB,
+    /// Language for output messages, e.g. ko or ja [default: en]
+    #[arg(short, long, value_parser = i18n::parse_language)]
+    pub lang: Option<String>,
 }
 
-impl SizePreset {
-    pub fn as_mb(&self) -> f32 {
-        match self {
-            SizePreset::Size1MB => 1.0,
-            SizePreset::Size5MB => 5.0,
-            SizePreset::Size10MB => 10.0,
-            SizePreset::Size30MB => 30.0,
-            SizePreset::Size50MB => 50.0,
-            SizePreset::Size100MB => 100.0,
-            SizePreset::Size250MB => 250.0,
-            SizePreset::Size500MB => 500.0,
-            SizePreset::Size1GB => 1000.0,
+impl CompressionCliSettings {
+    /// Fill every unset field from `fallback`
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            size: self.size.or(fallback.size),
+            platform: self.platform.or(fallback.platform),
+            platform_preset: self.platform_preset.or(fallback.platform_preset),
+            hw_encoder: self.hw_encoder.or(fallback.hw_encoder),
+            hw_preset: self.hw_preset.or(fallback.hw_preset),
+            hw_quality: self.hw_quality.or(fallback.hw_quality),
+            quality: self.quality.or(fallback.quality),
+            cuda_device: self.cuda_device.or(fallback.cuda_device),
+            force_software: self.force_software.or(fallback.force_software),
+            memory_opt: self.memory_opt.or(fallback.memory_opt),
+            compatibility: self.compatibility.or(fallback.compatibility),
+            keep_resolution: self.keep_resolution.or(fallback.keep_resolution),
+            max_width: self.max_width.or(fallback.max_width),
+            max_height: self.max_height.or(fallback.max_height),
+            strict: self.strict.or(fallback.strict),
+            max_iterations: self.max_iterations.or(fallback.max_iterations),
+            size_tolerance: self.size_tolerance.or(fallback.size_tolerance),
+            lang: self.lang.or(fallback.lang),
         }
     }
-}
-
-#[derive(Clone, ValueEnum, Debug)]
-pub enum Language {
-    #[value(name = "ko")]
-    Korean,
-    #[value(name = "en")]
-    English,
-    #[value(name = "ja")]
-    Japanese,
+    
+    pub fn strict(&self) -> bool {
+        self.strict.unwrap_or(false)
+    }
+    
+    /// Turn the settings into engine settings for this machine, using the
+    /// CLI's built-in default for anything left unset
+    pub fn to_compression_settings(&self, hw_capabilities: &HardwareCapabilities) -> CompressionSettings {
+        let defaults = CompressionSettings::builder()
+            .compatibility_mode(false)
+            .remove_audio(false);  // Default to keeping audio in CLI
+        self.apply(defaults).build_with_capabilities(hw_capabilities)
+    }
+    
+    /// Set the fields that have a value on `builder`, leaving its other
+    /// defaults alone, so the GUI can keep its own defaults
+    pub fn apply(&self, mut builder: CompressionSettingsBuilder) -> CompressionSettingsBuilder {
+        // The size may only lower the platform's limit
+        let preset = self.platform_preset.clone()
+            .or_else(|| self.platform.as_deref().and_then(PlatformPreset::find_built_in));
+        if let Some(preset) = preset {
+            builder = builder.platform(preset);
+        }
+        if let Some(size) = self.size {
+            builder = builder.target_size(size);
+        }
+        if let Some(preset) = &self.hw_preset {
+            builder = builder.hardware_preset(preset.to_hardware_preset());
+        }
+        if let Some(quality) = &self.hw_quality {
+            builder = builder.hardware_quality(quality.to_hardware_quality());
+        }
+        if let Some(level) = self.quality {
+            builder = builder.quality_level(level);
+        }
+        if let Some(force) = self.force_software {
+            builder = builder.force_software(force);
+        }
+        if let Some(enabled) = self.memory_opt {
+            builder = builder.memory_optimization(enabled);
+        }
+        if let Some(enabled) = self.compatibility {
+            builder = builder.compatibility_mode(enabled);
+        }
+        if let Some(keep) = self.keep_resolution {
+            builder = builder.keep_resolution(keep);
+        }
+        if let Some(width) = self.max_width {
+            builder = builder.max_width(width);
+        }
+        if let Some(height) = self.max_height {
+            builder = builder.max_height(height);
+        }
+        if self.max_iterations.is_some() || self.size_tolerance.is_some() {
+            let defaults = SizeConvergence::default();
+            builder = builder.size_convergence(SizeConvergence {
+                max_iterations: self.max_iterations.unwrap_or(defaults.max_iterations).max(1),
+                tolerance: self.size_tolerance.map_or(defaults.tolerance, |percent| percent.max(0.0) / 100.0),
+            });
+        }
+        
+        if let Some(encoder) = &self.hw_encoder {
+            if !matches!(encoder, HardwareEncoderCli::Auto) {
+                builder = builder.hardware_encoder(encoder.to_hardware_encoder());
+            }
+        }
+        
+        if let Some(device_id) = self.cuda_device {
+            builder = builder.cuda_device(device_id);
+        }
+        
+        builder
+    }
 }
 
 // Usage examples that can be shown in help:
 // small-mp4 compress video.mov --size 10mb --hw-encoder nvenc-h264 --hw-preset fast
 // small-mp4 compress video.mov --auto --hw-encoder auto --cuda-device 0
+// small-mp4 compress video.mov --hw-quality constant --quality 20  # Quality over size
+// small-mp4 compress video.mov --max-height 720  # At most 720p
+// small-mp4 compress ~/Videos -r --output-dir ~/Videos/small  # Whole folder tree
+// small-mp4 watch ~/Recordings --originals move  # Compress new recordings as they land
 // small-mp4 list-hw  # List available hardware encoders
+// small-mp4 compress clips/ --json  # One JSON record per file for scripts
 // small-mp4 compress video.mov --force-software  # Disable hardware acceleration
+// small-mp4 compress talk.mp4 --start 4:10 --end 4:30 --size 8mb  # Share 20 seconds of a recording
@@ -0,0 +1,355 @@
+use anyhow::{Result, anyhow};
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+use small_mp4::compression::hardware::HardwareCapabilities;
+use small_mp4::compression::{CancellationToken, Cancelled, CompressionEngine, CompressionResult, CompressionSettings};
+use small_mp4::utils::is_video_file;
+
+use super::batch::{describe_result, BatchEntry, BatchInput, BatchOutcome};
+use super::{json_file, OutputFormat};
+use crate::i18n::t;
+
+/// Default name of the state file kept in the watched folder
+pub const STATE_FILE_NAME: &str = ".small-mp4-watch.json";
+
+/// What happens to an original once it has been compressed
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OriginalsAction {
+    /// Leave it in place; the state file marks it as done
+    Mark,
+    /// Move it to the archive folder
+    Move,
+}
+
+/// How `watch` finds, compresses and files away videos.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub recursive: bool,
+    pub originals: OriginalsAction,
+    pub archive_dir: PathBuf,
+    pub state_file: PathBuf,
+    /// Time between folder scans
+    pub poll_interval: Duration,
+    /// How long a file must stay unchanged before it is compressed
+    pub settle: Duration,
+}
+
+impl WatchOptions {
+    /// Options for `dir` with outputs, originals and state kept inside it
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            output_dir: dir.join("compressed"),
+            archive_dir: dir.join("originals"),
+            state_file: dir.join(STATE_FILE_NAME),
+            dir,
+            recursive: false,
+            originals: OriginalsAction::Mark,
+            poll_interval: Duration::from_secs(5),
+            settle: Duration::from_secs(10),
+        }
+    }
+
+    /// Video files under the watched folder, skipping our own output and
+    /// archive folders and hidden files (often partial uploads)
+    fn scan(&self) -> Vec<PathBuf> {
+        let max_depth = if self.recursive { usize::MAX } else { 1 };
+        let mut found: Vec<_> = WalkDir::new(&self.dir)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|entry| {
+                let hidden = entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.');
+                !hidden && entry.path() != self.output_dir && entry.path() != self.archive_dir
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_video_file(entry.path()))
+            .map(|entry| entry.into_path())
+            .collect();
+        found.sort();
+        found
+    }
+}
+
+/// Size and modification time, used to tell a finished file from one that
+/// is still being written, and a new file from one already processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    pub size: u64,
+    /// Seconds since the Unix epoch
+    pub modified: u64,
+}
+
+impl FileFingerprint {
+    pub fn of(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata.modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Ok(Self { size: metadata.len(), modified })
+    }
+}
+
+/// Outcome recorded for a processed file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum WatchOutcome {
+    Done { output: PathBuf },
+    /// Not retried until the file changes
+    Failed { error: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessedFile {
+    pub fingerprint: FileFingerprint,
+    #[serde(flatten)]
+    pub outcome: WatchOutcome,
+}
+
+/// Files already handled, keyed by their path relative to the watched
+/// folder, so a restart picks up where the last run stopped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WatchState {
+    files: BTreeMap<PathBuf, ProcessedFile>,
+}
+
+impl WatchState {
+    /// Load the state file, or start empty if there is none yet
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(json_file::load(path, "watch state file")?.unwrap_or_default())
+    }
+
+    /// Write the state, replacing the old file only once the new one is complete
+    pub fn save(&self, path: &Path) -> Result<()> {
+        json_file::save(path, self, "watch state file")
+    }
+
+    /// Already handled in this exact version
+    pub fn is_processed(&self, relative: &Path, fingerprint: FileFingerprint) -> bool {
+        self.files.get(relative).is_some_and(|file| file.fingerprint == fingerprint)
+    }
+
+    pub fn record(&mut self, relative: PathBuf, fingerprint: FileFingerprint, outcome: WatchOutcome) {
+        self.files.insert(relative, ProcessedFile { fingerprint, outcome });
+    }
+}
+
+/// Decides when a file has stopped growing.
+#[derive(Debug)]
+pub struct StabilityTracker {
+    settle: Duration,
+    seen: HashMap<PathBuf, (FileFingerprint, Instant)>,
+}
+
+impl StabilityTracker {
+    pub fn new(settle: Duration) -> Self {
+        Self { settle, seen: HashMap::new() }
+    }
+
+    /// Record the file's current fingerprint; true once it has been
+    /// unchanged for the settle time
+    pub fn observe(&mut self, path: &Path, fingerprint: FileFingerprint, now: Instant) -> bool {
+        match self.seen.get(path) {
+            Some((seen, since)) if *seen == fingerprint => now.duration_since(*since) >= self.settle,
+            _ => {
+                self.seen.insert(path.to_path_buf(), (fingerprint, now));
+                false
+            }
+        }
+    }
+
+    pub fn forget(&mut self, path: &Path) {
+        self.seen.remove(path);
+    }
+}
+
+/// Watch `options.dir` until `cancel` is cancelled, compressing every video
+/// that appears once it has finished being written.
+pub async fn run(
+    options: &WatchOptions,
+    settings: &CompressionSettings,
+    capabilities: HardwareCapabilities,
+    cancel: &CancellationToken,
+    format: OutputFormat,
+) -> Result<()> {
+    if !options.dir.is_dir() {
+        return Err(anyhow!("Watch folder does not exist: {}", options.dir.display()));
+    }
+
+    // Canonical paths, so the scan recognises our own folders however they were given
+    let mut options = options.clone();
+    fs::create_dir_all(&options.output_dir)?;
+    options.dir = options.dir.canonicalize()?;
+    options.output_dir = options.output_dir.canonicalize()?;
+    if options.originals == OriginalsAction::Move {
+        fs::create_dir_all(&options.archive_dir)?;
+        options.archive_dir = options.archive_dir.canonicalize()?;
+    }
+    if options.output_dir == options.dir {
+        return Err(anyhow!("The output folder must differ from the watched folder, or outputs would be compressed again"));
+    }
+
+    let mut state = WatchState::load(&options.state_file)?;
+    let mut tracker = StabilityTracker::new(options.settle);
+    // One engine for the whole session, so encoder failures are remembered
+    let mut engine = CompressionEngine::new(capabilities);
+
+    if format == OutputFormat::Text {
+        println!("{}", t!("watch_started", dir = options.dir.display()));
+    }
+
+    while !cancel.is_cancelled() {
+        for path in options.scan() {
+            let relative = path.strip_prefix(&options.dir).unwrap_or(&path).to_path_buf();
+            let Ok(fingerprint) = FileFingerprint::of(&path) else { continue };
+
+            if state.is_processed(&relative, fingerprint) {
+                continue;
+            }
+            if !tracker.observe(&path, fingerprint, Instant::now()) {
+                debug!("Waiting for {} to finish writing", relative.display());
+                continue;
+            }
+            tracker.forget(&path);
+
+            let input = BatchInput { path: path.clone(), relative: relative.clone() };
+            let output = input.output_path(&options.output_dir);
+
+            if format == OutputFormat::Text {
+                println!("{}", t!("watch_compressing", file = relative.display()));
+            }
+            let result = process(&mut engine, &options, settings, &input, &output, cancel).await;
+            let outcome = match &result {
+                Err(e) if e.is::<Cancelled>() => return Ok(()),
+                Ok(_) => WatchOutcome::Done { output },
+                Err(e) => WatchOutcome::Failed { error: e.to_string() },
+            };
+            report(BatchEntry { input: path.clone(), outcome: result.into() }, format);
+
+            // Kept in memory regardless, so this session does not redo the file
+            state.record(relative, fingerprint, outcome);
+            if let Err(e) = state.save(&options.state_file) {
+                warn!("{:#}", e);
+            }
+
+            if cancel.is_cancelled() {
+                return Ok(());
+            }
+        }
+
+        tokio::time::sleep(options.poll_interval).await;
+    }
+
+    info!("Stopped watching {}", options.dir.display());
+    Ok(())
+}
+
+/// Compress one settled file and file its original away. Any error is this
+/// file's outcome; the watch carries on with the next one.
+async fn process(
+    engine: &mut CompressionEngine,
+    options: &WatchOptions,
+    settings: &CompressionSettings,
+    input: &BatchInput,
+    output: &Path,
+    cancel: &CancellationToken,
+) -> Result<CompressionResult> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| anyhow!("Failed to create {}: {}", parent.display(), e))?;
+    }
+
+    let result = engine.compress_cancellable(&input.path, Some(output), settings, None, cancel).await?;
+    if options.originals == OriginalsAction::Move {
+        archive_original(&input.path, &options.archive_dir.join(&input.relative))?;
+    }
+    Ok(result)
+}
+
+/// Print the outcome of one file as a line of text or a JSON record
+fn report(entry: BatchEntry, format: OutputFormat) {
+    match (&entry.outcome, format) {
+        (_, OutputFormat::Json) => println!("{}", entry.json_record()),
+        (BatchOutcome::Compressed(result), OutputFormat::Text) => println!("✅ {}", describe_result(result)),
+        (BatchOutcome::Failed(e), OutputFormat::Text) => eprintln!("❌ {}: {}", entry.input.display(), e),
+        (BatchOutcome::Skipped { .. }, OutputFormat::Text) => {}
+    }
+}
+
+/// Move a compressed original out of the watched folder
+fn archive_original(path: &Path, destination: &Path) -> Result<()> {
+    if let Some(parent) = destination.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    // rename() can't cross filesystems; copy and delete instead
+    if fs::rename(path, destination).is_err() {
+        fs::copy(path, destination)
+            .map_err(|e| anyhow!("Failed to move {} to {}: {}", path.display(), destination.display(), e))?;
+        if let Err(e) = fs::remove_file(path) {
+            warn!("Copied {} to the archive but could not remove it: {}", path.display(), e);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_must_settle_before_it_is_ready() {
+        let mut tracker = StabilityTracker::new(Duration::from_secs(10));
+        let path = Path::new("clip.mov");
+        let start = Instant::now();
+        let growing = FileFingerprint { size: 100, modified: 1 };
+        let grown = FileFingerprint { size: 200, modified: 2 };
+
+        assert!(!tracker.observe(path, growing, start));
+        assert!(!tracker.observe(path, grown, start + Duration::from_secs(5)));
+        assert!(!tracker.observe(path, grown, start + Duration::from_secs(10)));
+        assert!(tracker.observe(path, grown, start + Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join(STATE_FILE_NAME);
+        let fingerprint = FileFingerprint { size: 100, modified: 1 };
+
+        let mut state = WatchState::load(&state_file).unwrap();
+        state.record(PathBuf::from("a.mov"), fingerprint, WatchOutcome::Done { output: PathBuf::from("compressed/a.mp4") });
+        state.save(&state_file).unwrap();
+
+        let state = WatchState::load(&state_file).unwrap();
+        assert!(state.is_processed(Path::new("a.mov"), fingerprint));
+        // Replaced by a different file of the same name
+        assert!(!state.is_processed(Path::new("a.mov"), FileFingerprint { size: 300, modified: 5 }));
+        assert!(!state.is_processed(Path::new("b.mov"), fingerprint));
+    }
+
+    #[test]
+    fn test_scan_skips_own_folders_and_hidden_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.mov", ".partial.mov", "notes.txt", "compressed/a.mp4", "originals/old.mov", "sub/b.mkv"] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+
+        let mut options = WatchOptions::new(dir.path().to_path_buf());
+        assert_eq!(options.scan(), [dir.path().join("a.mov")]);
+
+        options.recursive = true;
+        assert_eq!(options.scan(), [dir.path().join("a.mov"), dir.path().join("sub/b.mkv")]);
+    }
+}
@@ -0,0 +1,187 @@
+//! Cancelling and pausing running encodes.
+//!
+//! A [`CancellationToken`] is shared between whoever controls a job (CLI
+//! signal handler, GUI buttons) and the [`FfmpegRunner`](super::FfmpegRunner)
+//! executing it. The runner attaches every FFmpeg process it starts, so
+//! cancelling kills the whole process tree immediately and pausing suspends it.
+
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Error returned by the engine when a job was cancelled through its token.
+///
+/// Check for it with `error.is::<Cancelled>()`; the partial output and any
+/// two-pass log files have already been removed.
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("compression cancelled")]
+pub struct Cancelled;
+
+/// Shared handle used to cancel, pause and resume a compression job.
+///
+/// Clones refer to the same job.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<TokenState>,
+}
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    /// Process (group) ids of the FFmpeg processes currently running
+    processes: Mutex<Vec<u32>>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop the job, killing any running FFmpeg process tree.
+    pub fn cancel(&self) {
+        let processes = self.inner.processes.lock().unwrap();
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        info!("Cancelling compression");
+        for &pid in processes.iter() {
+            kill_process_tree(pid);
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Whether pausing is available on this platform
+    pub fn supports_pause() -> bool {
+        cfg!(unix)
+    }
+
+    /// Suspend the running FFmpeg processes until [`resume`](Self::resume).
+    pub fn pause(&self) -> Result<()> {
+        if !Self::supports_pause() {
+            return Err(anyhow!("Pausing encodes is only supported on Unix"));
+        }
+
+        let processes = self.inner.processes.lock().unwrap();
+        if !self.inner.paused.swap(true, Ordering::SeqCst) {
+            info!("Pausing compression");
+            for &pid in processes.iter() {
+                suspend_process_tree(pid, true);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<()> {
+        if !Self::supports_pause() {
+            return Err(anyhow!("Pausing encodes is only supported on Unix"));
+        }
+
+        let processes = self.inner.processes.lock().unwrap();
+        if self.inner.paused.swap(false, Ordering::SeqCst) {
+            info!("Resuming compression");
+            for &pid in processes.iter() {
+                suspend_process_tree(pid, false);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::SeqCst)
+    }
+
+    /// Track a freshly spawned process, applying a cancel or pause that
+    /// arrived before it started. On Unix `pid` must lead its own process group.
+    pub(crate) fn attach(&self, pid: u32) {
+        let mut processes = self.inner.processes.lock().unwrap();
+        processes.push(pid);
+
+        if self.is_cancelled() {
+            kill_process_tree(pid);
+        } else if self.is_paused() {
+            suspend_process_tree(pid, true);
+        }
+    }
+
+    pub(crate) fn detach(&self, pid: u32) {
+        self.inner.processes.lock().unwrap().retain(|&attached| attached != pid);
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_tree(pid: u32) {
+    signal_process_group(pid, libc::SIGKILL);
+}
+
+#[cfg(windows)]
+fn kill_process_tree(pid: u32) {
+    // taskkill /T takes the child processes down with it
+    let status = std::process::Command::new("taskkill")
+        .args(["/PID", &pid.to_string(), "/T", "/F"])
+        .output();
+    if let Err(e) = status {
+        warn!("Failed to kill FFmpeg process {}: {}", pid, e);
+    }
+}
+
+#[cfg(unix)]
+fn suspend_process_tree(pid: u32, suspend: bool) {
+    signal_process_group(pid, if suspend { libc::SIGSTOP } else { libc::SIGCONT });
+}
+
+#[cfg(windows)]
+fn suspend_process_tree(_pid: u32, _suspend: bool) {}
+
+#[cfg(unix)]
+fn signal_process_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: kill() has no memory-safety preconditions; a negative pid
+    // addresses the process group the runner created for FFmpeg.
+    let result = unsafe { libc::kill(-(pgid as libc::pid_t), signal) };
+    if result != 0 {
+        warn!("Failed to send signal {} to FFmpeg process group {}: {}",
+              signal, pgid, std::io::Error::last_os_error());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_state() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+
+        assert!(!token.is_cancelled());
+        handle.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cancel_kills_attached_process_group() {
+        use std::os::unix::process::CommandExt;
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+
+        let token = CancellationToken::new();
+        token.attach(child.id());
+        token.pause().unwrap();
+        assert!(token.is_paused());
+        token.cancel();
+
+        let status = child.wait().unwrap();
+        assert!(!status.success());
+        token.detach(child.id());
+    }
+}
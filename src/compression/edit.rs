@@ -0,0 +1,314 @@
+//! Trimming, cropping and rotation applied before encoding.
+//!
+//! A [`VideoEdit`] turns into FFmpeg input options (the trimmed range) and
+//! video filters (crop, then rotation) ahead of any scaling. The planner
+//! sees the edited video through [`VideoEdit::apply`], so the bit budget is
+//! spent on the part that is kept.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::serde_util::string_serde;
+use super::{FilterChain, VideoFilter, VideoMetadata};
+
+/// Part of the frame to keep, in source pixels, written `WIDTH:HEIGHT:X:Y`
+/// like FFmpeg's crop filter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRect {
+    pub width: u32,
+    pub height: u32,
+    /// Left edge
+    pub x: u32,
+    /// Top edge
+    pub y: u32,
+}
+
+impl CropRect {
+    /// Size after cropping, rounded down to even sides as 4:2:0 encoders require
+    pub fn output_size(&self) -> (u32, u32) {
+        ((self.width & !1).max(2), (self.height & !1).max(2))
+    }
+
+    fn fits(&self, width: u32, height: u32) -> bool {
+        self.width >= 2 && self.height >= 2
+            && self.x.saturating_add(self.width) <= width
+            && self.y.saturating_add(self.height) <= height
+    }
+}
+
+impl fmt::Display for CropRect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}:{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+impl FromStr for CropRect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<u32> = s.split(':').map(|part| part.trim().parse()).collect::<Result<_, _>>()
+            .map_err(|_| format!("invalid crop '{}', expected WIDTH:HEIGHT:X:Y, e.g. 1280:720:320:180", s))?;
+        match parts.as_slice() {
+            &[width, height, x, y] if width > 0 && height > 0 => Ok(Self { width, height, x, y }),
+            _ => Err(format!("invalid crop '{}', expected WIDTH:HEIGHT:X:Y, e.g. 1280:720:320:180", s)),
+        }
+    }
+}
+
+/// Clockwise rotation, written in degrees: `0`, `90`, `180` or `270`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Rotate180,
+    Clockwise270,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [Rotation::None, Rotation::Clockwise90, Rotation::Rotate180, Rotation::Clockwise270];
+
+    pub fn degrees(&self) -> u16 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 90,
+            Rotation::Rotate180 => 180,
+            Rotation::Clockwise270 => 270,
+        }
+    }
+
+    /// Whether width and height trade places
+    pub fn swaps_sides(&self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Clockwise270)
+    }
+
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.degrees())
+    }
+}
+
+impl FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let degrees = s.trim().trim_end_matches('°');
+        Rotation::ALL.into_iter()
+            .find(|rotation| rotation.degrees().to_string() == degrees)
+            .ok_or_else(|| format!("invalid rotation '{}', expected 0, 90, 180 or 270", s))
+    }
+}
+
+string_serde!(CropRect);
+string_serde!(Rotation);
+
+/// An edit that does not fit the video it is applied to
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum EditError {
+    #[error("the start ({}) is not before the end of the {} video", format_timestamp(*start), format_timestamp(*duration))]
+    StartPastEnd { start: f64, duration: f64 },
+    #[error("the end ({}) is not after the start ({})", format_timestamp(*end), format_timestamp(*start))]
+    EmptyRange { start: f64, end: f64 },
+    #[error("the crop {crop} does not fit the {width}x{height} frame")]
+    CropOutsideFrame { crop: CropRect, width: u32, height: u32 },
+}
+
+/// Trim range, crop and rotation of a video. The default keeps it as it is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoEdit {
+    /// Seconds into the source where the output starts
+    pub start: Option<f64>,
+    /// Seconds into the source where the output ends
+    pub end: Option<f64>,
+    pub crop: Option<CropRect>,
+    /// Applied after cropping
+    pub rotation: Rotation,
+}
+
+impl VideoEdit {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// The video as it is after editing: trimmed duration and the frame size
+    /// after cropping and rotating
+    pub fn apply(&self, metadata: &VideoMetadata) -> Result<VideoMetadata, EditError> {
+        let duration = metadata.duration_seconds as f64;
+        let start = self.start.unwrap_or(0.0).max(0.0);
+        let end = self.end.map_or(duration, |end| end.min(duration));
+        if start >= duration {
+            return Err(EditError::StartPastEnd { start, duration });
+        }
+        if end <= start {
+            return Err(EditError::EmptyRange { start, end });
+        }
+
+        let (mut width, mut height) = (metadata.width, metadata.height);
+        if let Some(crop) = self.crop {
+            if !crop.fits(width, height) {
+                return Err(EditError::CropOutsideFrame { crop, width, height });
+            }
+            (width, height) = crop.output_size();
+        }
+        if self.rotation.swaps_sides() {
+            (width, height) = (height, width);
+        }
+
+        // The rotation stays the source's: decoding still applies it, which
+        // decides where frames can be decoded
+        Ok(VideoMetadata {
+            width,
+            height,
+            duration_seconds: (end - start) as f32,
+            ..metadata.clone()
+        })
+    }
+
+    /// Options placed before `-i`: seeking there is fast and cuts audio too
+    pub fn input_args(&self) -> Vec<String> {
+        let start = self.start.filter(|&start| start > 0.0);
+        let mut args = Vec::new();
+        if let Some(start) = start {
+            args.extend(["-ss".to_string(), format!("{:.3}", start)]);
+        }
+        if let Some(end) = self.end {
+            args.extend(["-t".to_string(), format!("{:.3}", end - start.unwrap_or(0.0))]);
+        }
+        args
+    }
+
+    /// Video filters, to run before any scaling
+    pub fn filters(&self) -> FilterChain {
+        let crop = self.crop.map(VideoFilter::Crop);
+        let rotation = Some(self.rotation)
+            .filter(|&rotation| rotation != Rotation::None)
+            .map(VideoFilter::Transpose);
+        crop.into_iter().chain(rotation).collect()
+    }
+}
+
+impl fmt::Display for VideoEdit {
+    /// e.g. `0:10.0-0:30.0, crop 1280:720:0:0, rotated 90°`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.start.is_some() || self.end.is_some() {
+            let end = self.end.map_or_else(|| "end".to_string(), format_timestamp);
+            parts.push(format!("{}-{}", format_timestamp(self.start.unwrap_or(0.0)), end));
+        }
+        if let Some(crop) = self.crop {
+            parts.push(format!("crop {}", crop));
+        }
+        if self.rotation != Rotation::None {
+            parts.push(format!("rotated {}°", self.rotation));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Parse a position as seconds (`90`, `12.5`) or `[h:]m:ss[.f]` (`1:30`)
+pub fn parse_timestamp(s: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid time '{}', expected seconds or [h:]m:ss, e.g. 90 or 1:30", s);
+    let parts: Vec<&str> = s.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+
+    let mut seconds = 0.0;
+    for (index, part) in parts.iter().enumerate() {
+        let value: f64 = part.parse().map_err(|_| invalid())?;
+        let last = index == parts.len() - 1;
+        if !value.is_finite() || value < 0.0 || (!last && value.fract() != 0.0) || (index > 0 && value >= 60.0) {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Ok(seconds)
+}
+
+/// `m:ss.t`, or `h:mm:ss.t` from an hour on
+pub fn format_timestamp(seconds: f64) -> String {
+    let tenths = (seconds.max(0.0) * 10.0).round() as u64;
+    let (hours, minutes, secs) = (tenths / 36_000, tenths / 600 % 60, tenths / 10 % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}.{}", hours, minutes, secs, tenths % 10)
+    } else {
+        format!("{}:{:02}.{}", minutes, secs, tenths % 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip() -> VideoMetadata {
+        VideoMetadata { duration_seconds: 600.0, ..VideoMetadata::default_hd() }
+    }
+
+    #[test]
+    fn test_parse_timestamps() {
+        assert_eq!(parse_timestamp("90"), Ok(90.0));
+        assert_eq!(parse_timestamp("12.5"), Ok(12.5));
+        assert_eq!(parse_timestamp("1:30"), Ok(90.0));
+        assert_eq!(parse_timestamp("1:02:03.5"), Ok(3723.5));
+        assert!(parse_timestamp("1:75").is_err());
+        assert!(parse_timestamp("-3").is_err());
+        assert!(parse_timestamp("1:2:3:4").is_err());
+        assert_eq!(format_timestamp(3723.5), "1:02:03.5");
+        assert_eq!(format_timestamp(75.04), "1:15.0");
+    }
+
+    #[test]
+    fn test_parse_crop_and_rotation() {
+        assert_eq!("1280:720:320:180".parse(), Ok(CropRect { width: 1280, height: 720, x: 320, y: 180 }));
+        assert!("1280x720".parse::<CropRect>().is_err());
+        assert!("0:720:0:0".parse::<CropRect>().is_err());
+        assert_eq!("270".parse(), Ok(Rotation::Clockwise270));
+        assert!("45".parse::<Rotation>().is_err());
+    }
+
+    #[test]
+    fn test_edited_video_is_what_the_planner_sees() {
+        let edit = VideoEdit {
+            start: Some(30.0),
+            end: Some(50.0),
+            crop: Some(CropRect { width: 1081, height: 1080, x: 420, y: 0 }),
+            rotation: Rotation::Clockwise90,
+        };
+        let edited = edit.apply(&clip()).unwrap();
+        assert_eq!(edited.duration_seconds, 20.0);
+        assert_eq!((edited.width, edited.height), (1080, 1080));
+
+        let rotated = VideoEdit { rotation: Rotation::Clockwise270, ..VideoEdit::default() }.apply(&clip()).unwrap();
+        assert_eq!((rotated.width, rotated.height), (1080, 1920));
+
+        // An end past the video keeps the rest of it
+        let tail = VideoEdit { start: Some(590.0), end: Some(900.0), ..VideoEdit::default() };
+        assert_eq!(tail.apply(&clip()).unwrap().duration_seconds, 10.0);
+
+        assert!(matches!(VideoEdit { start: Some(600.0), ..VideoEdit::default() }.apply(&clip()), Err(EditError::StartPastEnd { .. })));
+        assert!(matches!(VideoEdit { start: Some(40.0), end: Some(20.0), ..VideoEdit::default() }.apply(&clip()), Err(EditError::EmptyRange { .. })));
+        let crop = Some(CropRect { width: 1280, height: 720, x: 1000, y: 0 });
+        assert!(matches!(VideoEdit { crop, ..VideoEdit::default() }.apply(&clip()), Err(EditError::CropOutsideFrame { .. })));
+    }
+
+    #[test]
+    fn test_ffmpeg_arguments() {
+        let edit = VideoEdit {
+            start: Some(30.0),
+            end: Some(50.25),
+            crop: Some(CropRect { width: 641, height: 360, x: 10, y: 20 }),
+            rotation: Rotation::Rotate180,
+        };
+        assert_eq!(edit.input_args(), ["-ss", "30.000", "-t", "20.250"]);
+        assert_eq!(edit.filters().to_string(), "crop=640:360:10:20,hflip,vflip");
+        assert_eq!(edit.to_string(), "0:30.0-0:50.3, crop 641:360:10:20, rotated 180°");
+
+        assert!(VideoEdit::default().input_args().is_empty());
+        assert!(VideoEdit::default().filters().is_empty());
+        assert_eq!(VideoEdit { end: Some(20.0), ..VideoEdit::default() }.input_args(), ["-t", "20.000"]);
+    }
+}
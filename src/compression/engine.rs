@@ -1,25 +1,40 @@
 #![allow(dead_code)]
 use anyhow::{Result, anyhow};
 use log::{info, warn, error, debug};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio::fs;
-use tokio::sync::mpsc;
 
-use super::hardware::{HardwareCapabilities, HardwareEncoder, fallback::FallbackSystem};
-use super::{CompressionSettings, SizeEstimator};
+use super::hardware::{HardwareCapabilities, HardwareEncoder, HardwareQuality, backend::EncoderCodec, fallback::FallbackSystem};
+use super::cancel::{CancellationToken, Cancelled};
+use super::progress::{estimate_remaining, EncodeStage, ProgressEvent, ProgressParser, ProgressSender, ProgressUpdate};
+use super::ffmpeg::{FfmpegExit, FfmpegInvocation, FfmpegOutput, FfmpegRunner, SystemFfmpegRunner};
+use super::{CompressionSettings, EditError, EncodePipeline, EncodingPlan, FileSize, PlatformPreset, ScaleMode, SizeEstimator, VideoFilter, VideoMetadata};
+use super::planner::MIN_VIDEO_BITRATE_KBPS;
 use super::metadata::get_video_metadata;
-use regex::Regex;
+
+/// Encodes tried per file before giving up, including fallbacks
+const MAX_ATTEMPTS: u32 = 3;
 
 pub struct CompressionEngine {
     capabilities: HardwareCapabilities,
     fallback_system: FallbackSystem,
     size_estimator: SizeEstimator,
+    runner: Arc<dyn FfmpegRunner>,
 }
 
 impl CompressionEngine {
     pub fn new(capabilities: HardwareCapabilities) -> Self {
+        Self::with_runner(capabilities, Arc::new(SystemFfmpegRunner))
+    }
+    
+    /// Create an engine that executes FFmpeg through `runner` instead of
+    /// spawning processes directly, e.g. to record or fake encodes.
+    pub fn with_runner(capabilities: HardwareCapabilities, runner: Arc<dyn FfmpegRunner>) -> Self {
         let fallback_system = FallbackSystem::new(&capabilities);
         let size_estimator = SizeEstimator::new();
         
@@ -27,6 +42,7 @@ impl CompressionEngine {
             capabilities,
             fallback_system,
             size_estimator,
+            runner,
         }
     }
     
@@ -35,7 +51,23 @@ impl CompressionEngine {
         input_path: &Path,
         output_path: Option<&Path>,
         settings: &CompressionSettings,
-        progress_tx: Option<mpsc::UnboundedSender<(f32, Option<std::time::Duration>)>>,
+        progress_tx: Option<ProgressSender>,
+    ) -> Result<CompressionResult> {
+        self.compress_cancellable(input_path, output_path, settings, progress_tx, &CancellationToken::new()).await
+    }
+    
+    /// Like [`compress`](Self::compress), but stops when `cancel` is cancelled.
+    ///
+    /// Cancelling kills the running FFmpeg process tree, removes the partial
+    /// output and two-pass log files and returns a [`Cancelled`] error.
+    /// Pausing the token suspends the encode until it is resumed.
+    pub async fn compress_cancellable(
+        &mut self,
+        input_path: &Path,
+        output_path: Option<&Path>,
+        settings: &CompressionSettings,
+        progress_tx: Option<ProgressSender>,
+        cancel: &CancellationToken,
     ) -> Result<CompressionResult> {
         info!("Starting video compression");
         info!("Input: {}", input_path.display());
@@ -59,36 +91,64 @@ impl CompressionEngine {
             info!("Using encoder: {:?}", settings.hardware_encoder);
         }
         
+        // Get video metadata first to calculate proper bitrate
+        send_progress(&progress_tx, ProgressEvent::Stage(EncodeStage::Probing));
+        let metadata = get_video_metadata(input_path).await?;
+        
         // Try compression with fallback
         let mut current_settings = settings.clone();
         let mut attempts = 0;
-        const MAX_ATTEMPTS: u32 = 3;
         
         while attempts < MAX_ATTEMPTS {
             attempts += 1;
             
-            match self.try_compress(input_path, &output_path, &current_settings, progress_tx.clone()).await {
-                Ok(result) => {
+            match self.compress_with_metadata(input_path, &output_path, &current_settings, &metadata, progress_tx.clone(), cancel).await {
+                Ok(mut result) => {
                     // Record success for the encoder
                     self.fallback_system.record_success(&current_settings.hardware_encoder);
                     
                     info!("Compression completed successfully in {} attempts", attempts);
+                    if let Some(platform) = &settings.platform {
+                        result.platform_violations = check_platform(platform, &output_path).await;
+                    }
                     return Ok(result);
                 },
+                Err(e) if e.is::<Cancelled>() => {
+                    info!("Compression cancelled, partial output removed");
+                    return Err(e);
+                },
+                // Every encoder would reject the edit the same way
+                Err(e) if e.is::<EditError>() => return Err(e),
                 Err(e) => {
                     error!("Compression attempt {} failed: {}", attempts, e);
                     
                     // Record failure for the current encoder
                     self.fallback_system.record_failure(&current_settings.hardware_encoder, &e);
                     
+                    if attempts < MAX_ATTEMPTS && EncodePipeline::select(&current_settings, &metadata).is_gpu() {
+                        // The GPU may encode what it failed to decode or filter
+                        warn!("Retrying {:?} with CPU decoding and filtering", current_settings.hardware_encoder);
+                        send_progress(&progress_tx, ProgressEvent::Stage(EncodeStage::FallbackRetry {
+                            encoder: current_settings.hardware_encoder,
+                            attempt: attempts + 1,
+                        }));
+                        current_settings.full_gpu_pipeline = false;
+                        continue;
+                    }
+                    
                     if attempts < MAX_ATTEMPTS {
                         // Try to find a fallback encoder
                         let fallback_encoder = self.fallback_system.get_next_encoder(&current_settings.hardware_encoder);
                         
                         if fallback_encoder != current_settings.hardware_encoder {
                             warn!("Attempting fallback to encoder: {:?}", fallback_encoder);
+                            send_progress(&progress_tx, ProgressEvent::Stage(EncodeStage::FallbackRetry {
+                                encoder: fallback_encoder,
+                                attempt: attempts + 1,
+                            }));
                             current_settings.hardware_encoder = fallback_encoder;
                             current_settings.enable_hardware_accel = fallback_encoder.is_hardware_accelerated();
+                            current_settings.full_gpu_pipeline = settings.full_gpu_pipeline;
                             continue;
                         }
                     }
@@ -104,224 +164,259 @@ impl CompressionEngine {
         Err(anyhow!("Compression failed after maximum attempts"))
     }
     
-    async fn try_compress(
+    /// Plan resolution, frame rate and bitrates for `input_path` without
+    /// encoding anything, e.g. to report an infeasible target up front.
+    pub async fn plan(&self, input_path: &Path, settings: &CompressionSettings) -> Result<EncodingPlan> {
+        let metadata = settings.edit.apply(&get_video_metadata(input_path).await?)?;
+        Ok(self.size_estimator.plan_encoding(&metadata, settings))
+    }
+    
+    /// Resolve everything a compression of `input_path` would do without
+    /// running it: the plan, the encoders tried in turn if encodes fail, and
+    /// the FFmpeg invocations each of them would run.
+    pub async fn dry_run(
         &self,
         input_path: &Path,
-        output_path: &Path,
+        output_path: Option<&Path>,
         settings: &CompressionSettings,
-        progress_tx: Option<mpsc::UnboundedSender<(f32, Option<std::time::Duration>)>>,
-    ) -> Result<CompressionResult> {
-        let start_time = std::time::Instant::now();
-        
-        // Get video metadata first to calculate proper bitrate
-        let metadata = get_video_metadata(input_path).await?;
-        
-        // Calculate target bitrate
-        let target_bitrate = self.calculate_target_bitrate(settings, &metadata);
-        info!("Using target bitrate: {} kbps", target_bitrate);
-        
-        // Check if we should use 2-pass encoding
-        let use_two_pass = match &settings.hardware_encoder {
-            HardwareEncoder::Software => true,
-            // NVENC doesn't support traditional 2-pass, uses multipass instead
-            _ => false,
-        };
-        
-        if use_two_pass {
-            info!("Using 2-pass encoding for better size accuracy");
-            return self.two_pass_encode(input_path, output_path, settings, target_bitrate, &metadata, progress_tx).await;
+    ) -> Result<DryRun> {
+        if !input_path.exists() {
+            return Err(anyhow!("Input file does not exist: {}", input_path.display()));
         }
+        let output_path = match output_path {
+            Some(path) => path.to_path_buf(),
+            None => generate_output_path(input_path)?,
+        };
         
-        // Build ffmpeg command using the shared function
-        let mut cmd = self.build_ffmpeg_command(input_path, output_path, settings, target_bitrate, &metadata);
-        
-        // Add progress reporting
-        cmd.arg("-progress").arg("pipe:2");
-        
-        debug!("FFmpeg command: {:?}", cmd);
-        
-        // Execute compression with real-time progress
-        let mut child = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| anyhow!("Failed to spawn FFmpeg: {}", e))?;
-        
-        // Parse progress from stderr if channel provided
-        if let Some(tx) = progress_tx {
-            if let Some(stderr) = child.stderr.take() {
-                let reader = BufReader::new(stderr);
-                let duration_seconds = metadata.duration_seconds;
-                
-                tokio::task::spawn_blocking(move || {
-                    let start_time = std::time::Instant::now();
-                    for line in reader.lines() {
-                        if let Ok(line) = line {
-                            if line.starts_with("out_time_ms=") {
-                                if let Ok(time_ms) = line.split('=').nth(1).unwrap_or("0").parse::<u64>() {
-                                    let current_seconds = time_ms as f64 / 1_000_000.0;
-                                    let progress = (current_seconds / duration_seconds as f64).min(1.0) as f32;
-                                    
-                                    // Calculate ETA
-                                    let elapsed = start_time.elapsed();
-                                    let eta = if progress > 0.01 {
-                                        let total_estimated = elapsed.as_secs_f64() / progress as f64;
-                                        let remaining = total_estimated - elapsed.as_secs_f64();
-                                        if remaining > 0.0 {
-                                            Some(std::time::Duration::from_secs_f64(remaining))
-                                        } else {
-                                            None
-                                        }
-                                    } else {
-                                        None
-                                    };
-                                    
-                                    // Send progress update through channel
-                                    let _ = tx.send((progress, eta));
-                                }
-                            }
-                        }
+        let metadata = get_video_metadata(input_path).await?;
+        self.dry_run_with_metadata(input_path, &output_path, settings, metadata)
+    }
+    
+    fn dry_run_with_metadata(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        settings: &CompressionSettings,
+        metadata: VideoMetadata,
+    ) -> Result<DryRun> {
+        let edited = settings.edit.apply(&metadata)?;
+        let plan = self.size_estimator.plan_encoding(&edited, settings);
+        // A real run uses a fresh temporary directory; any writable prefix will do
+        let log_file = std::env::temp_dir().join("small-mp4-ffmpeg2pass");
+        
+        // The same sequence `compress` walks when every attempt fails: a
+        // full-GPU attempt is retried with CPU decoding before falling back
+        let mut attempt_settings = settings.clone();
+        let mut attempts: Vec<DryRunAttempt> = Vec::new();
+        while attempts.len() < MAX_ATTEMPTS as usize {
+            if let Some(previous) = attempts.last() {
+                if previous.pipeline.is_gpu() {
+                    attempt_settings.full_gpu_pipeline = false;
+                } else {
+                    let failed: Vec<_> = attempts.iter().map(|attempt| attempt.encoder).collect();
+                    let encoder = self.fallback_system.next_after(&failed);
+                    if encoder != attempt_settings.hardware_encoder {
+                        attempt_settings.hardware_encoder = encoder;
+                        attempt_settings.enable_hardware_accel = encoder.is_hardware_accelerated();
+                        attempt_settings.full_gpu_pipeline = settings.full_gpu_pipeline;
                     }
-                });
+                }
             }
+            
+            let invocations: Vec<_> = if use_two_pass(&attempt_settings) {
+                (1..=2)
+                    .map(|pass_num| self.pass_invocation(input_path, output_path, &attempt_settings, &plan, &edited, pass_num, &log_file))
+                    .collect()
+            } else {
+                vec![self.build_invocation(input_path, output_path, &attempt_settings, &plan, &edited)]
+            };
+            attempts.push(DryRunAttempt {
+                encoder: attempt_settings.hardware_encoder,
+                pipeline: EncodePipeline::select(&attempt_settings, &edited),
+                commands: invocations.iter().map(ToString::to_string).collect(),
+                invocations,
+            });
         }
         
-        let output = child.wait_with_output()
-            .map_err(|e| anyhow!("Failed to wait for FFmpeg: {}", e))?;
-        
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow!("FFmpeg encoding failed: {}", stderr));
-        }
-        
-        let encoding_time = start_time.elapsed();
-        
-        // Get output file size
-        let output_size = fs::metadata(&output_path).await?.len();
-        let output_size_mb = output_size as f64 / (1024.0 * 1024.0);
-        
-        // Get input file size for comparison
-        let input_size = fs::metadata(input_path).await?.len();
-        let input_size_mb = input_size as f64 / (1024.0 * 1024.0);
-        let compression_ratio = input_size_mb / output_size_mb;
-        
-        // Check if we exceeded target size
-        let target_mb = settings.target_size.as_mb();
-        if output_size_mb > target_mb as f64 {
-            warn!("Output size ({:.1} MB) exceeds target size ({:.1} MB)!", output_size_mb, target_mb);
-        }
-        
-        info!("Compression completed:");
-        info!("  Input size: {:.1} MB", input_size_mb);
-        info!("  Output size: {:.1} MB", output_size_mb);
-        info!("  Target size: {:.1} MB", target_mb);
-        info!("  Compression ratio: {:.1}:1", compression_ratio);
-        info!("  Encoding time: {:.1}s", encoding_time.as_secs_f64());
+        let size_reencodes = if settings.hardware_quality.has_size_target() {
+            settings.size_convergence.max_iterations.max(1) - 1
+        } else {
+            0
+        };
         
-        Ok(CompressionResult {
+        Ok(DryRun {
             input_path: input_path.to_path_buf(),
             output_path: output_path.to_path_buf(),
-            input_size_mb,
-            output_size_mb,
-            compression_ratio,
-            encoding_time,
-            encoder_used: settings.hardware_encoder.clone(),
-            hardware_accelerated: settings.enable_hardware_accel,
+            metadata,
+            plan,
+            attempts,
+            size_reencodes,
         })
     }
     
-    async fn two_pass_encode(
+    async fn compress_with_metadata(
         &self,
         input_path: &Path,
         output_path: &Path,
         settings: &CompressionSettings,
-        target_bitrate: u32,
-        metadata: &super::estimator::VideoMetadata,
-        progress_tx: Option<mpsc::UnboundedSender<(f32, Option<std::time::Duration>)>>,
+        metadata: &VideoMetadata,
+        progress_tx: Option<ProgressSender>,
+        cancel: &CancellationToken,
     ) -> Result<CompressionResult> {
         let start_time = std::time::Instant::now();
-        let temp_log = format!("/tmp/ffmpeg2pass_{}", std::process::id());
-        
-        // First pass
-        info!("Starting first pass analysis...");
-        let first_pass_result = self.run_ffmpeg_pass(
-            input_path,
-            settings,
-            target_bitrate,
-            1,
-            &temp_log,
-            progress_tx.clone(),
-        ).await?;
-        
-        if !first_pass_result {
-            return Err(anyhow!("First pass failed"));
-        }
-        
-        // Second pass
-        info!("Starting second pass encoding...");
-        let mut cmd = self.build_ffmpeg_command(input_path, output_path, settings, target_bitrate, metadata);
-        
-        // Add 2-pass specific arguments
-        cmd.arg("-pass").arg("2");
-        cmd.arg("-passlogfile").arg(&temp_log);
-        
-        // Execute second pass
-        let mut child = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| anyhow!("Failed to start FFmpeg: {}", e))?;
-        
-        // Monitor progress
-        if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-            let duration = metadata.duration_seconds;
+        
+        // Only the kept part of the video spends the budget
+        let edited = settings.edit.apply(metadata)?;
+        let metadata = &edited;
+        
+        // Pick resolution, frame rate and bitrates for the budget
+        let mut plan = self.size_estimator.plan_encoding(metadata, settings);
+        info!("Using target bitrate: {} kbps", plan.video_bitrate_kbps);
+        
+        // Re-encode with a lower bitrate until the output fits
+        let size_limit = settings.size_convergence.size_limit(settings.target_size);
+        let max_iterations = settings.size_convergence.max_iterations.max(1);
+        let mut iteration = 1;
+        let mut encode_settings = settings.clone();
+        
+        loop {
+            let mut result = self.encode_with_plan(
+                input_path,
+                output_path,
+                &encode_settings,
+                &plan,
+                metadata,
+                progress_tx.clone(),
+                cancel,
+            ).await?;
             
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    if let Some(progress) = parse_ffmpeg_progress(&line, duration as f64) {
-                        // Second pass progress (50-100%)
-                        let adjusted_progress = 0.5 + (progress * 0.5);
-                        if let Some(ref tx) = progress_tx {
-                            let eta = calculate_eta(adjusted_progress, start_time.elapsed());
-                            let _ = tx.send((adjusted_progress, eta));
-                        }
-                    }
+            let output_size = FileSize::from_bytes(fs::metadata(output_path).await?.len());
+            result.size_iterations = iteration;
+            result.target_met = output_size <= size_limit;
+            result.encoding_time = start_time.elapsed();
+            
+            // Constant quality has no size target to converge on
+            if result.target_met || !settings.hardware_quality.has_size_target() {
+                result.target_met = true;
+                return Ok(result);
+            }
+            
+            if iteration >= max_iterations {
+                warn!("Output size ({}) still exceeds target size ({}) after {} encode(s)",
+                      output_size, settings.target_size, iteration);
+                return Ok(result);
+            }
+            
+            // Capped quality overshot: fall back to hitting the size directly
+            if encode_settings.hardware_quality.uses_quality_level() {
+                iteration += 1;
+                warn!("Output size ({}) exceeds the cap ({}), re-encoding at {} kbps (encode {}/{})",
+                      output_size, settings.target_size, plan.video_bitrate_kbps, iteration, max_iterations);
+                encode_settings.hardware_quality = HardwareQuality::Auto;
+                continue;
+            }
+            
+            match converge_bitrate(plan.video_bitrate_kbps, output_size, size_limit, plan.audio_bitrate_kbps, metadata.duration_seconds) {
+                Some(next_bitrate) => {
+                    iteration += 1;
+                    warn!("Output size ({}) exceeds target size ({}), re-encoding at {} kbps (encode {}/{})",
+                          output_size, settings.target_size, next_bitrate, iteration, max_iterations);
+                    plan.video_bitrate_kbps = next_bitrate;
+                }
+                None => {
+                    warn!("Output size ({}) exceeds target size ({}) and the bitrate cannot be lowered further",
+                          output_size, settings.target_size);
+                    return Ok(result);
                 }
             }
         }
+    }
+    
+    /// Run a single encode (two passes for software) following `plan`
+    #[allow(clippy::too_many_arguments)]
+    async fn encode_with_plan(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        settings: &CompressionSettings,
+        plan: &EncodingPlan,
+        metadata: &VideoMetadata,
+        progress_tx: Option<ProgressSender>,
+        cancel: &CancellationToken,
+    ) -> Result<CompressionResult> {
+        let start_time = std::time::Instant::now();
         
-        let output = child.wait_with_output()
-            .map_err(|e| anyhow!("Failed to wait for FFmpeg: {}", e))?;
+        let use_two_pass = use_two_pass(settings);
         
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow!("FFmpeg encoding failed: {}", stderr));
-        }
+        let pass = |stage, offset, span| PassProgress {
+            stage,
+            duration_seconds: metadata.duration_seconds,
+            total_frames: Some((metadata.duration_seconds * plan.fps).round() as u64).filter(|frames| *frames > 0),
+            offset,
+            span,
+        };
+        
+        let encoded = if use_two_pass {
+            info!("Using 2-pass encoding for better size accuracy");
+            // Private to this job so concurrent two-pass encodes don't share logs
+            let temp_dir = JobTempDir::create()?;
+            let temp_log = temp_dir.path().join("ffmpeg2pass");
+            
+            let passes = async {
+                // First pass
+                info!("Starting first pass analysis...");
+                let first_pass = self.pass_invocation(input_path, output_path, settings, plan, metadata, 1, &temp_log);
+                let exit = self.run_ffmpeg(first_pass, pass(EncodeStage::FirstPass, 0.0, 0.5), progress_tx.clone(), cancel).await?;
+                if !exit.success {
+                    return Err(anyhow!("First pass failed: {}", exit.stderr_tail));
+                }
+                
+                // Second pass
+                info!("Starting second pass encoding...");
+                let second_pass = self.pass_invocation(input_path, output_path, settings, plan, metadata, 2, &temp_log);
+                let exit = self.run_ffmpeg(second_pass, pass(EncodeStage::SecondPass, 0.5, 0.5), progress_tx.clone(), cancel).await?;
+                if !exit.success {
+                    return Err(anyhow!("FFmpeg encoding failed: {}", exit.stderr_tail));
+                }
+                Ok(())
+            }.await;
+            
+            // Removes the log files, including the ones x264 is still writing when cancelled
+            drop(temp_dir);
+            
+            passes
+        } else {
+            let invocation = self.build_invocation(input_path, output_path, settings, plan, metadata);
+            match self.run_ffmpeg(invocation, pass(EncodeStage::Encoding, 0.0, 1.0), progress_tx.clone(), cancel).await {
+                Ok(exit) if !exit.success => Err(anyhow!("FFmpeg encoding failed: {}", exit.stderr_tail)),
+                other => other.map(|_| ()),
+            }
+        };
         
-        // Clean up log files
-        let _ = std::fs::remove_file(format!("{}-0.log", temp_log));
-        let _ = std::fs::remove_file(format!("{}-0.log.mbtree", temp_log));
+        if let Err(e) = encoded {
+            // Leave nothing half-written behind
+            if e.is::<Cancelled>() && output_path.exists() {
+                let _ = fs::remove_file(output_path).await;
+            }
+            return Err(e);
+        }
         
-        // Get results
+        send_progress(&progress_tx, ProgressEvent::Stage(EncodeStage::Finalizing));
         let encoding_time = start_time.elapsed();
-        let output_size = fs::metadata(&output_path).await?.len();
-        let output_size_mb = output_size as f64 / (1024.0 * 1024.0);
-        let input_size = fs::metadata(input_path).await?.len();
-        let input_size_mb = input_size as f64 / (1024.0 * 1024.0);
-        let compression_ratio = input_size_mb / output_size_mb;
         
-        // Check if we exceeded target size
-        let target_mb = settings.target_size.as_mb();
-        if output_size_mb > target_mb as f64 {
-            warn!("Output size ({:.1} MB) exceeds target size ({:.1} MB)!", output_size_mb, target_mb);
-        }
+        // Get output file size
+        let output_size = FileSize::from_bytes(fs::metadata(&output_path).await?.len());
+        let output_size_mb = output_size.as_mb();
         
-        info!("2-pass compression completed:");
+        // Get input file size for comparison
+        let input_size = FileSize::from_bytes(fs::metadata(input_path).await?.len());
+        let input_size_mb = input_size.as_mb();
+        let compression_ratio = input_size_mb / output_size_mb;
+        
+        info!("{} completed:", if use_two_pass { "2-pass compression" } else { "Compression" });
         info!("  Input size: {:.1} MB", input_size_mb);
         info!("  Output size: {:.1} MB", output_size_mb);
-        info!("  Target size: {:.1} MB", target_mb);
+        info!("  Target size: {}", settings.target_size);
         info!("  Compression ratio: {:.1}:1", compression_ratio);
         info!("  Encoding time: {:.1}s", encoding_time.as_secs_f64());
         
@@ -332,289 +427,337 @@ impl CompressionEngine {
             output_size_mb,
             compression_ratio,
             encoding_time,
-            encoder_used: settings.hardware_encoder.clone(),
+            encoder_used: settings.hardware_encoder,
             hardware_accelerated: settings.enable_hardware_accel,
+            target_size: settings.target_size,
+            target_met: output_size <= settings.target_size,
+            size_iterations: 1,
+            plan: plan.clone(),
+            metadata: metadata.clone(),
+            platform_violations: Vec::new(),
         })
     }
     
-    async fn run_ffmpeg_pass(
+    /// Invocation for one pass of a two-pass encode. The first pass only
+    /// analyses the video, so it drops audio and discards the output.
+    #[allow(clippy::too_many_arguments)]
+    fn pass_invocation(
         &self,
         input_path: &Path,
+        output_path: &Path,
         settings: &CompressionSettings,
-        target_bitrate: u32,
+        plan: &EncodingPlan,
+        metadata: &VideoMetadata,
         pass_num: u8,
-        log_file: &str,
-        progress_tx: Option<mpsc::UnboundedSender<(f32, Option<std::time::Duration>)>>,
-    ) -> Result<bool> {
-        let metadata = get_video_metadata(input_path).await?;
-        let mut cmd = self.build_ffmpeg_command(input_path, &PathBuf::from("/dev/null"), settings, target_bitrate, &metadata);
+        log_file: &Path,
+    ) -> FfmpegInvocation {
+        let mut invocation = self.build_invocation(input_path, output_path, settings, plan, metadata);
+        invocation.codec_args(["-pass".to_string(), pass_num.to_string(), "-passlogfile".to_string(), log_file.to_string_lossy().into_owned()]);
         
-        // Add pass-specific arguments
-        cmd.arg("-pass").arg(pass_num.to_string());
-        cmd.arg("-passlogfile").arg(log_file);
-        
-        // For first pass, we don't need audio
         if pass_num == 1 {
-            cmd.arg("-an");
+            invocation.codec_arg("-an");
+            invocation.outputs = vec![FfmpegOutput::null()];
         }
         
-        // For Windows, use NUL instead of /dev/null
-        #[cfg(target_os = "windows")]
-        {
-            cmd.arg("-f").arg("null").arg("NUL");
-        }
-        #[cfg(not(target_os = "windows"))]
-        {
-            cmd.arg("-f").arg("null").arg("/dev/null");
+        invocation
+    }
+    
+    /// Run `invocation` on the runner, announcing `pass.stage` and turning
+    /// FFmpeg's progress stream into [`ProgressEvent::Progress`] updates.
+    async fn run_ffmpeg(
+        &self,
+        invocation: FfmpegInvocation,
+        pass: PassProgress,
+        progress_tx: Option<ProgressSender>,
+        cancel: &CancellationToken,
+    ) -> Result<FfmpegExit> {
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
         }
+        debug!("FFmpeg command: {}", invocation);
+        send_progress(&progress_tx, ProgressEvent::Stage(pass.stage));
         
-        let mut child = cmd
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| anyhow!("Failed to start FFmpeg pass {}: {}", pass_num, e))?;
-        
-        // Get metadata for progress tracking
-        let metadata = get_video_metadata(input_path).await?;
-        let duration = metadata.duration_seconds;
+        let runner = Arc::clone(&self.runner);
+        let token = cancel.clone();
         let start_time = std::time::Instant::now();
         
-        // Monitor progress
-        if let Some(stderr) = child.stderr.take() {
-            let reader = BufReader::new(stderr);
-            
-            for line in reader.lines() {
-                if let Ok(line) = line {
-                    if let Some(progress) = parse_ffmpeg_progress(&line, duration as f64) {
-                        // First pass progress (0-50%)
-                        let adjusted_progress = progress * 0.5;
-                        if let Some(ref tx) = progress_tx {
-                            let eta = calculate_eta(adjusted_progress, start_time.elapsed());
-                            let _ = tx.send((adjusted_progress, eta));
-                        }
-                    }
+        let exit = tokio::task::spawn_blocking(move || {
+            let mut parser = ProgressParser::new();
+            let mut on_line = |line: &str| {
+                let Some(tx) = &progress_tx else { return };
+                if let Some(mut update) = parser.push_line(line) {
+                    update.total_frames = pass.total_frames;
+                    update.fraction = pass.overall_fraction(&update);
+                    // Extrapolate from this pass alone; earlier passes ran at a different speed
+                    update.eta = estimate_remaining(pass.pass_fraction(&update), start_time.elapsed())
+                        .map(|remaining| remaining + pass.remaining_after(start_time.elapsed(), &update));
+                    let _ = tx.send(ProgressEvent::Progress(update));
                 }
-            }
-        }
-        
-        let output = child.wait_with_output()
-            .map_err(|e| anyhow!("Failed to wait for FFmpeg pass {}: {}", pass_num, e))?;
+            };
+            runner.run(&invocation, &token, &mut on_line)
+        })
+        .await
+        .map_err(|e| anyhow!("FFmpeg task failed: {}", e))??;
         
-        Ok(output.status.success())
+        // Whatever FFmpeg reported, a kill we asked for is a cancellation
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(exit)
     }
     
-    fn build_ffmpeg_command(
+    /// Describe the FFmpeg command line that encodes `input_path` per `plan`
+    pub(crate) fn build_invocation(
         &self,
         input_path: &Path,
         output_path: &Path,
         settings: &CompressionSettings,
-        target_bitrate: u32,
-        metadata: &super::estimator::VideoMetadata,
-    ) -> Command {
-        let mut cmd = Command::new("ffmpeg");
-        
-        // Configure hardware acceleration for input if needed
-        if settings.enable_hardware_accel {
-            match &settings.hardware_encoder {
-                HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1 => {
-                    cmd.arg("-hwaccel").arg("cuda");
-                    if let Some(device_id) = settings.cuda_device_id {
-                        cmd.arg("-hwaccel_device").arg(device_id.to_string());
-                    }
-                },
-                HardwareEncoder::Vaapi => {
-                    cmd.arg("-hwaccel").arg("vaapi");
-                    cmd.arg("-hwaccel_device").arg("/dev/dri/renderD128");
-                },
-                HardwareEncoder::VideoToolbox => {
-                    cmd.arg("-hwaccel").arg("videotoolbox");
-                },
-                _ => {}
-            }
+        plan: &EncodingPlan,
+        metadata: &VideoMetadata,
+    ) -> FfmpegInvocation {
+        let mut invocation = FfmpegInvocation::new(input_path, output_path);
+        invocation.global_arg("-y"); // Overwrite output file
+        // Machine-readable progress on stderr instead of the interactive stats line
+        invocation.global_arg("-progress").global_arg("pipe:2").global_arg("-nostats");
+        
+        // Decode on the GPU and keep the frames there when the input allows it
+        let pipeline = EncodePipeline::select(settings, metadata);
+        pipeline.input_args(&mut invocation, settings, metadata);
+        
+        // Trim while reading, so only the kept range is decoded
+        for arg in settings.edit.input_args() {
+            invocation.input_arg(arg);
         }
         
-        cmd.arg("-i").arg(input_path);
-        cmd.arg("-y"); // Overwrite output file
-        
         // Configure video codec
-        let codec = if settings.compatibility_mode {
-            match &settings.hardware_encoder {
-                HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1 => "h264_nvenc",
-                HardwareEncoder::AmfH264 | HardwareEncoder::AmfH265 => "h264_amf",
-                HardwareEncoder::QsvH264 | HardwareEncoder::QsvH265 | HardwareEncoder::QsvAV1 => "h264_qsv",
-                HardwareEncoder::Vaapi => "h264_vaapi",
-                HardwareEncoder::VideoToolbox => "h264_videotoolbox",
-                HardwareEncoder::Software => "libx264",
-            }
-        } else {
-            match &settings.hardware_encoder {
-                HardwareEncoder::NvencH264 => "h264_nvenc",
-                HardwareEncoder::NvencH265 => "hevc_nvenc",
-                HardwareEncoder::NvencAV1 => "av1_nvenc",
-                HardwareEncoder::AmfH264 => "h264_amf",
-                HardwareEncoder::AmfH265 => "hevc_amf",
-                HardwareEncoder::QsvH264 => "h264_qsv",
-                HardwareEncoder::QsvH265 => "hevc_qsv",
-                HardwareEncoder::QsvAV1 => "av1_qsv",
-                HardwareEncoder::Vaapi => "h264_vaapi",
-                HardwareEncoder::VideoToolbox => "h264_videotoolbox",
-                HardwareEncoder::Software => "libx264",
-            }
-        };
-        
-        cmd.arg("-c:v").arg(codec);
-        info!("Using codec: {}", codec);
+        let backend = settings.hardware_encoder.backend();
+        let platform = settings.platform.as_ref();
+        let codec = backend.encoder(output_codec(settings));
+        invocation.codec_args(["-c:v", codec]);
+        info!("Using codec: {} ({})", codec, pipeline);
+        // Without device frames to follow, the encoder needs telling which GPU to use
+        if !pipeline.is_gpu() {
+            invocation.codec_args(backend.device_args(settings.cuda_device_id));
+        }
         
         // Convert VFR to CFR for better compression predictability
         // This helps 2-pass encoding be more accurate
-        cmd.arg("-vsync").arg("cfr");
-        cmd.arg("-r").arg(format!("{}", metadata.fps));
-        
-        // Set bitrate parameters
-        cmd.arg("-b:v").arg(format!("{}k", target_bitrate));
-        cmd.arg("-maxrate").arg(format!("{}k", target_bitrate));
-        cmd.arg("-bufsize").arg(format!("{}k", target_bitrate * 2));
-        
-        // Set preset based on hardware
-        match &settings.hardware_encoder {
-            HardwareEncoder::Software => {
-                let preset = settings.hardware_preset.software_preset();
-                cmd.arg("-preset").arg(preset);
-            },
-            HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1 => {
-                let preset = settings.hardware_preset.nvenc_preset();
-                cmd.arg("-preset").arg(preset);
-                // Use CBR for precise size control
-                cmd.arg("-rc").arg("cbr");
-                // Remove multipass for CBR mode
-                cmd.arg("-cbr").arg("true");
-            },
-            HardwareEncoder::AmfH264 | HardwareEncoder::AmfH265 => {
-                cmd.arg("-quality").arg("speed");
-                cmd.arg("-rc").arg("cbr");
-            },
-            HardwareEncoder::QsvH264 | HardwareEncoder::QsvH265 | HardwareEncoder::QsvAV1 => {
-                cmd.arg("-preset").arg("medium");
-                cmd.arg("-look_ahead").arg("1");
-            },
-            HardwareEncoder::Vaapi => {
-                cmd.arg("-profile").arg("main");
-                cmd.arg("-level").arg("4.0");
-            },
-            HardwareEncoder::VideoToolbox => {
-                cmd.arg("-profile").arg("main");
-            },
+        invocation.codec_args(["-vsync", "cfr"]);
+        invocation.codec_args(["-r".to_string(), plan.fps.to_string()]);
+        
+        // Crop and rotate first; `metadata` already describes the result
+        let mut filters = settings.edit.filters();
+        
+        // Downscale when the planner picked a smaller resolution
+        if plan.is_downscaled(metadata) {
+            if platform.is_some_and(|platform| platform.aspect_ratio.is_some()) {
+                // Fit inside the platform's frame shape and pad the rest
+                filters.push(VideoFilter::scale_to_fit(plan.width, plan.height))
+                    .push(VideoFilter::Pad { width: plan.width, height: plan.height });
+            } else {
+                filters.push(VideoFilter::Scale { width: plan.width, height: plan.height, mode: ScaleMode::Exact });
+            }
         }
         
+        for filter in pipeline.render(&filters) {
+            invocation.filter(filter);
+        }
+        
+        // Set rate control parameters
+        invocation.codec_args(rate_control_args(settings, plan.video_bitrate_kbps));
+        
+        // Speed/quality trade-off, then a platform's profile and level
+        invocation.codec_args(backend.preset_args(settings.hardware_preset));
+        invocation.codec_args(backend.profile_args(
+            platform.and_then(|platform| platform.profile.as_deref()),
+            platform.and_then(|platform| platform.level.as_deref()),
+        ));
+        
         // Configure audio encoding
-        if settings.remove_audio || !metadata.has_audio {
+        if plan.audio_bitrate_kbps == 0 {
             // User wants to remove audio OR no audio stream exists
-            cmd.arg("-an");
+            invocation.codec_arg("-an");
         } else {
-            // Keep and encode audio
-            let audio_bitrate = if metadata.duration_seconds > 600.0 {
-                "96k"  // 96 kbps for videos > 10 minutes
-            } else if metadata.duration_seconds > 300.0 {
-                "112k"  // 112 kbps for videos > 5 minutes
-            } else {
-                "128k"  // 128 kbps for shorter videos
-            };
-            
-            cmd.arg("-c:a").arg("aac");
-            cmd.arg("-b:a").arg(audio_bitrate);
-            cmd.arg("-ac").arg("2"); // Stereo
+            // Keep and encode audio at the planned bitrate
+            let audio_codec = platform.map_or("aac", |platform| platform.audio_codec.as_str());
+            invocation.codec_args(["-c:a", audio_codec]);
+            invocation.codec_args(["-b:a".to_string(), format!("{}k", plan.audio_bitrate_kbps)]);
+            invocation.codec_args(["-ac", "2"]); // Stereo
         }
         
         // Output format settings
-        cmd.arg("-movflags").arg("+faststart");
-        cmd.arg("-pix_fmt").arg("yuv420p");
+        let movflags = platform.map_or("+faststart", |platform| platform.movflags.as_str());
+        invocation.codec_args(["-movflags", movflags]);
+        // Frames in video memory already have the device's format
+        if !pipeline.encodes_device_frames() {
+            invocation.codec_args(["-pix_fmt", backend.pixel_format()]);
+        }
         
         // Memory optimization
         if settings.memory_optimization {
-            cmd.arg("-threads").arg("1");
+            invocation.codec_args(["-threads", "1"]);
         }
         
-        // Add output path
-        cmd.arg(output_path);
-        
-        cmd
+        invocation
     }
+}
+
+/// Probe `output` and list where it breaks `platform`'s constraints
+async fn check_platform(platform: &PlatformPreset, output: &Path) -> Vec<String> {
+    let checked = async {
+        let size = FileSize::from_bytes(fs::metadata(output).await?.len());
+        let metadata = get_video_metadata(output).await?;
+        anyhow::Ok(platform.check(size, &metadata))
+    }.await;
     
-    fn calculate_target_bitrate(&self, settings: &CompressionSettings, metadata: &super::estimator::VideoMetadata) -> u32 {
-        let target_mb = settings.target_size.as_mb();
-        let duration_seconds = metadata.duration_seconds;
-        
-        // Calculate total available bits
-        let total_bits = target_mb * 8.0 * 1024.0 * 1024.0;
-        
-        // Dynamic audio bitrate calculation
-        // Only reserve space for audio if the video has audio AND we're keeping it
-        let (audio_bitrate, audio_bits) = if metadata.has_audio && !settings.remove_audio {
-            let bitrate = if duration_seconds > 600.0 {
-                96.0  // 96 kbps for videos > 10 minutes
-            } else if duration_seconds > 300.0 {
-                112.0  // 112 kbps for videos > 5 minutes
-            } else {
-                128.0  // 128 kbps for shorter videos
-            };
-            (bitrate, bitrate * 1024.0 * duration_seconds)
-        } else {
-            (0.0, 0.0)  // No audio or removing audio, no bits reserved
-        };
-        
-        // Reserve 1% for container overhead (reduced from 2%)
-        let container_overhead = total_bits * 0.01;
-        
-        // Calculate available bits for video
-        let available_video_bits = total_bits - audio_bits - container_overhead;
-        
-        // Calculate video bitrate in kbps
-        let video_bitrate_bps = available_video_bits / duration_seconds;
-        let video_bitrate_kbps = video_bitrate_bps / 1024.0;
-        
-        // Apply smaller safety margin of 0.98 for better size utilization
-        let safe_bitrate = (video_bitrate_kbps * 0.98) as u32;
-        
-        // Calculate minimum bitrate based on resolution for quality
-        // But only apply if it doesn't exceed our target
-        let recommended_min_bitrate = if metadata.width >= 1920 {
-            300  // 1080p+ ideally needs at least 300 kbps
-        } else if metadata.width >= 1280 {
-            200  // 720p ideally needs at least 200 kbps
-        } else {
-            150  // Lower resolutions
-        };
-        
-        // Only use minimum if it doesn't cause us to exceed target size
-        let final_bitrate = if safe_bitrate >= recommended_min_bitrate {
-            safe_bitrate  // We have enough bitrate budget
-        } else {
-            // Warn user that quality might be poor
-            warn!("Target bitrate {}kbps is below recommended {}kbps for {}p video. Quality may be poor.", 
-                  safe_bitrate, recommended_min_bitrate, metadata.height);
-            safe_bitrate  // Still respect the target size
-        };
-        
-        // Absolute minimum to prevent unusable video
-        let final_bitrate = final_bitrate.max(50);
-        
-        info!("Bitrate calculation: target={:.1}MB, duration={:.1}s, audio={}kbps, video={}kbps", 
-              target_mb, duration_seconds, audio_bitrate, final_bitrate);
-        
-        final_bitrate
+    match checked {
+        Ok(violations) => {
+            for violation in &violations {
+                warn!("Output does not meet {}: {}", platform.name, violation);
+            }
+            violations
+        }
+        Err(e) => {
+            warn!("Could not check the output against {}: {}", platform.name, e);
+            vec![format!("could not be checked: {}", e)]
+        }
+    }
+}
+
+/// What `settings` encode to: a platform's codec, H.264 in compatibility
+/// mode, else the encoder's own
+fn output_codec(settings: &CompressionSettings) -> EncoderCodec {
+    match &settings.platform {
+        Some(platform) => platform.video_codec.into(),
+        None if settings.compatibility_mode => EncoderCodec::H264,
+        None => settings.hardware_encoder.codec(),
+    }
+}
+
+/// Whether an encode with `settings` runs two passes
+fn use_two_pass(settings: &CompressionSettings) -> bool {
+    settings.hardware_encoder.backend().two_pass(settings.hardware_quality, output_codec(settings))
+}
+
+/// Scratch directory for one encode, removed with everything in it on drop.
+struct JobTempDir {
+    path: PathBuf,
+}
+
+impl JobTempDir {
+    fn create() -> Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("small-mp4-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&path)
+            .map_err(|e| anyhow!("Failed to create temporary directory {}: {}", path.display(), e))?;
+        Ok(Self { path })
+    }
+    
+    fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JobTempDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            debug!("Failed to remove temporary directory {}: {}", self.path.display(), e);
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CompressionResult {
     pub input_path: PathBuf,
     pub output_path: PathBuf,
     pub input_size_mb: f64,
     pub output_size_mb: f64,
     pub compression_ratio: f64,
+    #[serde(rename = "encoding_time_seconds", serialize_with = "serialize_seconds")]
     pub encoding_time: std::time::Duration,
     pub encoder_used: HardwareEncoder,
     pub hardware_accelerated: bool,
+    pub target_size: FileSize,
+    /// Whether the output fits the target (within the convergence tolerance)
+    pub target_met: bool,
+    /// Number of encodes it took to reach the final output
+    pub size_iterations: u32,
+    /// Resolution, frame rate and bitrates used for the final encode
+    pub plan: EncodingPlan,
+    /// What ffprobe reported about the input
+    pub metadata: VideoMetadata,
+    /// Constraints of the settings' platform preset the output breaks
+    pub platform_violations: Vec<String>,
+}
+
+/// What a compression would run, as resolved by
+/// [`CompressionEngine::dry_run`].
+///
+/// `Display` renders it as a shell script: comments describing the input,
+/// plan and attempts, followed by each attempt's commands.
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRun {
+    pub input_path: PathBuf,
+    pub output_path: PathBuf,
+    pub metadata: VideoMetadata,
+    pub plan: EncodingPlan,
+    /// One entry per attempt, in order; later attempts only run if earlier ones fail
+    pub attempts: Vec<DryRunAttempt>,
+    /// Extra encodes at lower bitrates if the output overshoots the target
+    pub size_reencodes: u32,
+}
+
+/// The encoder and FFmpeg runs of one attempt
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunAttempt {
+    pub encoder: HardwareEncoder,
+    pub pipeline: EncodePipeline,
+    pub invocations: Vec<FfmpegInvocation>,
+    /// `invocations` as shell command lines
+    pub commands: Vec<String>,
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let metadata = &self.metadata;
+        writeln!(f, "# {} -> {}", self.input_path.display(), self.output_path.display())?;
+        writeln!(f, "# Input: {}x{} @ {:.2} fps, {:.1}s, {}, {:?} complexity",
+            metadata.width, metadata.height, metadata.fps, metadata.duration_seconds,
+            metadata.codec, metadata.estimated_complexity)?;
+        writeln!(f, "# Plan: {}", self.plan.summary())?;
+        
+        let mut previous: Option<&DryRunAttempt> = None;
+        for (index, attempt) in self.attempts.iter().enumerate() {
+            let number = index + 1;
+            match previous {
+                Some(previous) if previous.commands == attempt.commands => {
+                    writeln!(f, "# Attempt {}, if attempt {} fails: {:?} again, same commands", number, index, attempt.encoder)?;
+                    continue;
+                }
+                Some(previous) if previous.encoder == attempt.encoder => writeln!(f, "# Attempt {}, if attempt {} fails: {:?} again, {}",
+                    number, index, attempt.encoder, attempt.pipeline)?,
+                Some(_) => writeln!(f, "# Attempt {}, if attempt {} fails: fall back to {:?} ({})",
+                    number, index, attempt.encoder, attempt.pipeline)?,
+                None => writeln!(f, "# Attempt 1: {:?} ({})", attempt.encoder, attempt.pipeline)?,
+            }
+            for command in &attempt.commands {
+                writeln!(f, "{}", command)?;
+            }
+            previous = Some(attempt);
+        }
+        
+        if self.size_reencodes > 0 {
+            writeln!(f, "# If the output overshoots the target, up to {} more encode(s) follow at a lower -b:v",
+                self.size_reencodes)?;
+        }
+        Ok(())
+    }
+}
+
+fn serialize_seconds<S: serde::Serializer>(duration: &std::time::Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
 }
 
 impl CompressionResult {
@@ -633,11 +776,54 @@ impl CompressionResult {
     }
 }
 
-fn generate_output_path(input_path: &Path) -> Result<PathBuf> {
-    let mut output_path = input_path.to_path_buf();
+/// Bitrate or quality arguments for the selected rate control mode.
+///
+/// Size-targeted modes set `-b:v`/`-maxrate`/`-bufsize` from the planned
+/// bitrate. Constant-quality modes translate `quality_level` (CRF scale,
+/// 0-51) into each encoder's own quality knob; the capped mode additionally
+/// limits the peak rate on encoders that honour VBV with constant quality.
+fn rate_control_args(settings: &CompressionSettings, video_bitrate_kbps: u32) -> Vec<String> {
+    settings.hardware_encoder.backend()
+        .rate_control_args(settings.hardware_quality, output_codec(settings), settings.quality_level, video_bitrate_kbps)
+}
+
+/// Pick the video bitrate for the next encode after `output` overshot `limit`.
+///
+/// Audio is encoded at a fixed bitrate, so only the video share of the file
+/// is scaled. Returns `None` when the bitrate can't be lowered any further.
+fn converge_bitrate(
+    current_kbps: u32,
+    output: FileSize,
+    limit: FileSize,
+    audio_kbps: u32,
+    duration_seconds: f32,
+) -> Option<u32> {
+    let audio_bits = audio_kbps as f64 * 1000.0 * duration_seconds as f64;
+    let actual_video_bits = (output.as_bits() - audio_bits).max(1.0);
+    // Same 1% container overhead as the initial budget
+    let allowed_video_bits = limit.as_bits() * 0.99 - audio_bits;
     
-    // Change extension to .mp4
-    output_path.set_extension("mp4");
+    if allowed_video_bits <= 0.0 {
+        return None;
+    }
+    
+    // Aim slightly below the exact ratio, encoders don't scale linearly
+    let factor = (allowed_video_bits / actual_video_bits * 0.97).clamp(0.25, 0.99);
+    let next_kbps = ((current_kbps as f64 * factor) as u32).max(MIN_VIDEO_BITRATE_KBPS);
+    
+    (next_kbps < current_kbps).then_some(next_kbps)
+}
+
+/// First free `<stem>_compressed.mp4`-style path next to `input_path`, used
+/// when no output path is given.
+pub fn generate_output_path(input_path: &Path) -> Result<PathBuf> {
+    generate_output_path_avoiding(input_path, &HashSet::new())
+}
+
+/// Like [`generate_output_path`], also passing over the paths in `taken`,
+/// such as outputs already assigned to other files of the same batch.
+pub fn generate_output_path_avoiding(input_path: &Path, taken: &HashSet<PathBuf>) -> Result<PathBuf> {
+    let is_free = |candidate: &PathBuf| !candidate.exists() && !taken.contains(candidate);
     
     // Add suffix to avoid overwriting
     let stem = input_path.file_stem()
@@ -652,7 +838,7 @@ fn generate_output_path(input_path: &Path) -> Result<PathBuf> {
     
     for suffix in &suffixes {
         let candidate = parent.join(format!("{}{}.mp4", stem, suffix));
-        if !candidate.exists() {
+        if is_free(&candidate) {
             return Ok(candidate);
         }
     }
@@ -660,7 +846,7 @@ fn generate_output_path(input_path: &Path) -> Result<PathBuf> {
     // If all suffixes are taken, add a number
     for i in 1..1000 {
         let candidate = parent.join(format!("{}_compressed_{}.mp4", stem, i));
-        if !candidate.exists() {
+        if is_free(&candidate) {
             return Ok(candidate);
         }
     }
@@ -668,36 +854,616 @@ fn generate_output_path(input_path: &Path) -> Result<PathBuf> {
     Err(anyhow!("Could not generate unique output filename"))
 }
 
-fn parse_ffmpeg_progress(line: &str, total_duration: f64) -> Option<f32> {
-    // Parse FFmpeg progress output
-    // Example: "frame= 1234 fps=123.4 q=23.0 size= 1234kB time=00:01:23.45 bitrate= 123.4kbits/s speed=1.23x"
-    lazy_static::lazy_static! {
-        static ref TIME_RE: Regex = Regex::new(r"time=(\d{2}):(\d{2}):(\d{2}\.\d+)").unwrap();
-    }
-    
-    if let Some(captures) = TIME_RE.captures(line) {
-        let hours: f64 = captures.get(1)?.as_str().parse().ok()?;
-        let minutes: f64 = captures.get(2)?.as_str().parse().ok()?;
-        let seconds: f64 = captures.get(3)?.as_str().parse().ok()?;
+/// Where one FFmpeg run sits within an encode, for progress reporting
+#[derive(Debug, Clone, Copy)]
+struct PassProgress {
+    stage: EncodeStage,
+    duration_seconds: f32,
+    total_frames: Option<u64>,
+    /// Share of the encode completed before this run starts
+    offset: f32,
+    /// Share of the encode this run accounts for
+    span: f32,
+}
+
+impl PassProgress {
+    /// Progress within this run, by output time or else by frame count
+    fn pass_fraction(&self, update: &ProgressUpdate) -> f32 {
+        if update.pass_finished {
+            return 1.0;
+        }
         
-        let current_time = hours * 3600.0 + minutes * 60.0 + seconds;
-        let progress = (current_time / total_duration).min(1.0) as f32;
+        let by_time = update.out_time.as_secs_f32() / self.duration_seconds.max(0.1);
+        let by_frames = self.total_frames
+            .map(|total| update.frames_processed as f32 / total as f32)
+            .unwrap_or(0.0);
         
-        return Some(progress);
+        if update.out_time.is_zero() { by_frames } else { by_time }.clamp(0.0, 1.0)
+    }
+    
+    fn overall_fraction(&self, update: &ProgressUpdate) -> f32 {
+        (self.offset + self.pass_fraction(update) * self.span).clamp(0.0, 1.0)
     }
     
-    None
+    /// Rough time for the runs after this one, assuming they go as fast as this one
+    fn remaining_after(&self, elapsed: std::time::Duration, update: &ProgressUpdate) -> std::time::Duration {
+        let pass_fraction = self.pass_fraction(update);
+        let later_share = 1.0 - self.offset - self.span;
+        if pass_fraction <= 0.0 || later_share <= 0.0 {
+            return std::time::Duration::ZERO;
+        }
+        
+        let pass_total = elapsed.as_secs_f32() / pass_fraction;
+        std::time::Duration::from_secs_f32(pass_total * later_share / self.span)
+    }
+}
+
+fn send_progress(progress_tx: &Option<ProgressSender>, event: ProgressEvent) {
+    if let Some(tx) = progress_tx {
+        let _ = tx.send(event);
+    }
 }
 
-fn calculate_eta(progress: f32, elapsed: std::time::Duration) -> Option<std::time::Duration> {
-    if progress > 0.0 && progress < 1.0 {
-        let total_time = elapsed.as_secs_f64() / progress as f64;
-        let remaining_time = total_time - elapsed.as_secs_f64();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{CropRect, FramePipeline, Rotation, VideoEdit};
+    use crate::compression::hardware::HardwarePreset;
+    
+    #[test]
+    fn test_converge_bitrate_scales_video_share() {
+        // 10s clip, 128 kbps audio: 1.28 Mbit of the file is audio
+        let limit = FileSize::from_mb(1.0);
+        let output = FileSize::from_mb(1.2);
+        let next = converge_bitrate(600, output, limit, 128, 10.0).unwrap();
+        
+        // Video was ~8.32 Mbit and may be at most ~6.64 Mbit
+        assert!(next < 600);
+        assert!((440..=480).contains(&next), "unexpected bitrate {}", next);
+    }
+    
+    fn settings_for(encoder: HardwareEncoder, mode: HardwareQuality) -> CompressionSettings {
+        CompressionSettings::builder()
+            .hardware_encoder(encoder)
+            .hardware_quality(mode)
+            .quality_level(28)
+            .build()
+    }
+    
+    #[test]
+    fn test_rate_control_size_target() {
+        let args = rate_control_args(&settings_for(HardwareEncoder::NvencH264, HardwareQuality::Auto), 1000);
+        assert_eq!(args, ["-b:v", "1000k", "-maxrate", "1000k", "-bufsize", "2000k", "-rc", "cbr", "-cbr", "true"]);
+        
+        let args = rate_control_args(&settings_for(HardwareEncoder::Software, HardwareQuality::Variable), 1000);
+        assert_eq!(args, ["-b:v", "1000k", "-maxrate", "2000k", "-bufsize", "4000k"]);
+    }
+    
+    #[test]
+    fn test_rate_control_constant_quality_per_encoder() {
+        let args = |encoder| rate_control_args(&settings_for(encoder, HardwareQuality::Constant), 1000);
+        
+        assert_eq!(args(HardwareEncoder::Software), ["-crf", "28"]);
+        assert_eq!(args(HardwareEncoder::NvencH265), ["-rc", "vbr", "-cq", "28", "-b:v", "0"]);
+        assert_eq!(args(HardwareEncoder::AmfH264), ["-rc", "cqp", "-qp_i", "28", "-qp_p", "28", "-qp_b", "28"]);
+        // hevc_amf rejects -qp_b
+        let hevc_amf = CompressionSettings { compatibility_mode: false, ..settings_for(HardwareEncoder::AmfH265, HardwareQuality::Constant) };
+        assert_eq!(rate_control_args(&hevc_amf, 1000), ["-rc", "cqp", "-qp_i", "28", "-qp_p", "28"]);
+        assert_eq!(args(HardwareEncoder::QsvH264), ["-global_quality", "28"]);
+        assert_eq!(args(HardwareEncoder::Vaapi), ["-rc_mode", "CQP", "-qp", "28"]);
+        assert_eq!(args(HardwareEncoder::VideoToolbox), ["-q:v", "45"]);
+    }
+    
+    #[test]
+    fn test_rate_control_capped_quality() {
+        let args = rate_control_args(&settings_for(HardwareEncoder::Software, HardwareQuality::Constrained), 800);
+        assert_eq!(args, ["-crf", "28", "-maxrate", "800k", "-bufsize", "1600k"]);
+        
+        // QSV ICQ ignores VBV; the engine re-encodes by size if the cap is missed
+        let args = rate_control_args(&settings_for(HardwareEncoder::QsvH265, HardwareQuality::Constrained), 800);
+        assert_eq!(args, ["-global_quality", "28"]);
+    }
+    
+    #[test]
+    fn test_converge_bitrate_gives_up_at_minimum() {
+        let limit = FileSize::from_mb(1.0);
+        let output = FileSize::from_mb(1.5);
+        assert_eq!(converge_bitrate(MIN_VIDEO_BITRATE_KBPS, output, limit, 0, 10.0), None);
+        
+        // Audio alone doesn't fit
+        assert_eq!(converge_bitrate(500, output, limit, 1000, 10.0), None);
+    }
+    
+    /// Replays canned stderr and exit codes, writing an output file of the
+    /// next queued size for every encode that isn't a discarded first pass.
+    struct FakeRunner {
+        stderr: Vec<String>,
+        exit: FfmpegExit,
+        /// Cancel the token during this (1-based) call, as a user would
+        cancel_on_call: Option<usize>,
+        output_sizes: std::sync::Mutex<Vec<u64>>,
+        calls: std::sync::Mutex<Vec<FfmpegInvocation>>,
+    }
+    
+    impl FakeRunner {
+        fn new(output_sizes: &[u64]) -> Self {
+            Self {
+                stderr: ["frame=900", "fps=120.0", "out_time_us=30000000", "progress=continue",
+                         "frame=1800", "out_time_us=60000000", "progress=end"]
+                    .map(String::from)
+                    .to_vec(),
+                exit: FfmpegExit::success(),
+                cancel_on_call: None,
+                output_sizes: std::sync::Mutex::new(output_sizes.iter().rev().copied().collect()),
+                calls: std::sync::Mutex::new(Vec::new()),
+            }
+        }
         
-        if remaining_time > 0.0 {
-            return Some(std::time::Duration::from_secs_f64(remaining_time));
+        fn calls(&self) -> Vec<FfmpegInvocation> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+    
+    impl FfmpegRunner for FakeRunner {
+        fn run(
+            &self,
+            invocation: &FfmpegInvocation,
+            cancel: &CancellationToken,
+            on_stderr_line: &mut dyn FnMut(&str),
+        ) -> Result<FfmpegExit> {
+            self.calls.lock().unwrap().push(invocation.clone());
+            if let Some(call) = self.cancel_on_call {
+                if self.calls.lock().unwrap().len() == call {
+                    cancel.cancel();
+                    return Ok(FfmpegExit { success: false, code: None, stderr_tail: String::new() });
+                }
+            }
+            for line in &self.stderr {
+                on_stderr_line(line);
+            }
+            
+            // x264 writes its first-pass statistics next to -passlogfile
+            let args = invocation.args();
+            if let Some(index) = args.iter().position(|arg| arg == "-passlogfile") {
+                std::fs::write(format!("{}-0.log", args[index + 1]), b"stats")?;
+            }
+            
+            let output = invocation.output_path().unwrap();
+            if self.exit.success && output != FfmpegOutput::null().path {
+                let size = self.output_sizes.lock().unwrap().pop().expect("unexpected encode");
+                std::fs::write(output, vec![0u8; size as usize])?;
+            }
+            Ok(self.exit.clone())
+        }
+    }
+    
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        input: PathBuf,
+        output: PathBuf,
+    }
+    
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.mov");
+        std::fs::write(&input, vec![0u8; 4_000_000]).unwrap();
+        let output = dir.path().join("output.mp4");
+        Fixture { _dir: dir, input, output }
+    }
+    
+    fn engine_with(runner: Arc<FakeRunner>) -> CompressionEngine {
+        CompressionEngine::with_runner(HardwareCapabilities::software_only(), runner)
+    }
+    
+    #[tokio::test]
+    async fn test_hardware_encode_runs_single_invocation() {
+        let files = fixture();
+        let runner = Arc::new(FakeRunner::new(&[900_000]));
+        let settings = CompressionSettings::builder()
+            .target_size(FileSize::from_mb(1.0))
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .build();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        
+        let result = engine_with(runner.clone())
+            .compress_with_metadata(&files.input, &files.output, &settings, &VideoMetadata::default_hd(), Some(tx), &CancellationToken::new())
+            .await
+            .unwrap();
+        
+        assert!(result.target_met);
+        assert_eq!(result.size_iterations, 1);
+        
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        let args = calls[0].args();
+        assert!(args.windows(2).any(|pair| pair == ["-hwaccel", "cuda"]));
+        assert!(args.windows(2).any(|pair| pair == ["-c:v", "h264_nvenc"]));
+        assert_eq!(calls[0].output_path(), Some(files.output.as_path()));
+        
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
         }
+        assert_eq!(events.first(), Some(&ProgressEvent::Stage(EncodeStage::Encoding)));
+        assert_eq!(events.last(), Some(&ProgressEvent::Stage(EncodeStage::Finalizing)));
+        
+        let ProgressEvent::Progress(halfway) = &events[1] else { panic!("expected progress, got {:?}", events[1]) };
+        assert_eq!(halfway.fraction, 0.5);
+        assert_eq!(halfway.frames_processed, 900);
+        assert_eq!(halfway.total_frames, Some(1800));
+        assert_eq!(halfway.fps, Some(120.0));
     }
     
-    None
+    #[test]
+    fn test_backends_honor_preset_and_profile() {
+        let engine = engine_with(Arc::new(FakeRunner::new(&[])));
+        let metadata = VideoMetadata::default_hd();
+        let args = |encoder, preset| {
+            let settings = CompressionSettings::builder()
+                .hardware_encoder(encoder)
+                .hardware_preset(preset)
+                .compatibility_mode(false)
+                .build();
+            let plan = engine.size_estimator.plan_encoding(&metadata, &settings);
+            engine.build_invocation(Path::new("in.mov"), Path::new("out.mp4"), &settings, &plan, &metadata).args()
+        };
+        
+        let amf = args(HardwareEncoder::AmfH265, HardwarePreset::Slower);
+        assert_eq!(arg_after(&amf, "-c:v"), Some("hevc_amf"));
+        assert_eq!(arg_after(&amf, "-quality"), Some("quality"));
+        assert_eq!(arg_after(&args(HardwareEncoder::AmfH264, HardwarePreset::Faster), "-quality"), Some("speed"));
+        
+        let qsv = args(HardwareEncoder::QsvH264, HardwarePreset::UltraFast);
+        assert_eq!(arg_after(&qsv, "-preset"), Some("veryfast"));
+        assert_eq!(arg_after(&args(HardwareEncoder::QsvAV1, HardwarePreset::Highest), "-preset"), Some("veryslow"));
+        
+        assert_eq!(arg_after(&args(HardwareEncoder::NvencH264, HardwarePreset::Fast), "-preset"), Some("p3"));
+        assert_eq!(arg_after(&args(HardwareEncoder::Software, HardwarePreset::Slow), "-preset"), Some("slow"));
+        
+        // Without a platform, VAAPI and VideoToolbox fall back to Main
+        let vaapi = args(HardwareEncoder::Vaapi, HardwarePreset::Medium);
+        assert_eq!(arg_after(&vaapi, "-profile:v"), Some("main"));
+        assert_eq!(arg_after(&vaapi, "-level:v"), Some("4.0"));
+        assert_eq!(arg_after(&args(HardwareEncoder::VideoToolbox, HardwarePreset::Medium), "-profile:v"), Some("main"));
+        assert_eq!(arg_after(&args(HardwareEncoder::Software, HardwarePreset::Medium), "-profile:v"), None);
+    }
+    
+    #[test]
+    fn test_platform_sets_codec_profile_and_padding() {
+        let engine = engine_with(Arc::new(FakeRunner::new(&[])));
+        let metadata = VideoMetadata::default_hd();
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(HardwareEncoder::NvencH265)
+            .platform(PlatformPreset::find_built_in("short-video").unwrap())
+            .build_with_capabilities(&HardwareCapabilities {
+                available_encoders: vec![HardwareEncoder::NvencH265, HardwareEncoder::Software],
+                ..HardwareCapabilities::software_only()
+            });
+        let plan = engine.size_estimator.plan_encoding(&metadata, &settings);
+        let args = engine.build_invocation(Path::new("in.mov"), Path::new("out.mp4"), &settings, &plan, &metadata).args();
+        
+        assert!(args.windows(2).any(|pair| pair == ["-c:v", "h264_nvenc"]));
+        assert!(args.windows(2).any(|pair| pair == ["-profile:v", "high"]));
+        assert!(args.windows(2).any(|pair| pair == ["-level:v", "4.2"]));
+        let filters = &args[args.iter().position(|arg| arg == "-vf").unwrap() + 1];
+        // CUDA scales; padding has no CUDA version
+        assert_eq!(filters, "scale_cuda=w=1080:h=1920:force_original_aspect_ratio=decrease:force_divisible_by=2,\
+            hwdownload,format=nv12,pad=1080:1920:(ow-iw)/2:(oh-ih)/2,setsar=1,hwupload");
+    }
+    
+    /// Arguments `encoder` would encode `metadata` with, scaled down to 720p
+    fn gpu_args(encoder: HardwareEncoder, metadata: &VideoMetadata) -> Vec<String> {
+        let engine = engine_with(Arc::new(FakeRunner::new(&[])));
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(encoder)
+            .max_height(720)
+            .build();
+        let plan = engine.size_estimator.plan_encoding(metadata, &settings);
+        assert_eq!(plan.height, 720);
+        engine.build_invocation(Path::new("in.mov"), Path::new("out.mp4"), &settings, &plan, metadata).args()
+    }
+    
+    fn arg_after<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+        args.iter().position(|arg| arg == flag).map(|index| args[index + 1].as_str())
+    }
+    
+    #[test]
+    fn test_full_gpu_pipelines_decode_and_scale_on_device() {
+        let metadata = VideoMetadata::default_hd();
+        
+        let cuda = gpu_args(HardwareEncoder::NvencH264, &metadata);
+        assert_eq!(arg_after(&cuda, "-init_hw_device"), Some("cuda=gpu:0"));
+        assert_eq!(arg_after(&cuda, "-hwaccel"), Some("cuda"));
+        assert_eq!(arg_after(&cuda, "-hwaccel_output_format"), Some("cuda"));
+        assert_eq!(arg_after(&cuda, "-vf"), Some("scale_cuda=w=1280:h=720"));
+        assert_eq!(arg_after(&cuda, "-pix_fmt"), None);
+        
+        let vaapi = gpu_args(HardwareEncoder::Vaapi, &metadata);
+        assert_eq!(arg_after(&vaapi, "-init_hw_device"), Some("vaapi=gpu:/dev/dri/renderD128"));
+        assert_eq!(arg_after(&vaapi, "-filter_hw_device"), Some("gpu"));
+        assert_eq!(arg_after(&vaapi, "-hwaccel_output_format"), Some("vaapi"));
+        assert_eq!(arg_after(&vaapi, "-vf"), Some("scale_vaapi=w=1280:h=720"));
+        assert_eq!(arg_after(&vaapi, "-pix_fmt"), None);
+        
+        // QSV decodes with its own decoder, named before the input
+        let qsv = gpu_args(HardwareEncoder::QsvH264, &metadata);
+        let input = qsv.iter().position(|arg| arg == "-i").unwrap();
+        assert_eq!(qsv[..input].windows(2).find(|pair| pair[0] == "-c:v"), Some(&["-c:v".to_string(), "h264_qsv".to_string()][..]));
+        assert_eq!(arg_after(&qsv, "-hwaccel_output_format"), Some("qsv"));
+        assert_eq!(arg_after(&qsv, "-vf"), Some("scale_qsv=w=1280:h=720"));
+        assert_eq!(arg_after(&qsv, "-pix_fmt"), None);
+    }
+    
+    #[test]
+    fn test_undecodable_input_falls_back_to_hybrid_pipeline() {
+        let ten_bit = VideoMetadata { codec: "hevc".to_string(), profile: Some("Main 10".to_string()), ..VideoMetadata::default_hd() };
+        let prores = VideoMetadata { codec: "prores".to_string(), profile: None, ..VideoMetadata::default_hd() };
+        let upside_down = VideoMetadata { rotation: 180, ..VideoMetadata::default_hd() };
+        
+        for metadata in [&ten_bit, &prores, &upside_down] {
+            // NVENC takes system memory frames on the chosen GPU
+            let cuda = gpu_args(HardwareEncoder::NvencH264, metadata);
+            assert_eq!(arg_after(&cuda, "-hwaccel"), None);
+            assert_eq!(arg_after(&cuda, "-vf"), Some("scale=1280:720"));
+            assert_eq!(arg_after(&cuda, "-pix_fmt"), Some("yuv420p"));
+            
+            // VAAPI only encodes surfaces, so the frames are uploaded last
+            let vaapi = gpu_args(HardwareEncoder::Vaapi, metadata);
+            assert_eq!(arg_after(&vaapi, "-init_hw_device"), Some("vaapi=gpu:/dev/dri/renderD128"));
+            assert_eq!(arg_after(&vaapi, "-hwaccel"), None);
+            assert_eq!(arg_after(&vaapi, "-vf"), Some("scale=1280:720,format=nv12,hwupload"));
+            assert_eq!(arg_after(&vaapi, "-pix_fmt"), None);
+            
+            let qsv = gpu_args(HardwareEncoder::QsvH264, metadata);
+            let input = qsv.iter().position(|arg| arg == "-i").unwrap();
+            assert!(!qsv[..input].contains(&"-c:v".to_string()), "{:?}", qsv);
+            assert_eq!(arg_after(&qsv, "-vf"), Some("scale=1280:720"));
+            assert_eq!(arg_after(&qsv, "-pix_fmt"), Some("nv12"));
+        }
+        
+        // Turning the GPU pipeline off does the same for decodable input
+        let engine = engine_with(Arc::new(FakeRunner::new(&[])));
+        let metadata = VideoMetadata::default_hd();
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .cuda_device(1)
+            .full_gpu_pipeline(false)
+            .build();
+        let plan = engine.size_estimator.plan_encoding(&metadata, &settings);
+        let args = engine.build_invocation(Path::new("in.mov"), Path::new("out.mp4"), &settings, &plan, &metadata).args();
+        assert_eq!(arg_after(&args, "-hwaccel"), None);
+        assert_eq!(arg_after(&args, "-gpu"), Some("1"));
+    }
+    
+    #[test]
+    fn test_edit_trims_input_and_plans_for_kept_part() {
+        let engine = engine_with(Arc::new(FakeRunner::new(&[])));
+        let metadata = VideoMetadata { duration_seconds: 600.0, ..VideoMetadata::default_hd() };
+        let edit = VideoEdit {
+            start: Some(60.0),
+            end: Some(80.0),
+            crop: Some(CropRect { width: 1080, height: 1080, x: 420, y: 0 }),
+            rotation: Rotation::Clockwise90,
+        };
+        let settings = CompressionSettings::builder()
+            .target_size(FileSize::from_mb(8.0))
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .edit(edit)
+            .build();
+        
+        let dry_run = engine.dry_run_with_metadata(Path::new("in.mov"), Path::new("out.mp4"), &settings, metadata.clone()).unwrap();
+        let twenty_seconds = VideoMetadata { duration_seconds: 20.0, width: 1080, height: 1080, ..metadata.clone() };
+        assert_eq!(dry_run.plan, engine.size_estimator.plan_encoding(&twenty_seconds, &settings));
+        
+        let args = dry_run.attempts[0].invocations[0].args();
+        let input = args.iter().position(|arg| arg == "-i").unwrap();
+        assert_eq!(args[input - 4..input], ["-ss", "60.000", "-t", "20.000"]);
+        let filters = &args[args.iter().position(|arg| arg == "-vf").unwrap() + 1];
+        assert!(filters.starts_with("hwdownload,format=nv12,crop=1080:1080:420:0,transpose=clock"), "{}", filters);
+        
+        let past_the_end = CompressionSettings::builder()
+            .edit(VideoEdit { start: Some(700.0), ..VideoEdit::default() })
+            .build();
+        let error = engine.dry_run_with_metadata(Path::new("in.mov"), Path::new("out.mp4"), &past_the_end, metadata).unwrap_err();
+        assert!(error.is::<EditError>());
+    }
+    
+    #[test]
+    fn test_dry_run_lists_passes_and_fallbacks() {
+        let runner = Arc::new(FakeRunner::new(&[]));
+        let engine = engine_with(runner.clone());
+        let settings = CompressionSettings::builder()
+            .target_size(FileSize::from_mb(1.0))
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .build();
+        
+        let dry_run = engine.dry_run_with_metadata(Path::new("in.mov"), Path::new("out.mp4"), &settings, VideoMetadata::default_hd()).unwrap();
+        
+        // NVENC is retried until it has failed three times, then software takes over
+        let encoders: Vec<_> = dry_run.attempts.iter().map(|attempt| attempt.encoder).collect();
+        assert_eq!(encoders, [HardwareEncoder::NvencH264; 3]);
+        assert_eq!(dry_run.attempts[0].invocations.len(), 1);
+        assert!(dry_run.attempts[0].commands[0].contains("-c:v h264_nvenc"));
+        
+        // A failed full-GPU attempt is retried with CPU decoding first
+        let pipelines: Vec<_> = dry_run.attempts.iter().map(|attempt| attempt.pipeline).collect();
+        assert_eq!(pipelines, [
+            EncodePipeline::Gpu(FramePipeline::Cuda),
+            EncodePipeline::Hybrid(FramePipeline::Cuda),
+            EncodePipeline::Hybrid(FramePipeline::Cuda),
+        ]);
+        let script = dry_run.to_string();
+        assert!(script.contains("# Attempt 2, if attempt 1 fails: NvencH264 again, CPU decoding and filtering, Cuda encoding"), "{}", script);
+        assert!(script.contains("# Attempt 3, if attempt 2 fails: NvencH264 again, same commands"), "{}", script);
+        
+        let settings = CompressionSettings::builder().target_size(FileSize::from_mb(1.0)).build();
+        let dry_run = engine.dry_run_with_metadata(Path::new("in.mov"), Path::new("out.mp4"), &settings, VideoMetadata::default_hd()).unwrap();
+        let passes = &dry_run.attempts[0].invocations;
+        assert_eq!(passes.len(), 2);
+        assert!(passes[0].args().windows(2).any(|pair| pair == ["-pass", "1"]));
+        assert!(passes[1].args().windows(2).any(|pair| pair == ["-pass", "2"]));
+        
+        let script = dry_run.to_string();
+        assert!(script.contains(&dry_run.attempts[0].commands[1]));
+        assert!(script.contains("# Attempt 2, if attempt 1 fails: Software again, same commands"));
+        assert!(runner.calls().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_rotated_input_decodes_on_the_cpu() {
+        let files = fixture();
+        let phone = VideoMetadata { width: 1080, height: 1920, rotation: 90, ..VideoMetadata::default_hd() };
+        let settings = CompressionSettings::builder()
+            .target_size(FileSize::from_mb(1.0))
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .edit(VideoEdit { start: Some(5.0), ..VideoEdit::default() })
+            .build();
+        
+        let runner = Arc::new(FakeRunner::new(&[900_000]));
+        let engine = engine_with(runner.clone());
+        let dry_run = engine.dry_run_with_metadata(&files.input, &files.output, &settings, phone.clone()).unwrap();
+        
+        // No full-GPU attempt, so NVENC's retries are all hybrid
+        let pipelines: Vec<_> = dry_run.attempts.iter().map(|attempt| attempt.pipeline).collect();
+        assert_eq!(pipelines, [EncodePipeline::Hybrid(FramePipeline::Cuda); 3]);
+        assert!(!dry_run.attempts[0].commands[0].contains("-hwaccel"));
+        
+        engine.compress_with_metadata(&files.input, &files.output, &settings, &phone, None, &CancellationToken::new())
+            .await
+            .unwrap();
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(!calls[0].args().iter().any(|arg| arg == "-hwaccel"));
+    }
+    
+    #[tokio::test]
+    async fn test_software_encode_uses_two_passes() {
+        let files = fixture();
+        let runner = Arc::new(FakeRunner::new(&[900_000]));
+        let settings = CompressionSettings::builder().target_size(FileSize::from_mb(1.0)).build();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        
+        engine_with(runner.clone())
+            .compress_with_metadata(&files.input, &files.output, &settings, &VideoMetadata::default_hd(), Some(tx), &CancellationToken::new())
+            .await
+            .unwrap();
+        
+        // Both passes report, each covering half of the bar
+        let mut fractions = Vec::new();
+        let mut stages = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                ProgressEvent::Stage(stage) => stages.push(stage),
+                ProgressEvent::Progress(update) => fractions.push(update.fraction),
+            }
+        }
+        assert_eq!(stages, [EncodeStage::FirstPass, EncodeStage::SecondPass, EncodeStage::Finalizing]);
+        assert_eq!(fractions, [0.25, 0.5, 0.75, 1.0]);
+        
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].outputs, vec![FfmpegOutput::null()]);
+        assert!(calls[0].args().windows(2).any(|pair| pair == ["-pass", "1"]));
+        assert!(calls[1].args().windows(2).any(|pair| pair == ["-pass", "2"]));
+    }
+    
+    #[tokio::test]
+    async fn test_oversized_output_is_reencoded_at_lower_bitrate() {
+        let files = fixture();
+        let runner = Arc::new(FakeRunner::new(&[1_300_000, 950_000]));
+        let settings = CompressionSettings::builder()
+            .target_size(FileSize::from_mb(1.0))
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .build();
+        
+        let result = engine_with(runner.clone())
+            .compress_with_metadata(&files.input, &files.output, &settings, &VideoMetadata::default_hd(), None, &CancellationToken::new())
+            .await
+            .unwrap();
+        
+        assert!(result.target_met);
+        assert_eq!(result.size_iterations, 2);
+        
+        let bitrate = |invocation: &FfmpegInvocation| {
+            let args = invocation.args();
+            let index = args.iter().position(|arg| arg == "-b:v").unwrap();
+            args[index + 1].trim_end_matches('k').parse::<u32>().unwrap()
+        };
+        let calls = runner.calls();
+        assert!(bitrate(&calls[1]) < bitrate(&calls[0]));
+    }
+    
+    #[tokio::test]
+    async fn test_failed_encode_reports_stderr() {
+        let files = fixture();
+        let mut runner = FakeRunner::new(&[]);
+        runner.exit = FfmpegExit::failure(1, "Unknown encoder 'h264_nvenc'");
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .build();
+        
+        let error = engine_with(Arc::new(runner))
+            .compress_with_metadata(&files.input, &files.output, &settings, &VideoMetadata::default_hd(), None, &CancellationToken::new())
+            .await
+            .unwrap_err();
+        
+        assert!(error.to_string().contains("Unknown encoder"));
+    }
+    
+    #[tokio::test]
+    async fn test_cancel_removes_partial_output_and_pass_logs() {
+        let files = fixture();
+        let mut runner = FakeRunner::new(&[]);
+        runner.cancel_on_call = Some(2);
+        let runner = Arc::new(runner);
+        let settings = CompressionSettings::builder().build();
+        
+        // What a killed second pass leaves behind
+        std::fs::write(&files.output, b"partial").unwrap();
+        
+        let error = engine_with(runner.clone())
+            .compress_with_metadata(&files.input, &files.output, &settings, &VideoMetadata::default_hd(), None, &CancellationToken::new())
+            .await
+            .unwrap_err();
+        
+        assert!(error.is::<Cancelled>());
+        assert!(!files.output.exists());
+        
+        let calls = runner.calls();
+        assert_eq!(calls.len(), 2);
+        let args = calls[0].args();
+        let log_file = PathBuf::from(&args[args.iter().position(|arg| arg == "-passlogfile").unwrap() + 1]);
+        assert!(!log_file.parent().unwrap().exists());
+    }
+    
+    #[tokio::test]
+    async fn test_cancelled_token_runs_nothing() {
+        let files = fixture();
+        let runner = Arc::new(FakeRunner::new(&[]));
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        
+        let error = engine_with(runner.clone())
+            .compress_with_metadata(&files.input, &files.output, &CompressionSettings::default(), &VideoMetadata::default_hd(), None, &cancel)
+            .await
+            .unwrap_err();
+        
+        assert!(error.is::<Cancelled>());
+        assert!(runner.calls().is_empty());
+    }
+    
+    #[test]
+    fn test_generated_output_skips_taken_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let mov = dir.path().join("a.mov");
+        let mkv = dir.path().join("a.mkv");
+        
+        let mut taken = HashSet::new();
+        let first = generate_output_path_avoiding(&mov, &taken).unwrap();
+        taken.insert(first.clone());
+        let second = generate_output_path_avoiding(&mkv, &taken).unwrap();
+        
+        assert_eq!(first, dir.path().join("a_compressed.mp4"));
+        assert_eq!(second, dir.path().join("a_small.mp4"));
+    }
 }
@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use super::hardware::HardwareEncoder;
-use super::{CompressionSettings, TargetSize};
+use super::{CompressionSettings, EncodingPlan, FileSize};
 
 #[derive(Debug, Clone)]
 pub struct SizeEstimator {
@@ -51,8 +51,8 @@ impl SizeEstimator {
         metadata: &VideoMetadata,
         settings: &CompressionSettings,
     ) -> Result<SizeEstimation> {
-        let target_mb = settings.target_size.as_mb();
-        let optimal_bitrate = self.calculate_bitrate_for_target(metadata, target_mb);
+        let target_mb = settings.target_size.as_mb() as f32;
+        let optimal_bitrate = self.calculate_bitrate_for_target(metadata, settings.target_size);
         
         let encoding_time = self.estimate_encoding_time(metadata, settings);
         let quality_score = self.estimate_quality_from_bitrate(metadata, optimal_bitrate);
@@ -69,14 +69,14 @@ impl SizeEstimator {
     }
     
     /// Calculate bitrate needed for target file size
-    fn calculate_bitrate_for_target(&self, metadata: &VideoMetadata, target_mb: f32) -> u32 {
+    fn calculate_bitrate_for_target(&self, metadata: &VideoMetadata, target_size: FileSize) -> u32 {
         let duration_seconds = metadata.duration_seconds;
         
         // Calculate total available bits
-        let total_bits = target_mb * 8.0 * 1024.0 * 1024.0;
+        let total_bits = target_size.as_bits() as f32;
         
         // Reserve space for audio (assume 128 kbps)
-        let audio_bits = 128.0 * 1024.0 * duration_seconds;
+        let audio_bits = 128.0 * 1000.0 * duration_seconds;
         
         // Reserve 2% for container overhead
         let container_overhead = total_bits * 0.02;
@@ -86,7 +86,7 @@ impl SizeEstimator {
         
         // Calculate video bitrate in kbps
         let video_bitrate_bps = available_video_bits / duration_seconds;
-        let video_bitrate_kbps = video_bitrate_bps / 1024.0;
+        let video_bitrate_kbps = video_bitrate_bps / 1000.0;
         
         // Apply encoder efficiency adjustment
         let efficiency = self.encoder_efficiency.get(&HardwareEncoder::Software).unwrap_or(&1.0);
@@ -101,29 +101,8 @@ impl SizeEstimator {
     
     /// Estimate quality score from bitrate
     fn estimate_quality_from_bitrate(&self, metadata: &VideoMetadata, bitrate_kbps: u32) -> f32 {
-        // Calculate bits per pixel per frame
-        let pixel_count = metadata.width * metadata.height;
-        let bits_per_pixel = (bitrate_kbps as f32 * 1024.0) / (pixel_count as f32 * metadata.fps);
-        
-        // Map bits per pixel to quality score
-        let quality_score = match bits_per_pixel {
-            x if x >= 0.20 => 0.95,  // Excellent quality
-            x if x >= 0.15 => 0.85,  // Very good quality
-            x if x >= 0.10 => 0.75,  // Good quality
-            x if x >= 0.07 => 0.60,  // Acceptable quality
-            x if x >= 0.04 => 0.45,  // Poor quality
-            _ => 0.25,               // Very poor quality
-        };
-        
-        // Adjust for content complexity
-        let complexity_adjustment = match metadata.estimated_complexity {
-            ContentComplexity::Low => 1.1,    // Simple content looks better at lower bitrates
-            ContentComplexity::Medium => 1.0,  // Normal adjustment
-            ContentComplexity::High => 0.9,    // Complex content needs more bitrate
-        };
-        
-        let adjusted_score: f32 = quality_score * complexity_adjustment;
-        adjusted_score.min(1.0)
+        let bpp = bits_per_pixel(bitrate_kbps, metadata.width, metadata.height, metadata.fps);
+        quality_from_bits_per_pixel(bpp, &metadata.estimated_complexity)
     }
     
     /// Get encoder efficiency multiplier
@@ -169,11 +148,12 @@ impl SizeEstimator {
     pub fn recommend_bitrate_for_size(
         &self,
         metadata: &VideoMetadata,
-        target_size: TargetSize,
+        target_size: impl Into<FileSize>,
         encoder: &HardwareEncoder,
     ) -> Result<BitrateRecommendation> {
-        let target_mb = target_size.as_mb();
-        let optimal_bitrate = self.calculate_bitrate_for_target(metadata, target_mb);
+        let target_size = target_size.into();
+        let target_mb = target_size.as_mb() as f32;
+        let optimal_bitrate = self.calculate_bitrate_for_target(metadata, target_size);
         
         // Adjust for encoder efficiency
         let efficiency = self.get_encoder_efficiency(encoder);
@@ -190,6 +170,63 @@ impl SizeEstimator {
     }
 }
 
+impl SizeEstimator {
+    /// Plan `settings` at each of `targets` and estimate how long each of
+    /// `encoders` would take, without encoding anything.
+    pub fn estimate_targets(
+        &self,
+        metadata: &VideoMetadata,
+        settings: &CompressionSettings,
+        targets: &[FileSize],
+        encoders: &[HardwareEncoder],
+    ) -> Vec<TargetEstimate> {
+        targets.iter().map(|&target_size| {
+            let mut target_settings = settings.clone();
+            target_settings.target_size = target_size;
+            let plan = self.plan_encoding(metadata, &target_settings);
+            
+            let encode_times = encoders.iter().map(|&encoder| {
+                target_settings.hardware_encoder = encoder;
+                target_settings.enable_hardware_accel = encoder.is_hardware_accelerated();
+                EncodeTimeEstimate {
+                    encoder,
+                    seconds: self.estimate_encoding_time(metadata, &target_settings),
+                }
+            }).collect();
+            
+            TargetEstimate { target_size, plan, encode_times }
+        }).collect()
+    }
+}
+
+/// Bits spent per pixel per frame at the given video bitrate
+pub fn bits_per_pixel(bitrate_kbps: u32, width: u32, height: u32, fps: f32) -> f32 {
+    let pixel_count = (width * height) as f32;
+    (bitrate_kbps as f32 * 1000.0) / (pixel_count * fps).max(1.0)
+}
+
+/// Map bits per pixel to a 0.0-1.0 quality score
+pub fn quality_from_bits_per_pixel(bits_per_pixel: f32, complexity: &ContentComplexity) -> f32 {
+    let quality_score = match bits_per_pixel {
+        x if x >= 0.20 => 0.95,  // Excellent quality
+        x if x >= 0.15 => 0.85,  // Very good quality
+        x if x >= 0.10 => 0.75,  // Good quality
+        x if x >= 0.07 => 0.60,  // Acceptable quality
+        x if x >= 0.04 => 0.45,  // Poor quality
+        _ => 0.25,               // Very poor quality
+    };
+    
+    // Adjust for content complexity
+    let complexity_adjustment = match complexity {
+        ContentComplexity::Low => 1.1,    // Simple content looks better at lower bitrates
+        ContentComplexity::Medium => 1.0,  // Normal adjustment
+        ContentComplexity::High => 0.9,    // Complex content needs more bitrate
+    };
+    
+    let adjusted_score: f32 = quality_score * complexity_adjustment;
+    adjusted_score.min(1.0)
+}
+
 impl Default for SizeEstimator {
     fn default() -> Self {
         Self::new()
@@ -198,14 +235,27 @@ impl Default for SizeEstimator {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoMetadata {
+    /// Size of the upright frame, after the display rotation
     pub width: u32,
     pub height: u32,
+    /// Clockwise display rotation in degrees (0, 90, 180 or 270), which
+    /// FFmpeg applies while decoding
+    #[serde(default)]
+    pub rotation: u32,
     pub fps: f32,
     pub duration_seconds: f32,
     pub bitrate_kbps: Option<u32>,
     pub codec: String,
+    /// Video codec profile, e.g. "High"
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Video codec level as ffprobe reports it, e.g. 41 for H.264 level 4.1
+    #[serde(default)]
+    pub level: Option<u32>,
     pub estimated_complexity: ContentComplexity,
     pub has_audio: bool,
+    #[serde(default)]
+    pub audio_codec: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -224,6 +274,21 @@ pub struct SizeEstimation {
     pub recommended_bitrate_kbps: Option<u32>,
 }
 
+/// What compressing to one target size would look like.
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetEstimate {
+    pub target_size: FileSize,
+    /// Resolution, bitrates, quality score and feasibility the encode would use
+    pub plan: EncodingPlan,
+    pub encode_times: Vec<EncodeTimeEstimate>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodeTimeEstimate {
+    pub encoder: HardwareEncoder,
+    pub seconds: f32,
+}
+
 #[derive(Debug, Clone)]
 pub struct BitrateRecommendation {
     pub recommended_bitrate_kbps: u32,
@@ -238,12 +303,16 @@ impl VideoMetadata {
         Self {
             width: 1920,
             height: 1080,
+            rotation: 0,
             fps: 30.0,
             duration_seconds: 60.0,
             bitrate_kbps: Some(5000),
             codec: "h264".to_string(),
+            profile: Some("High".to_string()),
+            level: Some(40),
             estimated_complexity: ContentComplexity::Medium,
             has_audio: true,
+            audio_codec: Some("aac".to_string()),
         }
     }
     
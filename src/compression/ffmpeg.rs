@@ -0,0 +1,321 @@
+//! Typed model of an FFmpeg command line and the runner that executes it.
+//!
+//! The engine never builds a `std::process::Command` directly. It describes
+//! each encode as an [`FfmpegInvocation`] and hands it to an [`FfmpegRunner`],
+//! so the exact arguments can be logged, serialized or replayed by a fake
+//! runner in tests.
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::cancel::CancellationToken;
+use super::filter::{FilterChain, FramePipeline};
+
+/// Number of stderr lines kept for error messages
+const STDERR_TAIL_LINES: usize = 20;
+
+/// An input file and the options that precede its `-i`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FfmpegInput {
+    pub options: Vec<String>,
+    pub path: PathBuf,
+}
+
+/// An output file and the options that precede it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FfmpegOutput {
+    pub options: Vec<String>,
+    pub path: PathBuf,
+}
+
+impl FfmpegOutput {
+    /// Discard the encoded stream, as the first of two passes does
+    pub fn null() -> Self {
+        let path = if cfg!(target_os = "windows") { "NUL" } else { "/dev/null" };
+        Self {
+            options: vec!["-f".to_string(), "null".to_string()],
+            path: PathBuf::from(path),
+        }
+    }
+}
+
+/// A complete FFmpeg command line.
+///
+/// Arguments are emitted in the order `global_args`, each input, the video
+/// filter chain, `codec_args`, then each output. Codec arguments therefore
+/// apply to the first output. With a single input the chain is a `-vf`;
+/// with several it becomes a `-filter_complex` on the first input's video,
+/// mapped to the output together with that input's audio.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FfmpegInvocation {
+    pub program: String,
+    pub global_args: Vec<String>,
+    pub inputs: Vec<FfmpegInput>,
+    /// Video filters, joined with `,` into a single chain, as rendered by
+    /// [`FilterChain::render`](super::FilterChain::render)
+    pub filters: Vec<String>,
+    pub codec_args: Vec<String>,
+    pub outputs: Vec<FfmpegOutput>,
+}
+
+impl FfmpegInvocation {
+    /// An `ffmpeg` invocation reading `input` and writing `output`
+    pub fn new(input: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        Self {
+            program: "ffmpeg".to_string(),
+            global_args: Vec::new(),
+            inputs: vec![FfmpegInput { options: Vec::new(), path: input.into() }],
+            filters: Vec::new(),
+            codec_args: Vec::new(),
+            outputs: vec![FfmpegOutput { options: Vec::new(), path: output.into() }],
+        }
+    }
+
+    pub fn global_arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.global_args.push(arg.into());
+        self
+    }
+
+    /// Add an option before the first input's `-i`
+    pub fn input_arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        if let Some(input) = self.inputs.first_mut() {
+            input.options.push(arg.into());
+        }
+        self
+    }
+
+    pub fn filter(&mut self, filter: impl Into<String>) -> &mut Self {
+        self.filters.push(filter.into());
+        self
+    }
+
+    /// Append `chain` as it runs on frames in `pipeline`
+    pub fn filter_chain(&mut self, chain: &FilterChain, pipeline: FramePipeline) -> &mut Self {
+        self.filters.extend(chain.render(pipeline));
+        self
+    }
+
+    pub fn codec_arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.codec_args.push(arg.into());
+        self
+    }
+
+    pub fn codec_args<I, S>(&mut self, args: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.codec_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Path of the first output, if any
+    pub fn output_path(&self) -> Option<&Path> {
+        self.outputs.first().map(|output| output.path.as_path())
+    }
+
+    /// All arguments after the program name
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.global_args.clone();
+
+        for input in &self.inputs {
+            args.extend(input.options.iter().cloned());
+            args.push("-i".to_string());
+            args.push(input.path.to_string_lossy().into_owned());
+        }
+
+        if !self.filters.is_empty() && self.inputs.len() > 1 {
+            // `-vf` would pick whichever input's video FFmpeg likes best
+            args.push("-filter_complex".to_string());
+            args.push(format!("[0:v]{}[video]", self.filters.join(",")));
+            args.extend(["-map", "[video]", "-map", "0:a?"].map(String::from));
+        } else if !self.filters.is_empty() {
+            args.push("-vf".to_string());
+            args.push(self.filters.join(","));
+        }
+
+        args.extend(self.codec_args.iter().cloned());
+
+        for output in &self.outputs {
+            args.extend(output.options.iter().cloned());
+            args.push(output.path.to_string_lossy().into_owned());
+        }
+
+        args
+    }
+
+    pub fn to_command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(self.args());
+        cmd
+    }
+}
+
+impl fmt::Display for FfmpegInvocation {
+    /// Renders the invocation as a POSIX shell command line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", shell_quote(&self.program))?;
+        for arg in self.args() {
+            write!(f, " {}", shell_quote(&arg))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quote `arg` for a POSIX shell if it contains anything but safe characters
+fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c));
+
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// How an FFmpeg run ended.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FfmpegExit {
+    pub success: bool,
+    /// Process exit code; `None` when killed by a signal
+    pub code: Option<i32>,
+    /// Last lines FFmpeg wrote to stderr, for error messages
+    pub stderr_tail: String,
+}
+
+impl FfmpegExit {
+    pub fn success() -> Self {
+        Self { success: true, code: Some(0), stderr_tail: String::new() }
+    }
+
+    pub fn failure(code: i32, stderr_tail: impl Into<String>) -> Self {
+        Self { success: false, code: Some(code), stderr_tail: stderr_tail.into() }
+    }
+}
+
+/// Executes FFmpeg invocations for the engine.
+///
+/// Implementations run the invocation to completion, passing every line
+/// written to stderr to `on_stderr_line` as it arrives, and should stop as
+/// soon as `cancel` is cancelled. The engine calls this from a blocking task
+/// and treats any exit after cancellation as [`Cancelled`](super::Cancelled).
+pub trait FfmpegRunner: Send + Sync {
+    fn run(
+        &self,
+        invocation: &FfmpegInvocation,
+        cancel: &CancellationToken,
+        on_stderr_line: &mut dyn FnMut(&str),
+    ) -> Result<FfmpegExit>;
+}
+
+/// Runs invocations as real FFmpeg processes.
+///
+/// On Unix each process is started in its own process group so that
+/// cancelling or pausing reaches any helpers FFmpeg spawns, and so that a
+/// terminal Ctrl-C is left to the application to handle.
+#[derive(Debug, Clone, Default)]
+pub struct SystemFfmpegRunner;
+
+impl FfmpegRunner for SystemFfmpegRunner {
+    fn run(
+        &self,
+        invocation: &FfmpegInvocation,
+        cancel: &CancellationToken,
+        on_stderr_line: &mut dyn FnMut(&str),
+    ) -> Result<FfmpegExit> {
+        let mut cmd = invocation.to_command();
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            cmd.process_group(0);
+        }
+
+        let mut child = cmd.spawn()
+            .map_err(|e| anyhow!("Failed to spawn FFmpeg: {}. Is ffmpeg installed?", e))?;
+        let pid = child.id();
+        cancel.attach(pid);
+
+        let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+        if let Some(stderr) = child.stderr.take() {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                on_stderr_line(&line);
+
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        }
+
+        let status = child.wait();
+        cancel.detach(pid);
+        let status = status.map_err(|e| anyhow!("Failed to wait for FFmpeg: {}", e))?;
+
+        Ok(FfmpegExit {
+            success: status.success(),
+            code: status.code(),
+            stderr_tail: Vec::from(tail).join("\n"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_args_order() {
+        let mut invocation = FfmpegInvocation::new("in.mov", "out.mp4");
+        invocation.global_arg("-y")
+            .input_arg("-hwaccel").input_arg("cuda")
+            .filter("scale=1280:720")
+            .filter("fps=30")
+            .codec_args(["-c:v", "h264_nvenc"]);
+
+        assert_eq!(
+            invocation.args(),
+            ["-y", "-hwaccel", "cuda", "-i", "in.mov", "-vf", "scale=1280:720,fps=30", "-c:v", "h264_nvenc", "out.mp4"]
+        );
+    }
+
+    #[test]
+    fn test_filters_for_several_inputs_use_filter_complex() {
+        let mut invocation = FfmpegInvocation::new("in.mov", "out.mp4");
+        invocation.inputs.push(FfmpegInput { options: Vec::new(), path: PathBuf::from("logo.png") });
+        invocation.filter("scale=1280:720");
+
+        assert_eq!(
+            invocation.args(),
+            ["-i", "in.mov", "-i", "logo.png", "-filter_complex", "[0:v]scale=1280:720[video]",
+             "-map", "[video]", "-map", "0:a?", "out.mp4"]
+        );
+    }
+
+    #[test]
+    fn test_display_quotes_for_shell() {
+        let mut invocation = FfmpegInvocation::new("my clip's.mov", "out.mp4");
+        invocation.codec_args(["-c:v", "libx264"]);
+
+        assert_eq!(invocation.to_string(), r"ffmpeg -i 'my clip'\''s.mov' -c:v libx264 out.mp4");
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let mut invocation = FfmpegInvocation::new("in.mov", "out.mp4");
+        invocation.codec_args(["-b:v", "800k"]);
+
+        let json = serde_json::to_string(&invocation).unwrap();
+        let back: FfmpegInvocation = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, invocation);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::TargetSize;
+
+const KB: u64 = 1_000;
+const MB: u64 = 1_000_000;
+const GB: u64 = 1_000_000_000;
+const KIB: u64 = 1 << 10;
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+
+/// Units accepted when parsing a [`FileSize`].
+///
+/// `KB`/`MB`/`GB` are decimal (powers of 1000), matching how upload limits
+/// are usually advertised. `KiB`/`MiB`/`GiB` are binary (powers of 1024).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnit {
+    Bytes,
+    Kilobytes,
+    Megabytes,
+    Gigabytes,
+    Kibibytes,
+    Mebibytes,
+    Gibibytes,
+}
+
+impl SizeUnit {
+    pub fn bytes(&self) -> u64 {
+        match self {
+            SizeUnit::Bytes => 1,
+            SizeUnit::Kilobytes => KB,
+            SizeUnit::Megabytes => MB,
+            SizeUnit::Gigabytes => GB,
+            SizeUnit::Kibibytes => KIB,
+            SizeUnit::Mebibytes => MIB,
+            SizeUnit::Gibibytes => GIB,
+        }
+    }
+
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            SizeUnit::Bytes => "B",
+            SizeUnit::Kilobytes => "KB",
+            SizeUnit::Megabytes => "MB",
+            SizeUnit::Gigabytes => "GB",
+            SizeUnit::Kibibytes => "KiB",
+            SizeUnit::Mebibytes => "MiB",
+            SizeUnit::Gibibytes => "GiB",
+        }
+    }
+
+    fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix.to_ascii_lowercase().as_str() {
+            "" | "b" | "byte" | "bytes" => Some(SizeUnit::Bytes),
+            "k" | "kb" => Some(SizeUnit::Kilobytes),
+            "m" | "mb" => Some(SizeUnit::Megabytes),
+            "g" | "gb" => Some(SizeUnit::Gigabytes),
+            "ki" | "kib" => Some(SizeUnit::Kibibytes),
+            "mi" | "mib" => Some(SizeUnit::Mebibytes),
+            "gi" | "gib" => Some(SizeUnit::Gibibytes),
+            _ => None,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ParseFileSizeError {
+    #[error("size is empty")]
+    Empty,
+    #[error("invalid size number: {0}")]
+    InvalidNumber(String),
+    #[error("unknown size unit '{0}' (expected B, KB, MB, GB, KiB, MiB or GiB)")]
+    UnknownUnit(String),
+    #[error("size must be greater than zero")]
+    Zero,
+}
+
+/// An exact file size in bytes, used as the compression target.
+///
+/// Parses strings such as `8mb`, `24.5 MB`, `10MiB`, `750KB` or `1048576`
+/// (plain numbers are bytes). Decimal units are powers of 1000 and binary
+/// units powers of 1024, so `8MB` is 8,000,000 bytes and `8MiB` is 8,388,608.
+/// The [`TargetSize`] presets convert into decimal sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct FileSize(u64);
+
+impl FileSize {
+    pub const fn from_bytes(bytes: u64) -> Self {
+        Self(bytes)
+    }
+
+    /// Size in decimal megabytes (1 MB = 1,000,000 bytes)
+    pub fn from_mb(mb: f64) -> Self {
+        Self::from_unit(mb, SizeUnit::Megabytes)
+    }
+
+    /// Size in binary mebibytes (1 MiB = 1,048,576 bytes)
+    pub fn from_mib(mib: f64) -> Self {
+        Self::from_unit(mib, SizeUnit::Mebibytes)
+    }
+
+    pub fn from_unit(value: f64, unit: SizeUnit) -> Self {
+        Self((value.max(0.0) * unit.bytes() as f64).round() as u64)
+    }
+
+    pub const fn as_bytes(&self) -> u64 {
+        self.0
+    }
+
+    pub fn as_bits(&self) -> f64 {
+        self.0 as f64 * 8.0
+    }
+
+    pub fn as_unit(&self, unit: SizeUnit) -> f64 {
+        self.0 as f64 / unit.bytes() as f64
+    }
+
+    /// Size in decimal megabytes (1 MB = 1,000,000 bytes)
+    pub fn as_mb(&self) -> f64 {
+        self.as_unit(SizeUnit::Megabytes)
+    }
+
+    /// Size in binary mebibytes (1 MiB = 1,048,576 bytes)
+    pub fn as_mib(&self) -> f64 {
+        self.as_unit(SizeUnit::Mebibytes)
+    }
+
+    /// The preset this size corresponds to exactly, if any
+    pub fn preset(&self) -> Option<TargetSize> {
+        TargetSize::ALL.iter().copied().find(|preset| FileSize::from(*preset) == *self)
+    }
+
+    /// Largest unit of the same family (decimal or binary) that keeps the
+    /// displayed value at or above one.
+    fn display_unit(&self) -> SizeUnit {
+        let binary = self.0 >= KIB && self.0.is_multiple_of(KIB) && !self.0.is_multiple_of(KB);
+        let units = if binary {
+            [SizeUnit::Gibibytes, SizeUnit::Mebibytes, SizeUnit::Kibibytes]
+        } else {
+            [SizeUnit::Gigabytes, SizeUnit::Megabytes, SizeUnit::Kilobytes]
+        };
+
+        units.into_iter()
+            .find(|unit| self.0 >= unit.bytes())
+            .unwrap_or(SizeUnit::Bytes)
+    }
+}
+
+impl From<TargetSize> for FileSize {
+    fn from(preset: TargetSize) -> Self {
+        FileSize::from_mb(preset.as_mb() as f64)
+    }
+}
+
+impl Default for FileSize {
+    fn default() -> Self {
+        TargetSize::Size10MB.into()
+    }
+}
+
+impl fmt::Display for FileSize {
+    /// Formats with up to two decimals, e.g. `8 MB`, `24.5 MB`, `10 MiB`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = self.display_unit();
+        let value = format!("{:.2}", self.as_unit(unit));
+        let value = value.trim_end_matches('0').trim_end_matches('.');
+        write!(f, "{} {}", value, unit.suffix())
+    }
+}
+
+impl FromStr for FileSize {
+    type Err = ParseFileSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseFileSizeError::Empty);
+        }
+
+        let split = s.find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, suffix) = s.split_at(split);
+
+        let value: f64 = number.parse()
+            .map_err(|_| ParseFileSizeError::InvalidNumber(number.to_string()))?;
+        let unit = SizeUnit::from_suffix(suffix.trim())
+            .ok_or_else(|| ParseFileSizeError::UnknownUnit(suffix.trim().to_string()))?;
+
+        let size = FileSize::from_unit(value, unit);
+        if size.0 == 0 {
+            return Err(ParseFileSizeError::Zero);
+        }
+
+        Ok(size)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileSize {
+    /// Accepts either a byte count or a string such as `"8MB"`.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(FileSize(bytes)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimal_and_binary_units() {
+        assert_eq!("8mb".parse::<FileSize>().unwrap().as_bytes(), 8_000_000);
+        assert_eq!("8MiB".parse::<FileSize>().unwrap().as_bytes(), 8 * 1024 * 1024);
+        assert_eq!("24.5 MB".parse::<FileSize>().unwrap().as_bytes(), 24_500_000);
+        assert_eq!("750kb".parse::<FileSize>().unwrap().as_bytes(), 750_000);
+        assert_eq!("1gb".parse::<FileSize>().unwrap().as_bytes(), 1_000_000_000);
+        assert_eq!("1048576".parse::<FileSize>().unwrap().as_bytes(), 1_048_576);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<FileSize>(), Err(ParseFileSizeError::Empty));
+        assert_eq!("0mb".parse::<FileSize>(), Err(ParseFileSizeError::Zero));
+        assert!(matches!("mb".parse::<FileSize>(), Err(ParseFileSizeError::InvalidNumber(_))));
+        assert!(matches!("10 parsecs".parse::<FileSize>(), Err(ParseFileSizeError::UnknownUnit(_))));
+        assert!(matches!("-5mb".parse::<FileSize>(), Err(ParseFileSizeError::InvalidNumber(_))));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for text in ["8 MB", "24.5 MB", "10 MiB", "1 GB", "512 B"] {
+            let size: FileSize = text.parse().unwrap();
+            assert_eq!(size.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn test_presets_are_decimal() {
+        assert_eq!(FileSize::from(TargetSize::Size10MB).as_bytes(), 10_000_000);
+        assert_eq!(FileSize::from_mb(50.0).preset(), Some(TargetSize::Size50MB));
+        assert_eq!(FileSize::from_mb(8.0).preset(), None);
+    }
+
+    #[test]
+    fn test_deserialize_number_or_string() {
+        let from_text: FileSize = serde_json::from_str("\"25MB\"").unwrap();
+        let from_bytes: FileSize = serde_json::from_str("25000000").unwrap();
+        assert_eq!(from_text, from_bytes);
+    }
+}
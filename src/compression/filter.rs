@@ -0,0 +1,320 @@
+//! Typed video filter chains.
+//!
+//! A [`FilterChain`] lists [`VideoFilter`]s in the order FFmpeg runs them and
+//! renders them for the [`FramePipeline`] the decoded frames live in. On the
+//! CPU every filter runs as written. On a GPU pipeline the filters the device
+//! has its own version of run there; the first one it lacks downloads the
+//! frames, and the chain uploads them again at the end so the hardware
+//! encoder still gets GPU frames.
+
+use serde::Serialize;
+use std::fmt;
+
+use super::{CropRect, Rotation};
+
+/// Where decoded frames are kept between the decoder and the encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub enum FramePipeline {
+    /// System memory, as after software decoding or a download
+    #[default]
+    Software,
+    Cuda,
+    Vaapi,
+    Qsv,
+    VideoToolbox,
+}
+
+impl FramePipeline {
+    pub fn is_hardware(&self) -> bool {
+        !matches!(self, FramePipeline::Software)
+    }
+
+    /// Filter that moves system-memory frames onto the device given with
+    /// `-filter_hw_device`
+    fn upload(&self) -> &'static str {
+        match self {
+            // VAAPI and QSV surfaces only take NV12 uploads
+            FramePipeline::Vaapi | FramePipeline::Qsv => "format=nv12,hwupload",
+            FramePipeline::Software | FramePipeline::Cuda | FramePipeline::VideoToolbox => "hwupload",
+        }
+    }
+}
+
+/// How [`VideoFilter::Scale`] treats the source aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Exactly `width` x `height`
+    Exact,
+    /// As large as fits inside `width` x `height`, keeping the aspect ratio
+    Fit,
+}
+
+/// Deinterlacing filter, one output frame per input frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deinterlacer {
+    Yadif,
+    /// Slower than yadif, fewer artifacts on motion
+    Bwdif,
+}
+
+/// One step of a [`FilterChain`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoFilter {
+    /// Resize, with both sides rounded to even numbers as 4:2:0 encoders require
+    Scale { width: u32, height: u32, mode: ScaleMode },
+    /// Constant frame rate, dropping or repeating frames
+    Fps(f32),
+    Crop(CropRect),
+    /// Center the frame on a `width` x `height` black canvas with square pixels
+    Pad { width: u32, height: u32 },
+    Transpose(Rotation),
+    Deinterlace(Deinterlacer),
+    /// hqdn3d with the given luma spatial strength; FFmpeg derives the rest
+    Denoise(f32),
+    /// Convert to a pixel format, e.g. `yuv420p`
+    Format(String),
+}
+
+impl VideoFilter {
+    /// Scale into `width` x `height` without distorting the picture
+    pub fn scale_to_fit(width: u32, height: u32) -> Self {
+        VideoFilter::Scale { width, height, mode: ScaleMode::Fit }
+    }
+
+    /// The filter as FFmpeg runs it on the CPU; `None` when it does nothing
+    fn software(&self) -> Option<String> {
+        let rendered = match self {
+            VideoFilter::Scale { width, height, mode: ScaleMode::Exact } => {
+                format!("scale={}:{}", even(*width), even(*height))
+            }
+            VideoFilter::Scale { width, height, mode: ScaleMode::Fit } => format!(
+                "scale={}:{}:force_original_aspect_ratio=decrease:force_divisible_by=2", even(*width), even(*height),
+            ),
+            VideoFilter::Fps(fps) => format!("fps={}", fps),
+            VideoFilter::Crop(crop) => {
+                let (width, height) = crop.output_size();
+                format!("crop={}:{}:{}:{}", width, height, crop.x, crop.y)
+            }
+            VideoFilter::Pad { width, height } => {
+                format!("pad={}:{}:(ow-iw)/2:(oh-ih)/2,setsar=1", even(*width), even(*height))
+            }
+            VideoFilter::Transpose(rotation) => match rotation {
+                Rotation::None => return None,
+                Rotation::Clockwise90 => "transpose=clock".to_string(),
+                Rotation::Rotate180 => "hflip,vflip".to_string(),
+                Rotation::Clockwise270 => "transpose=cclock".to_string(),
+            },
+            VideoFilter::Deinterlace(Deinterlacer::Yadif) => "yadif".to_string(),
+            VideoFilter::Deinterlace(Deinterlacer::Bwdif) => "bwdif".to_string(),
+            VideoFilter::Denoise(strength) => format!("hqdn3d={}", strength),
+            VideoFilter::Format(pixel_format) => format!("format={}", pixel_format),
+        };
+        Some(rendered)
+    }
+
+    /// The device's own version of the filter, if it has one
+    fn hardware(&self, pipeline: FramePipeline) -> Option<String> {
+        use FramePipeline::*;
+
+        let rendered = match (self, pipeline) {
+            (VideoFilter::Scale { width, height, mode }, Cuda | Vaapi | Qsv | VideoToolbox) => {
+                let name = match pipeline {
+                    Cuda => "scale_cuda",
+                    Vaapi => "scale_vaapi",
+                    Qsv => "scale_qsv",
+                    _ => "scale_vt",
+                };
+                let fit = match mode {
+                    ScaleMode::Exact => "",
+                    // Only CUDA and VAAPI scalers can keep the aspect ratio themselves
+                    ScaleMode::Fit if matches!(pipeline, Cuda | Vaapi) => ":force_original_aspect_ratio=decrease:force_divisible_by=2",
+                    ScaleMode::Fit => return None,
+                };
+                format!("{}=w={}:h={}{}", name, even(*width), even(*height), fit)
+            }
+            // Dropping and repeating frames never touches their contents
+            (VideoFilter::Fps(_), _) => return self.software(),
+            (VideoFilter::Transpose(Rotation::None), _) => return None,
+            (VideoFilter::Transpose(rotation), Vaapi | VideoToolbox) => {
+                let name = if pipeline == Vaapi { "transpose_vaapi" } else { "transpose_vt" };
+                let dir = match rotation {
+                    Rotation::Clockwise90 => "clock",
+                    Rotation::Rotate180 => "reversal",
+                    _ => "cclock",
+                };
+                format!("{}=dir={}", name, dir)
+            }
+            (VideoFilter::Deinterlace(Deinterlacer::Yadif), Cuda) => "yadif_cuda".to_string(),
+            (VideoFilter::Deinterlace(Deinterlacer::Bwdif), Cuda) => "bwdif_cuda".to_string(),
+            (VideoFilter::Deinterlace(_), Vaapi) => "deinterlace_vaapi".to_string(),
+            (VideoFilter::Deinterlace(_), Qsv) => "vpp_qsv=deinterlace=2".to_string(),
+            _ => return None,
+        };
+        Some(rendered)
+    }
+}
+
+/// Round down to an even number, at least 2
+fn even(value: u32) -> u32 {
+    (value & !1).max(2)
+}
+
+/// Video filters in the order they run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilterChain {
+    filters: Vec<VideoFilter>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, filter: VideoFilter) -> &mut Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn filters(&self) -> &[VideoFilter] {
+        &self.filters
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// FFmpeg filter strings for frames in `pipeline`, in order, ready for
+    /// [`FfmpegInvocation::filter`](super::FfmpegInvocation::filter)
+    pub fn render(&self, pipeline: FramePipeline) -> Vec<String> {
+        let mut rendered = Vec::new();
+        let mut on_device = pipeline.is_hardware();
+
+        for filter in &self.filters {
+            if on_device {
+                if let Some(hardware) = filter.hardware(pipeline) {
+                    rendered.push(hardware);
+                    continue;
+                }
+                // Nothing to do, so no reason to leave the device
+                let Some(software) = filter.software() else { continue };
+                rendered.push("hwdownload,format=nv12".to_string());
+                rendered.push(software);
+                on_device = false;
+            } else {
+                rendered.extend(filter.software());
+            }
+        }
+
+        if pipeline.is_hardware() && !on_device {
+            rendered.push(pipeline.upload().to_string());
+        }
+        rendered
+    }
+}
+
+impl FromIterator<VideoFilter> for FilterChain {
+    fn from_iter<I: IntoIterator<Item = VideoFilter>>(iter: I) -> Self {
+        Self { filters: iter.into_iter().collect() }
+    }
+}
+
+impl Extend<VideoFilter> for FilterChain {
+    fn extend<I: IntoIterator<Item = VideoFilter>>(&mut self, iter: I) {
+        self.filters.extend(iter);
+    }
+}
+
+impl fmt::Display for FilterChain {
+    /// The chain as it runs on the CPU, e.g. `crop=1080:1080:420:0,scale=720:720`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(FramePipeline::Software).join(","))
+    }
+}
+
+/// Largest even frame of the same shape as `width` x `height` that fits
+/// within the limits; never larger than the source.
+pub fn fit_within(width: u32, height: u32, max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let scaled = |side: u32, to: u32, from: u32| (side as u64 * to as u64 / from.max(1) as u64) as u32;
+
+    let mut size = (width, height);
+    if let Some(max) = max_width.filter(|&max| max < size.0) {
+        size = (max, scaled(height, max, width));
+    }
+    if let Some(max) = max_height.filter(|&max| max < size.1) {
+        size = (scaled(width, max, height), max);
+    }
+
+    if size == (width, height) {
+        return size;
+    }
+    (even(size.0), even(size.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain() -> FilterChain {
+        [
+            VideoFilter::Deinterlace(Deinterlacer::Bwdif),
+            VideoFilter::Crop(CropRect { width: 1081, height: 1080, x: 420, y: 0 }),
+            VideoFilter::Scale { width: 720, height: 720, mode: ScaleMode::Exact },
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    #[test]
+    fn test_software_rendering() {
+        assert_eq!(chain().to_string(), "bwdif,crop=1080:1080:420:0,scale=720:720");
+
+        let mut fitted = FilterChain::new();
+        fitted.push(VideoFilter::scale_to_fit(1080, 1921))
+            .push(VideoFilter::Pad { width: 1080, height: 1920 })
+            .push(VideoFilter::Transpose(Rotation::None))
+            .push(VideoFilter::Denoise(4.0))
+            .push(VideoFilter::Format("yuv420p".to_string()));
+        assert_eq!(
+            fitted.render(FramePipeline::Software),
+            [
+                "scale=1080:1920:force_original_aspect_ratio=decrease:force_divisible_by=2",
+                "pad=1080:1920:(ow-iw)/2:(oh-ih)/2,setsar=1",
+                "hqdn3d=4",
+                "format=yuv420p",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hardware_rendering_downloads_only_when_needed() {
+        // Deinterlacing stays on the GPU, cropping does not
+        assert_eq!(
+            chain().render(FramePipeline::Cuda),
+            ["bwdif_cuda", "hwdownload,format=nv12", "crop=1080:1080:420:0", "scale=720:720", "hwupload"]
+        );
+
+        let mut gpu_only = FilterChain::new();
+        gpu_only.push(VideoFilter::Scale { width: 1280, height: 720, mode: ScaleMode::Exact })
+            .push(VideoFilter::Transpose(Rotation::Clockwise90))
+            .push(VideoFilter::Fps(30.0));
+        assert_eq!(
+            gpu_only.render(FramePipeline::Vaapi),
+            ["scale_vaapi=w=1280:h=720", "transpose_vaapi=dir=clock", "fps=30"]
+        );
+        assert_eq!(
+            gpu_only.render(FramePipeline::Qsv),
+            ["scale_qsv=w=1280:h=720", "hwdownload,format=nv12", "transpose=clock", "fps=30", "format=nv12,hwupload"]
+        );
+        assert!(FilterChain::new().render(FramePipeline::Cuda).is_empty());
+    }
+
+    #[test]
+    fn test_fit_within() {
+        assert_eq!(fit_within(1920, 1080, None, Some(720)), (1280, 720));
+        assert_eq!(fit_within(1080, 1920, Some(720), Some(720)), (404, 720));
+        assert_eq!(fit_within(1920, 1080, Some(1280), Some(1280)), (1280, 720));
+        // Never upscaled
+        assert_eq!(fit_within(640, 360, Some(1280), None), (640, 360));
+        assert_eq!(fit_within(1920, 1080, None, None), (1920, 1080));
+    }
+}
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
-use anyhow::{Result, anyhow};
-use log::{debug, info, warn};
+use anyhow::Result;
+use log::{debug, info};
 use std::process::Command;
 
-use super::HardwareEncoder;
+use super::backend::{flag, size_target_args, EncoderBackend, EncoderCodec};
+use super::{HardwareEncoder, HardwarePreset, HardwareQuality};
 
 pub async fn detect_amd_vce() -> Result<Vec<HardwareEncoder>> {
     debug!("Starting AMD VCE detection");
@@ -163,31 +164,35 @@ async fn check_hevc_support() -> bool {
     true
 }
 
-/// Get optimal AMD VCE settings for encoding
-pub fn get_optimal_amd_settings(encoder: &HardwareEncoder) -> Vec<(&'static str, String)> {
-    let mut settings = Vec::new();
-    
-    match encoder {
-        HardwareEncoder::AmfH264 => {
-            settings.push(("c:v", "h264_amf".to_string()));
-            settings.push(("quality", "speed".to_string())); // Balance quality and speed
-            settings.push(("rc", "vbr_peak".to_string())); // Variable bitrate with peak
-            settings.push(("qmin", "18".to_string()));
-            settings.push(("qmax", "30".to_string()));
-        },
-        HardwareEncoder::AmfH265 => {
-            settings.push(("c:v", "hevc_amf".to_string()));
-            settings.push(("quality", "speed".to_string()));
-            settings.push(("rc", "vbr_peak".to_string()));
-            settings.push(("qmin", "20".to_string()));
-            settings.push(("qmax", "32".to_string()));
-        },
-        _ => {
-            warn!("get_optimal_amd_settings called with non-AMF encoder: {:?}", encoder);
+/// AMD AMF (VCE)
+pub struct AmfBackend;
+
+impl EncoderBackend for AmfBackend {
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::H264 => "h264_amf",
+            EncoderCodec::Hevc => "hevc_amf",
+            EncoderCodec::Av1 => "av1_amf",
         }
     }
     
-    settings
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String> {
+        flag("-quality", preset.amf_quality()).to_vec()
+    }
+    
+    fn rate_control_args(&self, mode: HardwareQuality, codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        if mode.uses_quality_level() {
+            let mut args = [flag("-rc", "cqp"), flag("-qp_i", quality), flag("-qp_p", quality)].concat();
+            // hevc_amf and av1_amf have no B-frame QP
+            if codec == EncoderCodec::H264 {
+                args.extend(flag("-qp_b", quality));
+            }
+            return args;
+        }
+        
+        let rc = if mode == HardwareQuality::Variable { "vbr_peak" } else { "cbr" };
+        [size_target_args(mode, bitrate_kbps), flag("-rc", rc).to_vec()].concat()
+    }
 }
 
 /// Check if the system supports AMD hardware acceleration
@@ -245,7 +250,7 @@ async fn get_amd_gpu_info_linux() -> Result<Vec<AmdGpuInfo>> {
     // This is a simplified implementation
     // In practice, you might want to use proper AMD GPU management libraries
     
-    if let Ok(output) = Command::new("lspci").args(&["-v"]).output() {
+    if let Ok(output) = Command::new("lspci").args(["-v"]).output() {
         let output_str = String::from_utf8_lossy(&output.stdout);
         
         for section in output_str.split("
@@ -0,0 +1,133 @@
+//! How each encoder family is driven from FFmpeg's command line.
+//!
+//! The engine decides what an encode should be (output codec, preset, rate
+//! control mode, platform profile) and asks the encoder's
+//! [`EncoderBackend`] for the arguments that say so to that encoder. Adding
+//! an encoder family means implementing the trait and returning it from
+//! [`HardwareEncoder::backend`](super::HardwareEncoder::backend).
+
+use crate::compression::{FramePipeline, VideoCodec};
+
+use super::{HardwarePreset, HardwareQuality};
+
+/// Video format an encode produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderCodec {
+    H264,
+    Hevc,
+    Av1,
+}
+
+impl From<VideoCodec> for EncoderCodec {
+    fn from(codec: VideoCodec) -> Self {
+        match codec {
+            VideoCodec::H264 => EncoderCodec::H264,
+            VideoCodec::Hevc => EncoderCodec::Hevc,
+        }
+    }
+}
+
+/// One encoder family's FFmpeg arguments
+pub trait EncoderBackend: Send + Sync {
+    /// FFmpeg encoder producing `codec`
+    fn encoder(&self, codec: EncoderCodec) -> &'static str;
+
+    /// Device whose frames the encoder reads when the whole pipeline runs
+    /// on it; `None` for encoders that read system memory only
+    fn device(&self) -> Option<FramePipeline> {
+        None
+    }
+
+    /// Arguments picking GPU `device_id` when no device frames already do
+    fn device_args(&self, _device_id: Option<u32>) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The speed/quality trade-off `preset` asks for
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String>;
+
+    /// Rate control for `mode` when encoding `codec`: `bitrate_kbps` is the
+    /// planned average, and `quality` the constant-quality level on a
+    /// CRF-style 0-51 scale
+    fn rate_control_args(&self, mode: HardwareQuality, codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String>;
+
+    /// A platform's profile and level, or the encoder's defaults when the
+    /// platform names neither
+    fn profile_args(&self, profile: Option<&str>, level: Option<&str>) -> Vec<String> {
+        profile_level_args(profile, level)
+    }
+
+    /// Pixel format the encoder takes from system memory
+    fn pixel_format(&self) -> &'static str {
+        "yuv420p"
+    }
+
+    /// Whether an encode of `codec` in `mode` runs an analysis pass first
+    fn two_pass(&self, _mode: HardwareQuality, _codec: EncoderCodec) -> bool {
+        false
+    }
+}
+
+/// `flag value`, as arguments
+pub fn flag(flag: &str, value: impl ToString) -> [String; 2] {
+    [flag.to_string(), value.to_string()]
+}
+
+/// `-profile:v` and `-level:v` for those given
+pub fn profile_level_args(profile: Option<&str>, level: Option<&str>) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(profile) = profile {
+        args.extend(flag("-profile:v", profile));
+    }
+    if let Some(level) = level {
+        args.extend(flag("-level:v", level));
+    }
+    args
+}
+
+/// Average bitrate and VBV buffer for a size target. CBR-style modes cap
+/// the rate at the average; `Variable` lets peaks reach twice that.
+pub fn size_target_args(mode: HardwareQuality, bitrate_kbps: u32) -> Vec<String> {
+    let peak = |factor: u32| format!("{}k", bitrate_kbps * factor);
+    let (maxrate, bufsize) = match mode {
+        HardwareQuality::Variable => (peak(2), peak(4)),
+        _ => (peak(1), peak(2)),
+    };
+    [flag("-b:v", peak(1)), flag("-maxrate", maxrate), flag("-bufsize", bufsize)].concat()
+}
+
+/// VBV cap keeping a constant-quality encode at or under `bitrate_kbps`
+pub fn quality_cap_args(bitrate_kbps: u32) -> Vec<String> {
+    [flag("-maxrate", format!("{}k", bitrate_kbps)), flag("-bufsize", format!("{}k", bitrate_kbps * 2))].concat()
+}
+
+/// x264 and x265 on the CPU
+pub struct SoftwareBackend;
+
+impl EncoderBackend for SoftwareBackend {
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::H264 => "libx264",
+            EncoderCodec::Hevc => "libx265",
+            EncoderCodec::Av1 => "libsvtav1",
+        }
+    }
+
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String> {
+        flag("-preset", preset.software_preset()).to_vec()
+    }
+
+    fn rate_control_args(&self, mode: HardwareQuality, _codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        match mode {
+            HardwareQuality::Constant => flag("-crf", quality).to_vec(),
+            HardwareQuality::Constrained => [flag("-crf", quality).to_vec(), quality_cap_args(bitrate_kbps)].concat(),
+            _ => size_target_args(mode, bitrate_kbps),
+        }
+    }
+
+    // Constant quality is single-pass by nature; libx265 takes its passes
+    // through -x265-params
+    fn two_pass(&self, mode: HardwareQuality, codec: EncoderCodec) -> bool {
+        !mode.uses_quality_level() && codec == EncoderCodec::H264
+    }
+}
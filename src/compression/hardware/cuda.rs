@@ -5,7 +5,9 @@ use log::{debug, info, warn};
 use sysinfo::System;
 use std::process::Command;
 
-use super::{HardwareEncoder, CudaDevice};
+use crate::compression::FramePipeline;
+use super::backend::{flag, quality_cap_args, size_target_args, EncoderBackend, EncoderCodec};
+use super::{HardwareEncoder, HardwarePreset, HardwareQuality, CudaDevice};
 
 pub struct CudaInfo {
     pub devices: Vec<CudaDevice>,
@@ -53,7 +55,7 @@ async fn query_cuda_devices_nvidia_smi() -> Result<Vec<CudaDevice>> {
     
     // Try to run nvidia-smi to get GPU information
     let output = Command::new("nvidia-smi")
-        .args(&[
+        .args([
             "--query-gpu=index,name,compute_cap,memory.total,encoder.max_sessions",
             "--format=csv,noheader,nounits"
         ])
@@ -214,67 +216,46 @@ pub fn device_supports_encoder(device: &CudaDevice, encoder: &HardwareEncoder) -
     }
 }
 
-/// Get optimal NVENC settings for a given device and encoder
-pub fn get_optimal_nvenc_settings(device: &CudaDevice, encoder: &HardwareEncoder) -> Vec<(&'static str, String)> {
-    let mut settings = Vec::new();
+/// NVIDIA NVENC
+pub struct NvencBackend;
+
+impl EncoderBackend for NvencBackend {
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::H264 => "h264_nvenc",
+            EncoderCodec::Hevc => "hevc_nvenc",
+            EncoderCodec::Av1 => "av1_nvenc",
+        }
+    }
     
-    // Basic NVENC settings
-    settings.push(("hwaccel", "cuda".to_string()));
-    settings.push(("hwaccel_output_format", "cuda".to_string()));
-    settings.push(("hwaccel_device", device.id.to_string()));
+    fn device(&self) -> Option<FramePipeline> {
+        Some(FramePipeline::Cuda)
+    }
     
-    // Encoder-specific settings
-    match encoder {
-        HardwareEncoder::NvencH264 => {
-            settings.push(("c:v", "h264_nvenc".to_string()));
-            
-            // Optimize based on GPU generation
-            if device.compute_capability >= (8, 0) {
-                // RTX 30xx/40xx series optimizations
-                settings.push(("preset", "p4".to_string())); // Balanced preset
-                settings.push(("tune", "hq".to_string())); // High quality tuning
-                settings.push(("rc", "vbr".to_string())); // Variable bitrate
-                settings.push(("multipass", "fullres".to_string())); // Two-pass encoding
-            } else if device.compute_capability >= (7, 0) {
-                // RTX 20xx series
-                settings.push(("preset", "medium".to_string()));
-                settings.push(("tune", "hq".to_string()));
-                settings.push(("rc", "vbr".to_string()));
-            } else {
-                // Older GPUs - basic settings
-                settings.push(("preset", "medium".to_string()));
-                settings.push(("rc", "cbr".to_string()));
-            }
-        },
-        HardwareEncoder::NvencH265 => {
-            settings.push(("c:v", "hevc_nvenc".to_string()));
-            
-            // Similar optimizations for HEVC
-            if device.compute_capability >= (8, 0) {
-                settings.push(("preset", "p4".to_string()));
-                settings.push(("tune", "hq".to_string()));
-                settings.push(("rc", "vbr".to_string()));
-                settings.push(("multipass", "fullres".to_string()));
-            } else {
-                settings.push(("preset", "medium".to_string()));
-                settings.push(("tune", "hq".to_string()));
-                settings.push(("rc", "vbr".to_string()));
-            }
-        },
-        HardwareEncoder::NvencAV1 => {
-            settings.push(("c:v", "av1_nvenc".to_string()));
-            // AV1 NVENC is only on newest GPUs, use high-quality settings
-            settings.push(("preset", "p4".to_string()));
-            settings.push(("tune", "hq".to_string()));
-            settings.push(("rc", "vbr".to_string()));
-        },
-        _ => {
-            // Non-NVENC encoder
-            warn!("get_optimal_nvenc_settings called with non-NVENC encoder: {:?}", encoder);
-        }
+    // CUDA frames already carry their GPU
+    fn device_args(&self, device_id: Option<u32>) -> Vec<String> {
+        device_id.map(|id| flag("-gpu", id).to_vec()).unwrap_or_default()
+    }
+    
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String> {
+        flag("-preset", preset.nvenc_preset()).to_vec()
     }
     
-    settings
+    fn rate_control_args(&self, mode: HardwareQuality, _codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        let mut args = if mode.uses_quality_level() {
+            [flag("-rc", mode.nvenc_rc_mode()), flag("-cq", quality.max(1)), flag("-b:v", 0)].concat()
+        } else {
+            [size_target_args(mode, bitrate_kbps), flag("-rc", mode.nvenc_rc_mode()).to_vec()].concat()
+        };
+        
+        match mode {
+            // Use CBR for precise size control
+            HardwareQuality::Auto => args.extend(flag("-cbr", true)),
+            HardwareQuality::Constrained => args.extend(quality_cap_args(bitrate_kbps)),
+            _ => {}
+        }
+        args
+    }
 }
 
 fn check_cuda_in_ffmpeg() -> bool {
@@ -77,11 +77,11 @@ pub async fn detect_hardware_capabilities() -> Result<HardwareCapabilities> {
     // Platform-specific detection
     #[cfg(target_os = "linux")]
     {
-        if ffmpeg_hwaccels.iter().any(|h| h.name == "vaapi") {
-            if super::platform::detect_vaapi_support().await {
-                capabilities.available_encoders.push(HardwareEncoder::Vaapi);
-                info!("VAAPI support detected and added");
-            }
+        if ffmpeg_hwaccels.iter().any(|h| h.name == "vaapi")
+            && super::platform::detect_vaapi_support().await
+        {
+            capabilities.available_encoders.push(HardwareEncoder::Vaapi);
+            info!("VAAPI support detected and added");
         }
     }
     
@@ -216,7 +216,7 @@ pub async fn quick_hardware_detection() -> Vec<String> {
     for accel in hwaccels {
         match accel.name.as_str() {
             "cuda" => {
-                if let Ok(_) = super::cuda::detect_cuda_capabilities().await {
+                if super::cuda::detect_cuda_capabilities().await.is_ok() {
                     available.push("NVIDIA NVENC (CUDA)".to_string());
                 }
             },
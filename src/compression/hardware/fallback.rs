@@ -44,6 +44,26 @@ impl FallbackSystem {
         HardwareEncoder::Software
     }
     
+    /// Encoder the engine would fall back to once every encoder in `failed`
+    /// had failed in turn, without recording anything
+    pub fn next_after(&self, failed: &[HardwareEncoder]) -> HardwareEncoder {
+        let mut failure_counts = self.failure_counts.clone();
+        for encoder in failed {
+            *failure_counts.entry(*encoder).or_insert(0) += 1;
+        }
+        let usable = |encoder: &HardwareEncoder| {
+            failure_counts.get(encoder).unwrap_or(&0) < &self.max_failures
+        };
+        
+        match failed.last() {
+            Some(last) if usable(last) => *last,
+            _ => self.fallback_chain.iter()
+                .find(|encoder| usable(encoder))
+                .copied()
+                .unwrap_or(HardwareEncoder::Software),
+        }
+    }
+    
     /// Record a failure for an encoder
     pub fn record_failure(&mut self, encoder: &HardwareEncoder, error: &anyhow::Error) {
         let count = self.failure_counts.entry(*encoder).or_insert(0);
@@ -77,24 +97,24 @@ impl FallbackSystem {
         let error_lower = error.to_lowercase();
         
         // CUDA/NVENC specific errors
-        if matches!(encoder, HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1) {
-            if error_lower.contains("cuda") || error_lower.contains("nvenc") {
-                return self.find_alternative_vendor_encoder(encoder);
-            }
+        if matches!(encoder, HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1)
+            && (error_lower.contains("cuda") || error_lower.contains("nvenc"))
+        {
+            return self.find_alternative_vendor_encoder(encoder);
         }
         
         // AMD specific errors
-        if matches!(encoder, HardwareEncoder::AmfH264 | HardwareEncoder::AmfH265) {
-            if error_lower.contains("amf") || error_lower.contains("amd") {
-                return self.find_alternative_vendor_encoder(encoder);
-            }
+        if matches!(encoder, HardwareEncoder::AmfH264 | HardwareEncoder::AmfH265)
+            && (error_lower.contains("amf") || error_lower.contains("amd"))
+        {
+            return self.find_alternative_vendor_encoder(encoder);
         }
         
         // Intel specific errors
-        if matches!(encoder, HardwareEncoder::QsvH264 | HardwareEncoder::QsvH265 | HardwareEncoder::QsvAV1) {
-            if error_lower.contains("qsv") || error_lower.contains("intel") {
-                return self.find_alternative_vendor_encoder(encoder);
-            }
+        if matches!(encoder, HardwareEncoder::QsvH264 | HardwareEncoder::QsvH265 | HardwareEncoder::QsvAV1)
+            && (error_lower.contains("qsv") || error_lower.contains("intel"))
+        {
+            return self.find_alternative_vendor_encoder(encoder);
         }
         
         // Memory errors - try software fallback
@@ -143,11 +163,12 @@ impl FallbackSystem {
         
         // Find the first alternative that's usable and different from the failed encoder
         for alternative in alternatives {
-            if alternative != *failed_encoder && self.is_encoder_usable(&alternative) {
-                if self.fallback_chain.contains(&alternative) {
-                    debug!("Found alternative encoder: {:?}", alternative);
-                    return Some(alternative);
-                }
+            if alternative != *failed_encoder
+                && self.is_encoder_usable(&alternative)
+                && self.fallback_chain.contains(&alternative)
+            {
+                debug!("Found alternative encoder: {:?}", alternative);
+                return Some(alternative);
             }
         }
         
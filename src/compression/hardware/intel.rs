@@ -1,9 +1,11 @@
 #![allow(dead_code)]
-use anyhow::{Result, anyhow};
-use log::{debug, info, warn};
+use anyhow::Result;
+use log::{debug, info};
 use std::process::Command;
 
-use super::HardwareEncoder;
+use crate::compression::FramePipeline;
+use super::backend::{flag, size_target_args, EncoderBackend, EncoderCodec};
+use super::{HardwareEncoder, HardwarePreset, HardwareQuality};
 
 pub async fn detect_intel_quicksync() -> Result<Vec<HardwareEncoder>> {
     debug!("Starting Intel QuickSync detection");
@@ -180,36 +182,39 @@ async fn check_av1_support() -> bool {
     false
 }
 
-/// Get optimal Intel QuickSync settings for encoding
-pub fn get_optimal_intel_settings(encoder: &HardwareEncoder) -> Vec<(&'static str, String)> {
-    let mut settings = Vec::new();
-    
-    match encoder {
-        HardwareEncoder::QsvH264 => {
-            settings.push(("c:v", "h264_qsv".to_string()));
-            settings.push(("preset", "medium".to_string()));
-            settings.push(("look_ahead", "1".to_string())); // Enable look-ahead
-            settings.push(("look_ahead_depth", "15".to_string()));
-            settings.push(("global_quality", "23".to_string())); // Similar to CRF
-        },
-        HardwareEncoder::QsvH265 => {
-            settings.push(("c:v", "hevc_qsv".to_string()));
-            settings.push(("preset", "medium".to_string()));
-            settings.push(("look_ahead", "1".to_string()));
-            settings.push(("look_ahead_depth", "15".to_string()));
-            settings.push(("global_quality", "25".to_string())); // HEVC can use slightly higher
-        },
-        HardwareEncoder::QsvAV1 => {
-            settings.push(("c:v", "av1_qsv".to_string()));
-            settings.push(("preset", "medium".to_string()));
-            settings.push(("global_quality", "27".to_string()));
-        },
-        _ => {
-            warn!("get_optimal_intel_settings called with non-QSV encoder: {:?}", encoder);
+/// Intel QuickSync
+pub struct QsvBackend;
+
+impl EncoderBackend for QsvBackend {
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::H264 => "h264_qsv",
+            EncoderCodec::Hevc => "hevc_qsv",
+            EncoderCodec::Av1 => "av1_qsv",
+        }
+    }
+    
+    fn device(&self) -> Option<FramePipeline> {
+        Some(FramePipeline::Qsv)
+    }
+    
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String> {
+        [flag("-preset", preset.qsv_preset()), flag("-look_ahead", 1)].concat()
+    }
+    
+    // ICQ ignores VBV, so a capped quality isn't capped here; the engine
+    // re-encodes by size if the cap is missed
+    fn rate_control_args(&self, mode: HardwareQuality, _codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        if mode.uses_quality_level() {
+            flag("-global_quality", quality.max(1)).to_vec()
+        } else {
+            size_target_args(mode, bitrate_kbps)
         }
     }
     
-    settings
+    fn pixel_format(&self) -> &'static str {
+        "nv12"
+    }
 }
 
 /// Check if the system supports Intel hardware acceleration
@@ -263,7 +268,7 @@ pub struct IntelGpuInfo {
 async fn get_intel_gpu_info_linux() -> Result<Vec<IntelGpuInfo>> {
     let mut gpus = Vec::new();
     
-    if let Ok(output) = Command::new("lspci").args(&["-v"]).output() {
+    if let Ok(output) = Command::new("lspci").args(["-v"]).output() {
         let output_str = String::from_utf8_lossy(&output.stdout);
         
         for section in output_str.split("
@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+pub mod backend;
 pub mod detection;
 pub mod cuda;
 pub mod amd;
@@ -12,6 +13,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use log::{info, debug};
 
+use backend::{EncoderBackend, EncoderCodec, SoftwareBackend};
+
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum HardwareEncoder {
@@ -55,18 +58,28 @@ impl HardwareEncoder {
     }
     
     pub fn codec_name(&self) -> &'static str {
+        self.backend().encoder(self.codec())
+    }
+    
+    /// Video format the encoder produces unless a platform or
+    /// compatibility mode asks for another
+    pub fn codec(&self) -> EncoderCodec {
         match self {
-            HardwareEncoder::NvencH264 => "h264_nvenc",
-            HardwareEncoder::NvencH265 => "hevc_nvenc", 
-            HardwareEncoder::NvencAV1 => "av1_nvenc",
-            HardwareEncoder::AmfH264 => "h264_amf",
-            HardwareEncoder::AmfH265 => "hevc_amf",
-            HardwareEncoder::QsvH264 => "h264_qsv",
-            HardwareEncoder::QsvH265 => "hevc_qsv",
-            HardwareEncoder::QsvAV1 => "av1_qsv",
-            HardwareEncoder::Vaapi => "h264_vaapi",
-            HardwareEncoder::VideoToolbox => "h264_videotoolbox",
-            HardwareEncoder::Software => "libx264",
+            HardwareEncoder::NvencH265 | HardwareEncoder::AmfH265 | HardwareEncoder::QsvH265 => EncoderCodec::Hevc,
+            HardwareEncoder::NvencAV1 | HardwareEncoder::QsvAV1 => EncoderCodec::Av1,
+            _ => EncoderCodec::H264,
+        }
+    }
+    
+    /// How FFmpeg drives this encoder's family
+    pub fn backend(&self) -> &'static dyn EncoderBackend {
+        match self {
+            HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1 => &cuda::NvencBackend,
+            HardwareEncoder::AmfH264 | HardwareEncoder::AmfH265 => &amd::AmfBackend,
+            HardwareEncoder::QsvH264 | HardwareEncoder::QsvH265 | HardwareEncoder::QsvAV1 => &intel::QsvBackend,
+            HardwareEncoder::Vaapi => &platform::VaapiBackend,
+            HardwareEncoder::VideoToolbox => &platform::VideoToolboxBackend,
+            HardwareEncoder::Software => &SoftwareBackend,
         }
     }
     
@@ -84,6 +97,11 @@ impl HardwareEncoder {
     pub fn is_hardware_accelerated(&self) -> bool {
         !matches!(self, HardwareEncoder::Software)
     }
+    
+    /// Runs on an NVENC session, which the driver limits per GPU
+    pub fn is_nvenc(&self) -> bool {
+        matches!(self, HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 | HardwareEncoder::NvencAV1)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -110,6 +128,28 @@ impl HardwarePreset {
         }
     }
     
+    /// AMF's `-quality`, which only knows three steps
+    pub fn amf_quality(&self) -> &'static str {
+        match self {
+            HardwarePreset::UltraFast | HardwarePreset::Faster | HardwarePreset::Fast => "speed",
+            HardwarePreset::Medium => "balanced",
+            HardwarePreset::Slow | HardwarePreset::Slower | HardwarePreset::Highest => "quality",
+        }
+    }
+    
+    /// QuickSync's `-preset`, which has no ultrafast
+    pub fn qsv_preset(&self) -> &'static str {
+        match self {
+            HardwarePreset::UltraFast => "veryfast",
+            HardwarePreset::Faster => "faster",
+            HardwarePreset::Fast => "fast",
+            HardwarePreset::Medium => "medium",
+            HardwarePreset::Slow => "slow",
+            HardwarePreset::Slower => "slower",
+            HardwarePreset::Highest => "veryslow",
+        }
+    }
+    
     pub fn software_preset(&self) -> &'static str {
         match self {
             HardwarePreset::UltraFast => "ultrafast",
@@ -135,26 +175,58 @@ impl HardwarePreset {
     }
 }
 
+/// Rate control mode, i.e. what the encoder is asked to hit.
+///
+/// `Auto` and `Variable` aim for the target size. `Constant` encodes at a
+/// fixed quality level (CRF/CQ/QP depending on the encoder) and ignores the
+/// target size. `Constrained` uses constant quality but never produces a file
+/// larger than the target.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum HardwareQuality {
-    Auto,       // Let hardware decide
+    Auto,       // Size target, CBR-style for predictable sizes
     Constant,   // CRF-like constant quality
-    Variable,   // Variable bitrate 
-    Constrained, // Constrained variable bitrate
+    Variable,   // Size target with variable bitrate peaks
+    Constrained, // Constant quality capped at the target size
 }
 
 impl HardwareQuality {
+    pub const ALL: [HardwareQuality; 4] = [
+        HardwareQuality::Auto,
+        HardwareQuality::Variable,
+        HardwareQuality::Constant,
+        HardwareQuality::Constrained,
+    ];
+    
     pub fn nvenc_rc_mode(&self) -> &'static str {
         match self {
-            HardwareQuality::Auto => "vbr",
-            HardwareQuality::Constant => "constqp",
+            HardwareQuality::Auto => "cbr",
+            HardwareQuality::Constant => "vbr",
             HardwareQuality::Variable => "vbr",
-            HardwareQuality::Constrained => "cbr",
+            HardwareQuality::Constrained => "vbr",
+        }
+    }
+    
+    /// Whether the output must fit the target size
+    pub fn has_size_target(&self) -> bool {
+        !matches!(self, HardwareQuality::Constant)
+    }
+    
+    /// Whether the encoder runs in a constant-quality mode
+    pub fn uses_quality_level(&self) -> bool {
+        matches!(self, HardwareQuality::Constant | HardwareQuality::Constrained)
+    }
+    
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            HardwareQuality::Auto => "Size target",
+            HardwareQuality::Constant => "Constant quality",
+            HardwareQuality::Variable => "Size target (VBR)",
+            HardwareQuality::Constrained => "Quality, capped at size",
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct HardwareCapabilities {
     pub available_encoders: Vec<HardwareEncoder>,
     pub cuda_devices: Vec<CudaDevice>,
@@ -165,7 +237,7 @@ pub struct HardwareCapabilities {
     pub encoder_performance: HashMap<HardwareEncoder, f32>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct CudaDevice {
     pub id: u32,
     pub name: String,
@@ -175,7 +247,7 @@ pub struct CudaDevice {
     pub max_concurrent_sessions: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct OpenCLDevice {
     pub id: u32,
     pub name: String,
@@ -259,7 +331,7 @@ impl HardwareCapabilities {
     }
     
     pub fn speed_improvement(&self, encoder: &HardwareEncoder) -> f32 {
-        self.encoder_performance.get(encoder).copied().unwrap_or_else(|| {
+        self.encoder_performance.get(encoder).copied().unwrap_or({
             match encoder {
                 HardwareEncoder::NvencH264 | HardwareEncoder::NvencH265 => 8.0,
                 HardwareEncoder::NvencAV1 => 6.0,
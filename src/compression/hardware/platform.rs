@@ -3,6 +3,13 @@
 use log::{debug, info};
 use std::path::Path;
 
+use crate::compression::{FramePipeline, MAX_QUALITY_LEVEL};
+use super::backend::{flag, profile_level_args, size_target_args, EncoderBackend, EncoderCodec};
+use super::{HardwarePreset, HardwareQuality};
+
+/// DRM render node VAAPI encodes and decodes run on
+pub const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
 /// Detect VAAPI support on Linux
 #[cfg(target_os = "linux")]
 pub async fn detect_vaapi_support() -> bool {
@@ -26,7 +33,7 @@ pub async fn detect_vaapi_support() -> bool {
     
     // Check if VAAPI devices are available
     let vaapi_devices = [
-        "/dev/dri/renderD128",
+        VAAPI_DEVICE,
         "/dev/dri/renderD129",
         "/dev/dri/card0",
         "/dev/dri/card1",
@@ -150,47 +157,80 @@ pub struct PlatformHwAccel {
     pub platform: String,
 }
 
-/// Get VAAPI-specific settings for Linux
-#[cfg(target_os = "linux")]
-pub fn get_vaapi_settings() -> Vec<(&'static str, String)> {
-    vec![
-        ("hwaccel", "vaapi".to_string()),
-        ("hwaccel_device", "/dev/dri/renderD128".to_string()),
-        ("hwaccel_output_format", "vaapi".to_string()),
-        ("c:v", "h264_vaapi".to_string()),
-        ("profile:v", "main".to_string()),
-        ("level", "4.0".to_string()),
-    ]
-}
+/// VAAPI on Linux
+pub struct VaapiBackend;
 
-/// Get VideoToolbox-specific settings for macOS
-#[cfg(target_os = "macos")]
-pub fn get_videotoolbox_settings() -> Vec<(&'static str, String)> {
-    vec![
-        ("hwaccel", "videotoolbox".to_string()),
-        ("c:v", "h264_videotoolbox".to_string()),
-        ("profile:v", "main".to_string()),
-        ("level", "4.0".to_string()),
-        ("realtime", "true".to_string()), // Enable real-time encoding
-    ]
+impl EncoderBackend for VaapiBackend {
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::H264 => "h264_vaapi",
+            EncoderCodec::Hevc => "hevc_vaapi",
+            EncoderCodec::Av1 => "av1_vaapi",
+        }
+    }
+    
+    fn device(&self) -> Option<FramePipeline> {
+        Some(FramePipeline::Vaapi)
+    }
+    
+    // Drivers disagree on what -compression_level means
+    fn preset_args(&self, _preset: HardwarePreset) -> Vec<String> {
+        Vec::new()
+    }
+    
+    fn rate_control_args(&self, mode: HardwareQuality, _codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        if mode.uses_quality_level() {
+            [flag("-rc_mode", "CQP"), flag("-qp", quality)].concat()
+        } else {
+            size_target_args(mode, bitrate_kbps)
+        }
+    }
+    
+    fn profile_args(&self, profile: Option<&str>, level: Option<&str>) -> Vec<String> {
+        match (profile, level) {
+            (None, None) => profile_level_args(Some("main"), Some("4.0")),
+            _ => profile_level_args(profile, level),
+        }
+    }
 }
 
-/// Get platform-specific optimal settings
-pub fn get_platform_optimal_settings(platform: &str) -> Vec<(&'static str, String)> {
-    match platform.to_lowercase().as_str() {
-        #[cfg(target_os = "linux")]
-        "linux" | "vaapi" => get_vaapi_settings(),
-        
-        #[cfg(target_os = "macos")]
-        "macos" | "videotoolbox" => get_videotoolbox_settings(),
-        
-        #[cfg(target_os = "windows")]
-        "windows" | "dxva" => vec![
-            ("hwaccel", "dxva2".to_string()),
-            ("c:v", "h264".to_string()), // Fallback to software with DXVA decode
-        ],
+/// VideoToolbox on macOS
+pub struct VideoToolboxBackend;
+
+impl EncoderBackend for VideoToolboxBackend {
+    // There is no VideoToolbox AV1 encoder, and no encoder asks for one
+    fn encoder(&self, codec: EncoderCodec) -> &'static str {
+        match codec {
+            EncoderCodec::Hevc => "hevc_videotoolbox",
+            EncoderCodec::H264 | EncoderCodec::Av1 => "h264_videotoolbox",
+        }
+    }
+    
+    fn device(&self) -> Option<FramePipeline> {
+        Some(FramePipeline::VideoToolbox)
+    }
+    
+    // Only the fastest presets trade quality for speed
+    fn preset_args(&self, preset: HardwarePreset) -> Vec<String> {
+        match preset {
+            HardwarePreset::UltraFast | HardwarePreset::Faster => flag("-realtime", true).to_vec(),
+            _ => Vec::new(),
+        }
+    }
+    
+    fn rate_control_args(&self, mode: HardwareQuality, _codec: EncoderCodec, quality: u8, bitrate_kbps: u32) -> Vec<String> {
+        if !mode.uses_quality_level() {
+            return size_target_args(mode, bitrate_kbps);
+        }
         
-        _ => vec![], // No platform-specific settings
+        // VideoToolbox quality runs 1-100, higher is better
+        let quality = quality.min(MAX_QUALITY_LEVEL);
+        let vt_quality = ((MAX_QUALITY_LEVEL - quality) as u32 * 100 / MAX_QUALITY_LEVEL as u32).max(1);
+        flag("-q:v", vt_quality).to_vec()
+    }
+    
+    fn profile_args(&self, profile: Option<&str>, level: Option<&str>) -> Vec<String> {
+        profile_level_args(Some(profile.unwrap_or("main")), level)
     }
 }
 
@@ -14,7 +14,7 @@ pub async fn get_video_metadata(video_path: &Path) -> Result<VideoMetadata> {
 
     // Run ffprobe to get video information in JSON format
     let output = Command::new("ffprobe")
-        .args(&[
+        .args([
             "-v", "quiet",
             "-print_format", "json",
             "-show_format",
@@ -44,8 +44,9 @@ pub async fn get_video_metadata(video_path: &Path) -> Result<VideoMetadata> {
         .ok_or_else(|| anyhow!("No video stream found"))?;
     
     // Check if audio stream exists
-    let has_audio = streams.iter()
-        .any(|s| s["codec_type"] == "audio");
+    let audio_stream = streams.iter()
+        .find(|s| s["codec_type"] == "audio");
+    let has_audio = audio_stream.is_some();
 
     // Extract format information
     let format = &json["format"];
@@ -57,6 +58,11 @@ pub async fn get_video_metadata(video_path: &Path) -> Result<VideoMetadata> {
     let height = video_stream["height"].as_u64()
         .ok_or_else(|| anyhow!("Failed to get video height"))? as u32;
 
+    // FFmpeg turns rotated phone clips upright while decoding, so crops,
+    // scaling and planning all see the upright frame
+    let rotation = display_rotation(video_stream);
+    let (width, height) = if rotation % 180 == 90 { (height, width) } else { (width, height) };
+
     // Parse frame rate
     let fps = parse_frame_rate(video_stream)?;
 
@@ -79,16 +85,27 @@ pub async fn get_video_metadata(video_path: &Path) -> Result<VideoMetadata> {
     let codec = video_stream["codec_name"].as_str()
         .unwrap_or("unknown")
         .to_string();
+    let profile = video_stream["profile"].as_str().map(str::to_string);
+    // ffprobe reports -99 when the level is unknown
+    let level = video_stream["level"].as_i64()
+        .and_then(|level| u32::try_from(level).ok());
+    let audio_codec = audio_stream
+        .and_then(|stream| stream["codec_name"].as_str())
+        .map(str::to_string);
 
     let mut metadata = VideoMetadata {
         width,
         height,
+        rotation,
         fps,
         duration_seconds,
         bitrate_kbps,
         codec,
+        profile,
+        level,
         estimated_complexity: ContentComplexity::Medium,
         has_audio,
+        audio_codec,
     };
 
     // Estimate content complexity based on bitrate
@@ -100,6 +117,20 @@ pub async fn get_video_metadata(video_path: &Path) -> Result<VideoMetadata> {
     Ok(metadata)
 }
 
+/// Clockwise rotation the stream asks for on display, snapped to 0, 90,
+/// 180 or 270 degrees
+fn display_rotation(video_stream: &Value) -> u32 {
+    // The display matrix counts counter-clockwise; older files carry a
+    // clockwise `rotate` tag instead
+    let degrees = video_stream["side_data_list"].as_array()
+        .and_then(|side_data| side_data.iter().find_map(|data| data["rotation"].as_f64()))
+        .map(|rotation| -rotation)
+        .or_else(|| video_stream["tags"]["rotate"].as_str().and_then(|s| s.parse::<f64>().ok()))
+        .unwrap_or(0.0);
+
+    ((degrees / 90.0).round() as i64 * 90).rem_euclid(360) as u32
+}
+
 /// Parse frame rate from ffprobe output
 fn parse_frame_rate(video_stream: &Value) -> Result<f32> {
     // Try r_frame_rate first (real frame rate)
@@ -145,9 +176,20 @@ mod tests {
     fn test_parse_fraction() {
         assert_eq!(parse_fraction("30/1"), Some(30.0));
         assert_eq!(parse_fraction("25/1"), Some(25.0));
-        assert_eq!(parse_fraction("30000/1001"), Some(29.97));
-        assert_eq!(parse_fraction("24000/1001"), Some(23.976));
+        assert!((parse_fraction("30000/1001").unwrap() - 29.97).abs() < 0.001);
+        assert!((parse_fraction("24000/1001").unwrap() - 23.976).abs() < 0.001);
         assert_eq!(parse_fraction("invalid"), None);
         assert_eq!(parse_fraction("30/0"), None);
     }
+
+    #[test]
+    fn test_display_rotation() {
+        let portrait = serde_json::json!({ "side_data_list": [{ "side_data_type": "Display Matrix", "rotation": -90 }] });
+        assert_eq!(display_rotation(&portrait), 90);
+        let flipped = serde_json::json!({ "side_data_list": [{ "rotation": 180 }] });
+        assert_eq!(display_rotation(&flipped), 180);
+        let tagged = serde_json::json!({ "tags": { "rotate": "270" } });
+        assert_eq!(display_rotation(&tagged), 270);
+        assert_eq!(display_rotation(&serde_json::json!({})), 0);
+    }
 }
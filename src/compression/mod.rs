@@ -1,24 +1,58 @@
+//! Size-targeted video compression.
+//!
+//! [`CompressionEngine`] drives FFmpeg to shrink a video to a [`FileSize`],
+//! retrying with the encoders from [`hardware::fallback`] when a hardware
+//! encoder fails. [`CompressionSettings`] describes a single job and is most
+//! easily created with [`CompressionSettings::builder`]. The [`TargetSize`]
+//! presets are shortcuts for common decimal sizes.
+
 pub mod hardware;
+mod cancel;
+mod edit;
 mod engine;
 mod estimator;
+pub mod ffmpeg;
+mod file_size;
+mod filter;
 mod metadata;
+mod pipeline;
+mod planner;
+mod platform;
+mod progress;
+mod scheduler;
+mod serde_util;
 mod size_presets;
 
-pub use engine::CompressionEngine;
-pub use estimator::SizeEstimator;
+pub use cancel::{CancellationToken, Cancelled};
+pub use edit::{format_timestamp, parse_timestamp, CropRect, EditError, Rotation, VideoEdit};
+pub use engine::{generate_output_path, generate_output_path_avoiding, CompressionEngine, CompressionResult, DryRun, DryRunAttempt};
+pub use estimator::{BitrateRecommendation, ContentComplexity, EncodeTimeEstimate, SizeEstimation, SizeEstimator, TargetEstimate, VideoMetadata};
+pub use ffmpeg::{FfmpegExit, FfmpegInvocation, FfmpegRunner, SystemFfmpegRunner};
+pub use file_size::{FileSize, ParseFileSizeError, SizeUnit};
+pub use filter::{fit_within, Deinterlacer, FilterChain, FramePipeline, ScaleMode, VideoFilter};
+pub use metadata::get_video_metadata;
+pub use pipeline::EncodePipeline;
+pub use planner::{EncodingPlan, MIN_BITS_PER_PIXEL};
+pub use platform::{AspectRatio, PlatformPreset, Resolution, VideoCodec};
+pub use progress::{EncodeStage, ProgressEvent, ProgressParser, ProgressSender, ProgressUpdate};
+pub use scheduler::{CompressionJob, JobLimits, JobScheduler};
 pub use size_presets::TargetSize;
 
-use anyhow::Result;
 use log::warn;
 
 use hardware::{HardwareCapabilities, HardwareEncoder, HardwarePreset, HardwareQuality};
-use crate::cli::CompressionCliSettings;
 
+/// Quality level used by the constant-quality modes unless overridden
+pub const DEFAULT_QUALITY_LEVEL: u8 = 23;
+
+/// Highest (worst) quality level, matching the x264 CRF scale
+pub const MAX_QUALITY_LEVEL: u8 = 51;
+
+/// Settings for a single compression job.
 #[derive(Debug, Clone)]
 pub struct CompressionSettings {
     // Existing fields
-    pub target_size: TargetSize,
-    #[allow(dead_code)]
+    pub target_size: FileSize,
     pub estimated_size_mb: Option<f32>,
     
     // New hardware acceleration fields
@@ -26,79 +60,319 @@ pub struct CompressionSettings {
     pub enable_hardware_accel: bool,
     pub cuda_device_id: Option<u32>,
     pub hardware_preset: HardwarePreset,
-    #[allow(dead_code)]
     pub hardware_quality: HardwareQuality,
-    #[allow(dead_code)]
+    pub quality_level: u8,  // CRF-style 0-51, lower is better; used by constant-quality modes
     pub force_software_fallback: bool,
     pub memory_optimization: bool,
     pub compatibility_mode: bool,  // Force x264 for maximum compatibility
     pub remove_audio: bool,  // Remove audio track from output
+    pub keep_resolution: bool,  // Never lower resolution or frame rate to fit the target
+    pub size_convergence: SizeConvergence,
+    /// Sharing platform whose constraints the output must meet
+    pub platform: Option<PlatformPreset>,
+    /// Trim range, crop and rotation applied before encoding
+    pub edit: VideoEdit,
+    /// Largest output frame, kept even with `keep_resolution`; the aspect ratio is preserved
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    /// Keep frames in video memory from decoding to encoding where the
+    /// encoder and input allow it; see [`EncodePipeline`]
+    pub full_gpu_pipeline: bool,
 }
 
 impl CompressionSettings {
-    pub fn from_cli_settings(
-        cli_settings: &CompressionCliSettings,
-        hw_capabilities: &HardwareCapabilities,
-    ) -> Result<Self> {
-        let target_size = TargetSize::from_mb(cli_settings.size.as_mb());
-        
-        // Determine hardware encoder
-        let hardware_encoder = match &cli_settings.hw_encoder {
-            Some(cli_encoder) => {
-                let requested = cli_encoder.to_hardware_encoder();
-                if hw_capabilities.available_encoders.contains(&requested) || cli_settings.force_software {
-                    requested
-                } else {
-                    warn!("Requested encoder {:?} not available, using preferred encoder", requested);
-                    hw_capabilities.preferred_encoder.clone().unwrap_or(HardwareEncoder::Software)
-                }
-            },
-            None => {
-                if cli_settings.force_software {
-                    HardwareEncoder::Software
-                } else {
-                    hw_capabilities.preferred_encoder.clone().unwrap_or(HardwareEncoder::Software)
-                }
-            }
-        };
-        
-        let enable_hardware_accel = !cli_settings.force_software && hardware_encoder != HardwareEncoder::Software;
-        
-        Ok(CompressionSettings {
-            target_size,
-            estimated_size_mb: None,
-            hardware_encoder,
-            enable_hardware_accel,
-            cuda_device_id: cli_settings.cuda_device,
-            hardware_preset: cli_settings.hw_preset.to_hardware_preset(),
-            hardware_quality: cli_settings.hw_quality.to_hardware_quality(),
-            force_software_fallback: cli_settings.force_software,
-            memory_optimization: cli_settings.memory_opt,
-            compatibility_mode: cli_settings.compatibility,
-            remove_audio: false,  // Default to keeping audio in CLI
-        })
-    }
-    
-    #[allow(dead_code)]
+    /// Start building settings from the defaults.
+    pub fn builder() -> CompressionSettingsBuilder {
+        CompressionSettingsBuilder::default()
+    }
+    
     pub fn get_effective_target_mb(&self) -> Option<f32> {
-        Some(self.target_size.as_mb())
+        Some(self.target_size.as_mb() as f32)
     }
 }
 
 impl Default for CompressionSettings {
     fn default() -> Self {
         Self {
-            target_size: TargetSize::Size10MB,
+            target_size: TargetSize::Size10MB.into(),
             estimated_size_mb: None,
             hardware_encoder: HardwareEncoder::Software,
             enable_hardware_accel: true,
             cuda_device_id: None,
             hardware_preset: HardwarePreset::Medium,
             hardware_quality: HardwareQuality::Auto,
+            quality_level: DEFAULT_QUALITY_LEVEL,
             force_software_fallback: false,
             memory_optimization: false,
             compatibility_mode: true,  // Default to true for maximum compatibility
             remove_audio: false,  // Default to keeping audio
+            keep_resolution: false,
+            size_convergence: SizeConvergence::default(),
+            platform: None,
+            edit: VideoEdit::default(),
+            max_width: None,
+            max_height: None,
+            full_gpu_pipeline: true,
+        }
+    }
+}
+
+/// Controls the re-encode loop that keeps the output at or below the target.
+///
+/// After each encode the engine measures the file; if it is over the limit
+/// the video bitrate is scaled down by the overshoot and the video encoded
+/// again, up to `max_iterations` encodes in total.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeConvergence {
+    /// Total number of encodes allowed, including the first. 1 disables re-encoding.
+    pub max_iterations: u32,
+    /// Allowed overshoot as a fraction of the target (0.0 = never larger).
+    pub tolerance: f32,
+}
+
+impl SizeConvergence {
+    /// A single encode with no size correction
+    pub fn disabled() -> Self {
+        Self { max_iterations: 1, tolerance: 0.0 }
+    }
+    
+    /// Largest output size that still counts as meeting `target`
+    pub fn size_limit(&self, target: FileSize) -> FileSize {
+        let tolerance = self.tolerance.max(0.0) as f64;
+        FileSize::from_bytes((target.as_bytes() as f64 * (1.0 + tolerance)).floor() as u64)
+    }
+}
+
+impl Default for SizeConvergence {
+    fn default() -> Self {
+        Self { max_iterations: 4, tolerance: 0.0 }
+    }
+}
+
+/// Builder for [`CompressionSettings`].
+///
+/// Unset fields keep their [`Default`] values. Use
+/// [`build_with_capabilities`](Self::build_with_capabilities) to resolve the
+/// requested encoder against what the machine actually supports.
+#[derive(Debug, Clone, Default)]
+pub struct CompressionSettingsBuilder {
+    settings: CompressionSettings,
+    requested_encoder: Option<HardwareEncoder>,
+    requested_target: Option<FileSize>,
+}
+
+impl CompressionSettingsBuilder {
+    /// Target output size; accepts a [`FileSize`] or a [`TargetSize`] preset.
+    pub fn target_size(mut self, target_size: impl Into<FileSize>) -> Self {
+        self.requested_target = Some(target_size.into());
+        self
+    }
+    
+    /// Request a specific encoder. Without this the preferred encoder of the
+    /// detected hardware (or software) is used.
+    pub fn hardware_encoder(mut self, encoder: HardwareEncoder) -> Self {
+        self.requested_encoder = Some(encoder);
+        self
+    }
+    
+    pub fn cuda_device(mut self, device_id: u32) -> Self {
+        self.settings.cuda_device_id = Some(device_id);
+        self
+    }
+    
+    pub fn hardware_preset(mut self, preset: HardwarePreset) -> Self {
+        self.settings.hardware_preset = preset;
+        self
+    }
+    
+    /// Rate control mode: size target, constant quality or capped quality.
+    pub fn hardware_quality(mut self, quality: HardwareQuality) -> Self {
+        self.settings.hardware_quality = quality;
+        self
+    }
+    
+    /// Quality level for the constant-quality modes on a CRF-style 0-51
+    /// scale, translated to CQ/QP/global_quality per encoder.
+    pub fn quality_level(mut self, level: u8) -> Self {
+        self.settings.quality_level = level.min(MAX_QUALITY_LEVEL);
+        self
+    }
+    
+    /// Disable hardware acceleration entirely.
+    pub fn force_software(mut self, force: bool) -> Self {
+        self.settings.force_software_fallback = force;
+        self
+    }
+    
+    pub fn memory_optimization(mut self, enabled: bool) -> Self {
+        self.settings.memory_optimization = enabled;
+        self
+    }
+    
+    /// Decode and scale on the encoder's GPU where the input allows it.
+    pub fn full_gpu_pipeline(mut self, enabled: bool) -> Self {
+        self.settings.full_gpu_pipeline = enabled;
+        self
+    }
+    
+    /// Always produce H.264 regardless of the encoder family.
+    pub fn compatibility_mode(mut self, enabled: bool) -> Self {
+        self.settings.compatibility_mode = enabled;
+        self
+    }
+    
+    pub fn remove_audio(mut self, remove: bool) -> Self {
+        self.settings.remove_audio = remove;
+        self
+    }
+    
+    /// Keep the source resolution and frame rate even when the budget is tight.
+    pub fn keep_resolution(mut self, keep: bool) -> Self {
+        self.settings.keep_resolution = keep;
+        self
+    }
+    
+    pub fn size_convergence(mut self, convergence: SizeConvergence) -> Self {
+        self.settings.size_convergence = convergence;
+        self
+    }
+    
+    /// Follow a sharing platform's constraints. Without a
+    /// [`target_size`](Self::target_size) the target becomes the platform's
+    /// limit; a larger target is capped at the limit and a smaller one is
+    /// kept, whatever order the builder methods are called in.
+    pub fn platform(mut self, platform: PlatformPreset) -> Self {
+        self.settings.platform = Some(platform);
+        self
+    }
+    
+    /// Trim, crop or rotate the video before encoding it.
+    pub fn edit(mut self, edit: VideoEdit) -> Self {
+        self.settings.edit = edit;
+        self
+    }
+    
+    /// Scale larger frames down to at most `width` pixels wide.
+    pub fn max_width(mut self, width: u32) -> Self {
+        self.settings.max_width = Some(width);
+        self
+    }
+    
+    /// Scale larger frames down to at most `height` pixels high.
+    pub fn max_height(mut self, height: u32) -> Self {
+        self.settings.max_height = Some(height);
+        self
+    }
+    
+    /// Build the settings without checking encoder availability.
+    pub fn build(self) -> CompressionSettings {
+        let encoder = if self.settings.force_software_fallback {
+            HardwareEncoder::Software
+        } else {
+            self.requested_encoder.unwrap_or(HardwareEncoder::Software)
+        };
+        self.finish(encoder)
+    }
+    
+    /// Build the settings, replacing the requested encoder with the preferred
+    /// one when it isn't available on this machine.
+    pub fn build_with_capabilities(self, capabilities: &HardwareCapabilities) -> CompressionSettings {
+        let preferred = capabilities.preferred_encoder.unwrap_or(HardwareEncoder::Software);
+        
+        let encoder = match self.requested_encoder {
+            _ if self.settings.force_software_fallback => HardwareEncoder::Software,
+            Some(requested) => {
+                if capabilities.available_encoders.contains(&requested) {
+                    requested
+                } else {
+                    warn!("Requested encoder {:?} not available, using preferred encoder", requested);
+                    preferred
+                }
+            },
+            None => preferred,
+        };
+        
+        self.finish(encoder)
+    }
+    
+    fn finish(mut self, encoder: HardwareEncoder) -> CompressionSettings {
+        if let Some(target) = self.requested_target {
+            self.settings.target_size = target;
         }
+        if let Some(platform) = &self.settings.platform {
+            match self.requested_target {
+                Some(target) if target > platform.max_size => {
+                    warn!("Target size {} is over the {} limit of {}, using the limit",
+                          target, platform.name, platform.max_size);
+                    self.settings.target_size = platform.max_size;
+                }
+                Some(_) => {}
+                None => self.settings.target_size = platform.max_size,
+            }
+            // Every encoder family can produce H.264 in compatibility mode
+            self.settings.compatibility_mode = platform.video_codec == VideoCodec::H264;
+        }
+        
+        self.settings.hardware_encoder = encoder;
+        self.settings.enable_hardware_accel =
+            !self.settings.force_software_fallback && encoder != HardwareEncoder::Software;
+        self.settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_builder_falls_back_to_preferred_encoder() {
+        let caps = HardwareCapabilities::software_only();
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(HardwareEncoder::NvencH264)
+            .build_with_capabilities(&caps);
+        
+        assert_eq!(settings.hardware_encoder, HardwareEncoder::Software);
+        assert!(!settings.enable_hardware_accel);
+    }
+    
+    #[test]
+    fn test_builder_force_software() {
+        let settings = CompressionSettings::builder()
+            .hardware_encoder(HardwareEncoder::NvencH265)
+            .force_software(true)
+            .build();
+        
+        assert_eq!(settings.hardware_encoder, HardwareEncoder::Software);
+        assert!(!settings.enable_hardware_accel);
+        assert!(settings.force_software_fallback);
+    }
+    
+    #[test]
+    fn test_builder_platform_and_target_in_either_order() {
+        let email = || PlatformPreset::find_built_in("email-25mb").unwrap();
+        let build = |target: FileSize, platform_first: bool| {
+            let builder = CompressionSettings::builder();
+            let builder = if platform_first {
+                builder.platform(email()).target_size(target)
+            } else {
+                builder.target_size(target).platform(email())
+            };
+            builder.build().target_size
+        };
+        
+        for platform_first in [true, false] {
+            assert_eq!(build(FileSize::from_mb(8.0), platform_first), FileSize::from_mb(8.0));
+            assert_eq!(build(FileSize::from_mb(50.0), platform_first), FileSize::from_mb(25.0));
+        }
+        assert_eq!(CompressionSettings::builder().platform(email()).build().target_size, FileSize::from_mb(25.0));
+    }
+    
+    #[test]
+    fn test_size_convergence_limit() {
+        let target = FileSize::from_mb(8.0);
+        assert_eq!(SizeConvergence::default().size_limit(target), target);
+        
+        let lenient = SizeConvergence { max_iterations: 2, tolerance: 0.05 };
+        assert_eq!(lenient.size_limit(target).as_bytes(), 8_400_000);
     }
 }
@@ -0,0 +1,169 @@
+//! Where frames are decoded and filtered on their way to the encoder.
+//!
+//! Hardware encoders are fastest when frames never leave video memory: the
+//! GPU decodes the input, scales it with its own filters and encodes it.
+//! That only works for input codecs and profiles the GPU can decode, so
+//! [`EncodePipeline::select`] falls back to a hybrid pipeline that decodes
+//! and filters on the CPU and hands the frames to the GPU for encoding. The
+//! engine also retries a failed full-GPU encode with the hybrid pipeline
+//! before giving up on the encoder.
+
+use serde::Serialize;
+use std::fmt;
+
+use super::ffmpeg::FfmpegInvocation;
+use super::filter::{FilterChain, FramePipeline};
+use super::hardware::platform::VAAPI_DEVICE;
+use super::{CompressionSettings, VideoMetadata};
+
+/// Name the pipeline's device is initialized under, for `-hwaccel_device`
+/// and `-filter_hw_device`
+const DEVICE_NAME: &str = "gpu";
+
+/// How frames get from the decoder to the encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EncodePipeline {
+    /// Decoding and filtering on the CPU; the encoder reads system memory,
+    /// as software and AMF encoders do
+    System,
+    /// Decoding and filtering on the CPU, encoding on the device. VideoToolbox
+    /// still decodes on the GPU where it can, handing frames back itself.
+    Hybrid(FramePipeline),
+    /// Decoding, scaling and encoding on the device; frames only visit
+    /// system memory for filters the device has no version of
+    Gpu(FramePipeline),
+}
+
+impl EncodePipeline {
+    /// The pipeline for encoding `metadata`'s video with `settings`
+    pub fn select(settings: &CompressionSettings, metadata: &VideoMetadata) -> Self {
+        let Some(device) = settings.hardware_encoder.backend().device() else {
+            return EncodePipeline::System;
+        };
+
+        let full_gpu = settings.enable_hardware_accel
+            && settings.full_gpu_pipeline
+            && device != FramePipeline::VideoToolbox
+            && hardware_decodes(device, metadata);
+        if full_gpu {
+            EncodePipeline::Gpu(device)
+        } else {
+            EncodePipeline::Hybrid(device)
+        }
+    }
+
+    pub fn is_gpu(&self) -> bool {
+        matches!(self, EncodePipeline::Gpu(_))
+    }
+
+    /// Whether the encoder receives frames in video memory rather than a
+    /// pixel format it could be told with `-pix_fmt`
+    pub fn encodes_device_frames(&self) -> bool {
+        matches!(self, EncodePipeline::Gpu(_) | EncodePipeline::Hybrid(FramePipeline::Vaapi))
+    }
+
+    /// Set up decoding and the device on `invocation`
+    pub(crate) fn input_args(&self, invocation: &mut FfmpegInvocation, settings: &CompressionSettings, metadata: &VideoMetadata) {
+        match self {
+            EncodePipeline::System => {}
+            EncodePipeline::Gpu(device) => {
+                let (hwaccel, output_format) = match device {
+                    FramePipeline::Cuda => ("cuda", "cuda"),
+                    FramePipeline::Vaapi => ("vaapi", "vaapi"),
+                    FramePipeline::Qsv => ("qsv", "qsv"),
+                    FramePipeline::Software | FramePipeline::VideoToolbox => return,
+                };
+                init_device(invocation, *device, settings);
+                invocation.input_arg("-hwaccel").input_arg(hwaccel)
+                    .input_arg("-hwaccel_device").input_arg(DEVICE_NAME)
+                    .input_arg("-hwaccel_output_format").input_arg(output_format);
+                // QSV decodes through its own decoders rather than a hwaccel hook
+                if let (FramePipeline::Qsv, Some(decoder)) = (device, qsv_decoder(&metadata.codec)) {
+                    invocation.input_arg("-c:v").input_arg(decoder);
+                }
+            }
+            // VAAPI encoders only take VAAPI surfaces, uploaded at the end of the chain
+            EncodePipeline::Hybrid(FramePipeline::Vaapi) => init_device(invocation, FramePipeline::Vaapi, settings),
+            EncodePipeline::Hybrid(FramePipeline::VideoToolbox) if settings.enable_hardware_accel => {
+                invocation.input_arg("-hwaccel").input_arg("videotoolbox");
+            }
+            EncodePipeline::Hybrid(_) => {}
+        }
+    }
+
+    /// `filters` rendered for where this pipeline keeps the frames
+    pub fn render(&self, filters: &FilterChain) -> Vec<String> {
+        match self {
+            EncodePipeline::System | EncodePipeline::Hybrid(_) => {
+                let mut rendered = filters.render(FramePipeline::Software);
+                if *self == EncodePipeline::Hybrid(FramePipeline::Vaapi) {
+                    rendered.push("format=nv12,hwupload".to_string());
+                }
+                rendered
+            }
+            EncodePipeline::Gpu(device) => filters.render(*device),
+        }
+    }
+}
+
+impl fmt::Display for EncodePipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodePipeline::System => write!(f, "CPU decoding and filtering"),
+            EncodePipeline::Hybrid(device) => write!(f, "CPU decoding and filtering, {:?} encoding", device),
+            EncodePipeline::Gpu(device) => write!(f, "{:?} decoding, filtering and encoding", device),
+        }
+    }
+}
+
+/// Create the device as [`DEVICE_NAME`] and hand it to the filters, which
+/// need it to upload frames again after a CPU-only filter
+fn init_device(invocation: &mut FfmpegInvocation, device: FramePipeline, settings: &CompressionSettings) {
+    let spec = match device {
+        FramePipeline::Cuda => format!("cuda={}:{}", DEVICE_NAME, settings.cuda_device_id.unwrap_or(0)),
+        FramePipeline::Vaapi => format!("vaapi={}:{}", DEVICE_NAME, VAAPI_DEVICE),
+        FramePipeline::Qsv => format!("qsv={}", DEVICE_NAME),
+        FramePipeline::Software | FramePipeline::VideoToolbox => return,
+    };
+    invocation.global_arg("-init_hw_device").global_arg(spec)
+        .global_arg("-filter_hw_device").global_arg(DEVICE_NAME);
+}
+
+/// Whether `device` decodes the input straight into video memory. 10-bit and
+/// 4:2:2/4:4:4 sources are left to the CPU: the output is 8-bit 4:2:0, and
+/// the GPU filters don't convert.
+fn hardware_decodes(device: FramePipeline, metadata: &VideoMetadata) -> bool {
+    // FFmpeg's automatic rotation filters only take system memory frames
+    if metadata.rotation != 0 {
+        return false;
+    }
+
+    let profile = metadata.profile.as_deref().unwrap_or_default().to_ascii_lowercase();
+    let beyond_8_bit_420 = ["10", "12", "4:2:2", "4:4:4", "rext"].iter().any(|marker| profile.contains(marker));
+    if beyond_8_bit_420 {
+        return false;
+    }
+
+    let codec = metadata.codec.as_str();
+    match device {
+        FramePipeline::Cuda => matches!(codec, "h264" | "hevc" | "av1" | "vp8" | "vp9" | "mpeg1video" | "mpeg2video" | "mpeg4" | "vc1" | "mjpeg"),
+        FramePipeline::Vaapi => matches!(codec, "h264" | "hevc" | "av1" | "vp8" | "vp9" | "mpeg2video" | "vc1" | "mjpeg"),
+        FramePipeline::Qsv => qsv_decoder(codec).is_some(),
+        FramePipeline::Software | FramePipeline::VideoToolbox => false,
+    }
+}
+
+/// FFmpeg's QSV decoder for `codec`, as ffprobe names it
+fn qsv_decoder(codec: &str) -> Option<&'static str> {
+    match codec {
+        "h264" => Some("h264_qsv"),
+        "hevc" => Some("hevc_qsv"),
+        "av1" => Some("av1_qsv"),
+        "vp8" => Some("vp8_qsv"),
+        "vp9" => Some("vp9_qsv"),
+        "mpeg2video" => Some("mpeg2_qsv"),
+        "vc1" => Some("vc1_qsv"),
+        "mjpeg" => Some("mjpeg_qsv"),
+        _ => None,
+    }
+}
@@ -0,0 +1,333 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::estimator::{bits_per_pixel, quality_from_bits_per_pixel};
+use super::{fit_within, CompressionSettings, FileSize, PlatformPreset, SizeEstimator, VideoMetadata};
+
+/// Absolute minimum video bitrate to prevent unusable video
+pub(crate) const MIN_VIDEO_BITRATE_KBPS: u32 = 50;
+
+/// Below this many bits per pixel the estimator rates quality "very poor",
+/// so the planner lowers resolution/frame rate to stay above it.
+pub const MIN_BITS_PER_PIXEL: f32 = 0.04;
+
+/// Short-side resolutions tried when downscaling (2160p, 1440p, ... 240p)
+const SHORT_SIDE_LADDER: [u32; 8] = [2160, 1440, 1080, 720, 540, 480, 360, 240];
+
+/// AAC bitrates the planner steps down through on tight budgets
+const AUDIO_LADDER_KBPS: [u32; 5] = [128, 112, 96, 64, 48];
+
+/// Audio may use at most this share of the total bitrate before it is lowered
+const MAX_AUDIO_SHARE: f32 = 0.2;
+
+/// Frame rate high-fps sources are reduced to before resolution is lowered
+const REDUCED_FPS: f32 = 30.0;
+
+// Same budget split as the encoder: 1% container overhead, 0.98 safety margin
+const CONTAINER_OVERHEAD: f32 = 0.01;
+const SAFETY_MARGIN: f32 = 0.98;
+
+/// Output resolution, frame rate and bitrates chosen for a size budget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodingPlan {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+    pub video_bitrate_kbps: u32,
+    /// 0 when the output has no audio
+    pub audio_bitrate_kbps: u32,
+    pub bits_per_pixel: f32,
+    pub estimated_quality: f32, // 0.0 to 1.0
+    /// False when even the smallest sensible output doesn't fit the target
+    pub feasible: bool,
+    /// Smallest target that still gives watchable output for this input
+    pub minimum_size: FileSize,
+}
+
+impl EncodingPlan {
+    pub fn is_downscaled(&self, metadata: &VideoMetadata) -> bool {
+        self.width != metadata.width || self.height != metadata.height
+    }
+
+    pub fn is_fps_reduced(&self, metadata: &VideoMetadata) -> bool {
+        self.fps + 0.01 < metadata.fps
+    }
+
+    pub fn summary(&self) -> String {
+        let audio = if self.audio_bitrate_kbps > 0 {
+            format!("{} kbps audio", self.audio_bitrate_kbps)
+        } else {
+            "no audio".to_string()
+        };
+
+        let fps = if self.fps.fract().abs() < 0.01 {
+            format!("{:.0}", self.fps)
+        } else {
+            format!("{:.2}", self.fps)
+        };
+
+        format!(
+            "{}x{} @ {} fps, {} kbps video, {}",
+            self.width, self.height, fps, self.video_bitrate_kbps, audio
+        )
+    }
+}
+
+impl SizeEstimator {
+    /// Pick resolution, frame rate and audio bitrate that fit the target size.
+    ///
+    /// Audio is lowered first if it would eat more than a fifth of the
+    /// budget, then high frame rates are capped at 30 fps, then resolution
+    /// steps down until the video gets at least [`MIN_BITS_PER_PIXEL`].
+    /// With `keep_resolution` only the audio bitrate is adapted. A platform
+    /// preset caps frame size, frame rate and audio bitrate up front, and
+    /// `max_width`/`max_height` the frame size, even with `keep_resolution`.
+    pub fn plan_encoding(&self, metadata: &VideoMetadata, settings: &CompressionSettings) -> EncodingPlan {
+        let duration_seconds = metadata.duration_seconds.max(0.1);
+        let keep_audio = metadata.has_audio && !settings.remove_audio;
+        let max_audio_kbps = settings.platform.as_ref()
+            .and_then(|platform| platform.max_audio_bitrate_kbps)
+            .unwrap_or(u32::MAX);
+        let mut source = match &settings.platform {
+            Some(platform) => platform_source(metadata, platform),
+            None => metadata.clone(),
+        };
+        (source.width, source.height) = fit_within(source.width, source.height, settings.max_width, settings.max_height);
+
+        // Total kbps the file may use, before container overhead
+        let total_kbps = settings.target_size.as_bits() as f32 * (1.0 - CONTAINER_OVERHEAD)
+            / duration_seconds / 1000.0;
+
+        let audio_bitrate_kbps = if keep_audio && !settings.hardware_quality.has_size_target() {
+            default_audio_bitrate_kbps(duration_seconds).min(max_audio_kbps)
+        } else if keep_audio {
+            let default_audio = default_audio_bitrate_kbps(duration_seconds).min(max_audio_kbps);
+            let mut audio = default_audio;
+            for &rung in AUDIO_LADDER_KBPS.iter().filter(|&&rung| rung < default_audio) {
+                if audio as f32 <= total_kbps * MAX_AUDIO_SHARE {
+                    break;
+                }
+                audio = rung;
+            }
+            audio
+        } else {
+            0
+        };
+
+        let video_budget_kbps = (total_kbps - audio_bitrate_kbps as f32) * SAFETY_MARGIN;
+        let video_bitrate_kbps = (video_budget_kbps.max(0.0) as u32).max(MIN_VIDEO_BITRATE_KBPS);
+
+        // Constant quality ignores the size, so there is nothing to adapt
+        let candidates = if settings.keep_resolution || !settings.hardware_quality.has_size_target() {
+            vec![(source.width, source.height, source.fps)]
+        } else {
+            output_candidates(&source)
+        };
+
+        let (width, height, fps) = candidates.iter()
+            .copied()
+            .find(|&(w, h, fps)| bits_per_pixel(video_bitrate_kbps, w, h, fps) >= MIN_BITS_PER_PIXEL)
+            .unwrap_or_else(|| *candidates.last().unwrap());
+
+        let bpp = bits_per_pixel(video_bitrate_kbps, width, height, fps);
+        let feasible = !settings.hardware_quality.has_size_target()
+            || (video_budget_kbps >= MIN_VIDEO_BITRATE_KBPS as f32 && bpp >= MIN_BITS_PER_PIXEL);
+        let minimum_size = minimum_sensible_size(&source, keep_audio);
+
+        let plan = EncodingPlan {
+            width,
+            height,
+            fps,
+            video_bitrate_kbps,
+            audio_bitrate_kbps,
+            bits_per_pixel: bpp,
+            estimated_quality: quality_from_bits_per_pixel(bpp, &metadata.estimated_complexity),
+            feasible,
+            minimum_size,
+        };
+
+        info!("Encoding plan for {}: {}", settings.target_size, plan.summary());
+        if !plan.feasible {
+            warn!("Target {} is too small for watchable output; the smallest sensible size is {}",
+                  settings.target_size, plan.minimum_size);
+        }
+
+        plan
+    }
+}
+
+/// The source as the platform allows it: frame padded and capped, frame
+/// rate capped
+fn platform_source(metadata: &VideoMetadata, platform: &PlatformPreset) -> VideoMetadata {
+    let (width, height) = platform.frame_for(metadata.width, metadata.height);
+    VideoMetadata {
+        width,
+        height,
+        fps: platform.max_fps.map_or(metadata.fps, |max_fps| metadata.fps.min(max_fps)),
+        ..metadata.clone()
+    }
+}
+
+/// Audio bitrate used when the budget isn't tight
+fn default_audio_bitrate_kbps(duration_seconds: f32) -> u32 {
+    if duration_seconds > 600.0 {
+        96  // 96 kbps for videos > 10 minutes
+    } else if duration_seconds > 300.0 {
+        112  // 112 kbps for videos > 5 minutes
+    } else {
+        128  // 128 kbps for shorter videos
+    }
+}
+
+/// Output (width, height, fps) options from best to smallest
+fn output_candidates(metadata: &VideoMetadata) -> Vec<(u32, u32, f32)> {
+    let mut candidates = vec![(metadata.width, metadata.height, metadata.fps)];
+    let capped_fps = metadata.fps.min(REDUCED_FPS);
+
+    if capped_fps < metadata.fps {
+        candidates.push((metadata.width, metadata.height, capped_fps));
+    }
+
+    let short_side = metadata.width.min(metadata.height);
+    for &rung in SHORT_SIDE_LADDER.iter().filter(|&&rung| rung < short_side) {
+        let (width, height) = scale_to_short_side(metadata.width, metadata.height, rung);
+        candidates.push((width, height, capped_fps));
+    }
+
+    candidates
+}
+
+/// Scale so the shorter side equals `short_side`, keeping the aspect ratio
+/// and rounding both sides to even numbers as 4:2:0 encoders require.
+fn scale_to_short_side(width: u32, height: u32, short_side: u32) -> (u32, u32) {
+    let factor = short_side as f64 / width.min(height) as f64;
+    let even = |value: u32| ((value as f64 * factor / 2.0).round() as u32 * 2).max(2);
+    (even(width), even(height))
+}
+
+fn minimum_sensible_size(metadata: &VideoMetadata, keep_audio: bool) -> FileSize {
+    let (width, height, fps) = *output_candidates(metadata).last().unwrap();
+    // One spare kbps so the truncated planner bitrate still clears the floor
+    let video_kbps = (MIN_BITS_PER_PIXEL * (width * height) as f32 * fps / 1000.0).ceil()
+        .max(MIN_VIDEO_BITRATE_KBPS as f32) + 1.0;
+    let audio_kbps = if keep_audio { AUDIO_LADDER_KBPS[AUDIO_LADDER_KBPS.len() - 1] } else { 0 };
+
+    let total_kbps = (video_kbps / SAFETY_MARGIN + audio_kbps as f32) / (1.0 - CONTAINER_OVERHEAD);
+    let bytes = total_kbps as f64 * 1000.0 * metadata.duration_seconds.max(0.1) as f64 / 8.0;
+    FileSize::from_bytes(bytes.ceil() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::TargetSize;
+
+    fn uhd_clip(duration_seconds: f32) -> VideoMetadata {
+        VideoMetadata {
+            width: 3840,
+            height: 2160,
+            fps: 60.0,
+            duration_seconds,
+            ..VideoMetadata::default_hd()
+        }
+    }
+
+    #[test]
+    fn test_generous_budget_keeps_source() {
+        let settings = CompressionSettings::builder().target_size(TargetSize::Size100MB).build();
+        let plan = SizeEstimator::new().plan_encoding(&VideoMetadata::default_hd(), &settings);
+
+        assert_eq!((plan.width, plan.height), (1920, 1080));
+        assert_eq!(plan.fps, 30.0);
+        assert_eq!(plan.audio_bitrate_kbps, 128);
+        assert!(plan.feasible);
+    }
+
+    #[test]
+    fn test_tight_budget_lowers_fps_then_resolution() {
+        let metadata = uhd_clip(60.0);
+        let settings = CompressionSettings::builder().target_size(TargetSize::Size10MB).build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+
+        assert!(plan.is_fps_reduced(&metadata));
+        assert!(plan.is_downscaled(&metadata));
+        assert!(plan.bits_per_pixel >= MIN_BITS_PER_PIXEL);
+        assert_eq!(plan.width % 2, 0);
+        assert_eq!(plan.height % 2, 0);
+        assert!(plan.feasible);
+    }
+
+    #[test]
+    fn test_infeasible_target_reports_minimum_size() {
+        // 10 minutes of 4K into 5 MB
+        let metadata = uhd_clip(600.0);
+        let settings = CompressionSettings::builder().target_size(TargetSize::Size5MB).build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+
+        assert!(!plan.feasible);
+        assert_eq!(plan.height, 240);
+        assert_eq!(plan.audio_bitrate_kbps, 48);
+        assert!(plan.minimum_size > settings.target_size);
+
+        // Planning for the reported minimum gives a feasible plan
+        let settings = CompressionSettings::builder().target_size(plan.minimum_size).build();
+        assert!(SizeEstimator::new().plan_encoding(&metadata, &settings).feasible);
+    }
+
+    #[test]
+    fn test_keep_resolution() {
+        let metadata = uhd_clip(60.0);
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size10MB)
+            .keep_resolution(true)
+            .build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+
+        assert!(!plan.is_downscaled(&metadata));
+        assert!(!plan.is_fps_reduced(&metadata));
+        assert!(!plan.feasible);
+    }
+
+    #[test]
+    fn test_platform_caps_frame_even_with_keep_resolution() {
+        let metadata = uhd_clip(30.0);
+        let email = PlatformPreset::find_built_in("email-25mb").unwrap();
+        let settings = CompressionSettings::builder()
+            .platform(email)
+            .keep_resolution(true)
+            .build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+
+        assert_eq!((plan.width, plan.height, plan.fps), (1280, 720, 30.0));
+        assert_eq!(plan.audio_bitrate_kbps, 128);
+        assert!(settings.compatibility_mode);
+        assert_eq!(settings.target_size, FileSize::from_mb(25.0));
+    }
+
+    #[test]
+    fn test_max_height_caps_frame_even_with_keep_resolution() {
+        let metadata = uhd_clip(30.0);
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size100MB)
+            .max_height(720)
+            .keep_resolution(true)
+            .build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+
+        assert_eq!((plan.width, plan.height, plan.fps), (1280, 720, 60.0));
+
+        // A tight target still steps down from the capped frame
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size1MB)
+            .max_width(1920)
+            .build();
+        let plan = SizeEstimator::new().plan_encoding(&metadata, &settings);
+        assert!(plan.width < 1920 && plan.height < 1080);
+    }
+
+    #[test]
+    fn test_scale_to_short_side_portrait() {
+        assert_eq!(scale_to_short_side(1080, 1920, 720), (720, 1280));
+        assert_eq!(scale_to_short_side(1920, 1080, 480), (854, 480));
+    }
+}
@@ -0,0 +1,341 @@
+//! Presets for sharing platforms.
+//!
+//! A [`PlatformPreset`] bundles everything a platform demands of an upload:
+//! a size limit, the largest resolution and frame rate, the video codec with
+//! its profile and level, the audio codec and the container flags. The
+//! planner and the FFmpeg invocation follow the preset, and the finished
+//! output is checked against it with [`PlatformPreset::check`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::serde_util::string_serde;
+use super::{FileSize, VideoMetadata};
+
+/// Width and height, written `1920x1080`.
+///
+/// As a limit it applies in either orientation: `1920x1080` admits both
+/// 1920x1080 and 1080x1920.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    /// Whether a `width`x`height` frame fits, in either orientation
+    pub fn admits(&self, width: u32, height: u32) -> bool {
+        width.max(height) <= self.width.max(self.height) && width.min(height) <= self.width.min(self.height)
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_pair(s, 'x')
+            .map(|(width, height)| Self { width, height })
+            .ok_or_else(|| format!("invalid resolution '{}', expected e.g. 1920x1080", s))
+    }
+}
+
+/// Frame shape, written `9:16`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_pair(s, ':')
+            .map(|(width, height)| Self { width, height })
+            .ok_or_else(|| format!("invalid aspect ratio '{}', expected e.g. 9:16", s))
+    }
+}
+
+fn parse_pair(s: &str, separator: char) -> Option<(u32, u32)> {
+    let (a, b) = s.trim().split_once(separator)?;
+    let (a, b) = (a.trim().parse().ok()?, b.trim().parse().ok()?);
+    (a > 0 && b > 0).then_some((a, b))
+}
+
+string_serde!(Resolution);
+string_serde!(AspectRatio);
+
+/// Video codec a platform requires
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    #[default]
+    H264,
+    Hevc,
+}
+
+impl VideoCodec {
+    /// Codec name as ffprobe reports it
+    pub fn ffprobe_name(&self) -> &'static str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::Hevc => "hevc",
+        }
+    }
+
+    /// ffprobe's integer form of a level such as `4.1`
+    fn ffprobe_level(&self, level: &str) -> Option<u32> {
+        let level: f32 = level.parse().ok()?;
+        let scale = match self {
+            VideoCodec::H264 => 10.0,
+            VideoCodec::Hevc => 30.0,
+        };
+        Some((level * scale).round() as u32)
+    }
+}
+
+fn default_audio_codec() -> String {
+    "aac".to_string()
+}
+
+fn default_movflags() -> String {
+    "+faststart".to_string()
+}
+
+/// What a sharing platform accepts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct PlatformPreset {
+    /// Taken from the table name when read from the config file
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Upload limit; also the default target size
+    pub max_size: FileSize,
+    /// Largest frame, in either orientation
+    #[serde(default)]
+    pub max_resolution: Option<Resolution>,
+    #[serde(default)]
+    pub max_fps: Option<f32>,
+    /// Pad the video to this frame shape
+    #[serde(default)]
+    pub aspect_ratio: Option<AspectRatio>,
+    #[serde(default)]
+    pub video_codec: VideoCodec,
+    /// Codec profile, e.g. `high` or `main`
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Highest codec level, e.g. `4.1`
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default = "default_audio_codec")]
+    pub audio_codec: String,
+    #[serde(default)]
+    pub max_audio_bitrate_kbps: Option<u32>,
+    /// Value of `-movflags`
+    #[serde(default = "default_movflags")]
+    pub movflags: String,
+}
+
+impl PlatformPreset {
+    /// The presets that ship with small-mp4
+    pub fn built_in() -> Vec<PlatformPreset> {
+        vec![
+            PlatformPreset {
+                name: "chat-8mb".to_string(),
+                description: "Chat apps with an 8 MB upload limit".to_string(),
+                max_size: FileSize::from_mb(8.0),
+                max_resolution: Some(Resolution::new(1920, 1080)),
+                max_fps: Some(60.0),
+                aspect_ratio: None,
+                video_codec: VideoCodec::H264,
+                profile: Some("high".to_string()),
+                level: Some("4.2".to_string()),
+                audio_codec: default_audio_codec(),
+                max_audio_bitrate_kbps: Some(128),
+                movflags: default_movflags(),
+            },
+            PlatformPreset {
+                name: "email-25mb".to_string(),
+                description: "Email attachments up to 25 MB, playable everywhere".to_string(),
+                max_size: FileSize::from_mb(25.0),
+                max_resolution: Some(Resolution::new(1280, 720)),
+                max_fps: Some(30.0),
+                aspect_ratio: None,
+                video_codec: VideoCodec::H264,
+                profile: Some("main".to_string()),
+                level: Some("3.1".to_string()),
+                audio_codec: default_audio_codec(),
+                max_audio_bitrate_kbps: Some(128),
+                movflags: default_movflags(),
+            },
+            PlatformPreset {
+                name: "short-video".to_string(),
+                description: "Vertical 9:16 short videos".to_string(),
+                max_size: FileSize::from_mb(50.0),
+                max_resolution: Some(Resolution::new(1080, 1920)),
+                max_fps: Some(60.0),
+                aspect_ratio: Some(AspectRatio { width: 9, height: 16 }),
+                video_codec: VideoCodec::H264,
+                profile: Some("high".to_string()),
+                level: Some("4.2".to_string()),
+                audio_codec: default_audio_codec(),
+                max_audio_bitrate_kbps: Some(128),
+                movflags: default_movflags(),
+            },
+        ]
+    }
+
+    /// Built-in preset called `name`
+    pub fn find_built_in(name: &str) -> Option<PlatformPreset> {
+        Self::built_in().into_iter().find(|preset| preset.name == name)
+    }
+
+    /// Output frame for a `width`x`height` source: padded to the aspect
+    /// ratio, then scaled down to fit the maximum resolution, with even sides
+    pub fn frame_for(&self, width: u32, height: u32) -> (u32, u32) {
+        let (mut frame_width, mut frame_height) = (width as f64, height as f64);
+
+        if let Some(aspect) = self.aspect_ratio {
+            let ratio = aspect.width as f64 / aspect.height as f64;
+            if frame_width / frame_height > ratio {
+                frame_height = frame_width / ratio;
+            } else {
+                frame_width = frame_height * ratio;
+            }
+        }
+
+        if let Some(max) = self.max_resolution {
+            let (long, short) = (frame_width.max(frame_height), frame_width.min(frame_height));
+            let max_long = max.width.max(max.height) as f64;
+            let max_short = max.width.min(max.height) as f64;
+            let factor = (max_long / long).min(max_short / short).min(1.0);
+            frame_width *= factor;
+            frame_height *= factor;
+        }
+
+        let even = |value: f64| ((value / 2.0).round() as u32 * 2).max(2);
+        (even(frame_width), even(frame_height))
+    }
+
+    /// Ways `output` (of `output_size`) breaks this preset's rules; empty
+    /// when it complies
+    pub fn check(&self, output_size: FileSize, output: &VideoMetadata) -> Vec<String> {
+        let mut violations = Vec::new();
+
+        if output_size > self.max_size {
+            violations.push(format!("{} is over the {} limit", output_size, self.max_size));
+        }
+        if let Some(max) = self.max_resolution {
+            if !max.admits(output.width, output.height) {
+                violations.push(format!("{}x{} is larger than {}", output.width, output.height, max));
+            }
+        }
+        if let Some(aspect) = self.aspect_ratio {
+            let expected = aspect.width as f32 / aspect.height as f32;
+            if (output.aspect_ratio() - expected).abs() > 0.01 {
+                violations.push(format!("{}x{} is not {}", output.width, output.height, aspect));
+            }
+        }
+        if let Some(max_fps) = self.max_fps {
+            if output.fps > max_fps + 0.01 {
+                violations.push(format!("{:.2} fps is above {} fps", output.fps, max_fps));
+            }
+        }
+        if output.codec != self.video_codec.ffprobe_name() {
+            violations.push(format!("video codec is {}, not {}", output.codec, self.video_codec.ffprobe_name()));
+        }
+        if let Some(profile) = &self.profile {
+            let actual = output.profile.as_deref().unwrap_or("unknown");
+            if !actual.eq_ignore_ascii_case(profile) {
+                violations.push(format!("video profile is {}, not {}", actual, profile));
+            }
+        }
+        if let Some(level) = &self.level {
+            let limit = self.video_codec.ffprobe_level(level);
+            if let (Some(actual), Some(limit)) = (output.level, limit) {
+                if actual > limit {
+                    violations.push(format!("video level {} is above {}", actual, level));
+                }
+            }
+        }
+        if let Some(audio_codec) = &output.audio_codec {
+            if *audio_codec != self.audio_codec {
+                violations.push(format!("audio codec is {}, not {}", audio_codec, self.audio_codec));
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_fits_limits_in_either_orientation() {
+        let email = PlatformPreset::find_built_in("email-25mb").unwrap();
+        assert_eq!(email.frame_for(1920, 1080), (1280, 720));
+        assert_eq!(email.frame_for(1080, 1920), (720, 1280));
+        assert_eq!(email.frame_for(640, 360), (640, 360));
+
+        // Landscape sources are letterboxed into a 9:16 frame
+        let short = PlatformPreset::find_built_in("short-video").unwrap();
+        assert_eq!(short.frame_for(1920, 1080), (1080, 1920));
+        assert_eq!(short.frame_for(720, 1280), (720, 1280));
+    }
+
+    #[test]
+    fn test_check_reports_each_violation() {
+        let email = PlatformPreset::find_built_in("email-25mb").unwrap();
+        let compliant = VideoMetadata {
+            width: 1280,
+            height: 720,
+            level: Some(31),
+            profile: Some("Main".to_string()),
+            ..VideoMetadata::default_hd()
+        };
+        assert!(email.check(FileSize::from_mb(20.0), &compliant).is_empty());
+
+        let violations = email.check(FileSize::from_mb(30.0), &VideoMetadata::default_hd());
+        assert_eq!(violations.len(), 4, "{:?}", violations);
+        assert!(violations[0].contains("over the 25 MB limit"));
+        assert!(violations[1].contains("1920x1080 is larger than 1280x720"));
+    }
+
+    #[test]
+    fn test_preset_from_toml_fields() {
+        let preset: PlatformPreset = serde_json::from_value(serde_json::json!({
+            "max-size": "10mb",
+            "max-resolution": "1280x720",
+            "aspect-ratio": "1:1",
+        }))
+        .unwrap();
+        assert_eq!(preset.max_size, FileSize::from_mb(10.0));
+        assert_eq!(preset.max_resolution, Some(Resolution::new(1280, 720)));
+        assert_eq!(preset.audio_codec, "aac");
+        assert_eq!(preset.movflags, "+faststart");
+
+        assert!("1920".parse::<Resolution>().is_err());
+        assert!("0:1".parse::<AspectRatio>().is_err());
+    }
+}
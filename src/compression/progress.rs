@@ -0,0 +1,181 @@
+//! Progress reporting for running jobs.
+//!
+//! The engine runs FFmpeg with `-progress pipe:2 -nostats`, which prints
+//! blocks of `key=value` lines terminated by `progress=continue` or
+//! `progress=end`. [`ProgressParser`] folds those blocks into
+//! [`ProgressUpdate`]s that are sent to the caller as [`ProgressEvent`]s,
+//! together with stage changes.
+
+use serde::Serialize;
+use std::time::Duration;
+
+use super::hardware::HardwareEncoder;
+use super::FileSize;
+
+/// Phase of a compression job.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum EncodeStage {
+    /// Reading the input's metadata with ffprobe
+    Probing,
+    /// Analysis pass of a two-pass encode; nothing is written
+    FirstPass,
+    /// Final pass of a two-pass encode
+    SecondPass,
+    /// Single-pass encode
+    Encoding,
+    /// Measuring the output after FFmpeg finished
+    Finalizing,
+    /// The previous encoder failed and the job restarts with `encoder`
+    FallbackRetry { encoder: HardwareEncoder, attempt: u32 },
+}
+
+impl EncodeStage {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            EncodeStage::Probing => "Probing",
+            EncodeStage::FirstPass => "First pass",
+            EncodeStage::SecondPass => "Second pass",
+            EncodeStage::Encoding => "Encoding",
+            EncodeStage::Finalizing => "Finalizing",
+            EncodeStage::FallbackRetry { .. } => "Retrying with fallback encoder",
+        }
+    }
+}
+
+/// One snapshot from FFmpeg's progress stream.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ProgressUpdate {
+    /// Progress of the whole encode (both passes), 0.0 to 1.0
+    pub fraction: f32,
+    /// Estimated time until the encode finishes
+    pub eta: Option<Duration>,
+    pub frames_processed: u64,
+    /// Expected number of output frames, from duration and frame rate
+    pub total_frames: Option<u64>,
+    pub fps: Option<f32>,
+    /// Encoding speed relative to real time (2.0 = twice as fast)
+    pub speed: Option<f32>,
+    pub bitrate_kbps: Option<f32>,
+    /// Bytes written so far; zero during an analysis pass
+    pub output_size: FileSize,
+    /// Position in the output timeline
+    pub out_time: Duration,
+    /// FFmpeg reported `progress=end` for the current pass
+    pub pass_finished: bool,
+}
+
+/// Sending half of a job's progress channel
+pub type ProgressSender = tokio::sync::mpsc::UnboundedSender<ProgressEvent>;
+
+/// Event sent on a job's progress channel.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ProgressEvent {
+    Stage(EncodeStage),
+    Progress(ProgressUpdate),
+}
+
+/// Accumulates `-progress` key/value lines into [`ProgressUpdate`]s.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressParser {
+    current: ProgressUpdate,
+}
+
+impl ProgressParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one line; returns a snapshot when a block is complete.
+    ///
+    /// `fraction` and `eta` are left for the caller, which knows the
+    /// duration and how this pass fits into the whole job.
+    pub fn push_line(&mut self, line: &str) -> Option<ProgressUpdate> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+
+        match key {
+            "frame" => self.current.frames_processed = value.parse().unwrap_or(self.current.frames_processed),
+            "fps" => self.current.fps = value.parse().ok().filter(|fps: &f32| *fps > 0.0),
+            "bitrate" => self.current.bitrate_kbps = value.trim_end_matches("kbits/s").parse().ok(),
+            "total_size" => {
+                if let Ok(bytes) = value.parse() {
+                    self.current.output_size = FileSize::from_bytes(bytes);
+                }
+            },
+            // Both are microseconds; out_time_ms is misnamed in FFmpeg
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<u64>() {
+                    self.current.out_time = Duration::from_micros(us);
+                }
+            },
+            "speed" => self.current.speed = value.trim_end_matches('x').parse().ok(),
+            "progress" => {
+                self.current.pass_finished = value == "end";
+                return Some(self.current.clone());
+            },
+            _ => {}
+        }
+
+        None
+    }
+}
+
+/// Estimated time left when `fraction` of the work took `elapsed`
+pub(crate) fn estimate_remaining(fraction: f32, elapsed: Duration) -> Option<Duration> {
+    if fraction > 0.0 && fraction < 1.0 {
+        let total_time = elapsed.as_secs_f64() / fraction as f64;
+        let remaining_time = total_time - elapsed.as_secs_f64();
+
+        if remaining_time > 0.0 {
+            return Some(Duration::from_secs_f64(remaining_time));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str = "frame=450\nfps=89.5\nstream_0_0_q=28.0\nbitrate= 812.4kbits/s\ntotal_size=1523456\n\
+                         out_time_us=15000000\nout_time_ms=15000000\nout_time=00:00:15.000000\n\
+                         dup_frames=0\ndrop_frames=0\nspeed=2.98x\nprogress=continue";
+
+    #[test]
+    fn test_parses_progress_block() {
+        let mut parser = ProgressParser::new();
+        let updates: Vec<_> = BLOCK.lines().filter_map(|line| parser.push_line(line)).collect();
+
+        assert_eq!(updates.len(), 1);
+        let update = &updates[0];
+        assert_eq!(update.frames_processed, 450);
+        assert_eq!(update.fps, Some(89.5));
+        assert_eq!(update.bitrate_kbps, Some(812.4));
+        assert_eq!(update.output_size.as_bytes(), 1_523_456);
+        assert_eq!(update.out_time, Duration::from_secs(15));
+        assert_eq!(update.speed, Some(2.98));
+        assert!(!update.pass_finished);
+    }
+
+    #[test]
+    fn test_unavailable_values_and_end() {
+        let mut parser = ProgressParser::new();
+        for line in ["frame=0", "fps=0.00", "bitrate=N/A", "total_size=N/A", "speed=N/A"] {
+            assert_eq!(parser.push_line(line), None);
+        }
+
+        let update = parser.push_line("progress=end").unwrap();
+        assert_eq!(update.fps, None);
+        assert_eq!(update.bitrate_kbps, None);
+        assert_eq!(update.speed, None);
+        assert!(update.pass_finished);
+    }
+
+    #[test]
+    fn test_estimate_remaining() {
+        assert_eq!(estimate_remaining(0.5, Duration::from_secs(10)), Some(Duration::from_secs(10)));
+        assert_eq!(estimate_remaining(0.0, Duration::from_secs(10)), None);
+        assert_eq!(estimate_remaining(1.0, Duration::from_secs(10)), None);
+    }
+}
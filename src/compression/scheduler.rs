@@ -0,0 +1,334 @@
+//! Running several compression jobs at once.
+//!
+//! [`JobScheduler`] starts jobs as soon as [`JobLimits`] allow: no more than
+//! `max_jobs` encodes overall, which keeps CPU and memory use in check, and no
+//! more NVENC encodes than the GPUs have sessions for. Every job runs on its
+//! own [`CompressionEngine`] taken from a pool, so concurrent jobs never share
+//! state while encoder fallback history still carries over to later jobs.
+
+use anyhow::{Result, anyhow};
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+
+use super::cancel::{CancellationToken, Cancelled};
+use super::engine::{CompressionEngine, CompressionResult, DryRun};
+use super::ffmpeg::{FfmpegRunner, SystemFfmpegRunner};
+use super::hardware::{HardwareCapabilities, HardwareEncoder};
+use super::progress::ProgressSender;
+use super::{CompressionSettings, EncodingPlan};
+use crate::utils::system_info::SystemInformation;
+
+/// How many jobs may run at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JobLimits {
+    /// Jobs running at once, whatever their encoder
+    pub max_jobs: usize,
+    /// NVENC encodes running at once, summed over all NVENC-capable GPUs
+    pub nvenc_sessions: usize,
+}
+
+impl JobLimits {
+    /// Limits for this machine, using the job count recommended for its
+    /// CPU and memory.
+    pub fn detect(capabilities: &HardwareCapabilities) -> Self {
+        let performance = SystemInformation::collect().estimate_encoding_performance();
+        info!("Running up to {} compression job(s) at once", performance.recommended_concurrent_jobs);
+        Self::new(performance.recommended_concurrent_jobs, capabilities)
+    }
+
+    /// `max_jobs` at once, with NVENC limited by the sessions of the detected GPUs
+    pub fn new(max_jobs: usize, capabilities: &HardwareCapabilities) -> Self {
+        let nvenc_sessions: usize = capabilities.cuda_devices.iter()
+            .filter(|device| device.nvenc_support)
+            .map(|device| device.max_concurrent_sessions as usize)
+            .sum();
+
+        Self {
+            max_jobs: max_jobs.max(1),
+            // NVENC may be usable without a detected CUDA device; allow one session
+            nvenc_sessions: nvenc_sessions.max(1),
+        }
+    }
+
+    /// One job at a time
+    pub fn sequential() -> Self {
+        Self { max_jobs: 1, nvenc_sessions: 1 }
+    }
+}
+
+/// A file to compress.
+#[derive(Debug, Clone)]
+pub struct CompressionJob {
+    pub input: PathBuf,
+    /// Defaults to `<input>_compressed.mp4`
+    pub output: Option<PathBuf>,
+    pub settings: CompressionSettings,
+}
+
+/// Runs compression jobs concurrently within [`JobLimits`].
+///
+/// Clones share the same limits and engine pool, so one scheduler can serve
+/// a whole application.
+#[derive(Clone)]
+pub struct JobScheduler {
+    capabilities: HardwareCapabilities,
+    runner: Arc<dyn FfmpegRunner>,
+    limits: JobLimits,
+    job_slots: Arc<Semaphore>,
+    nvenc_sessions: Arc<Semaphore>,
+    engines: Arc<Mutex<Vec<CompressionEngine>>>,
+}
+
+impl JobScheduler {
+    pub fn new(capabilities: HardwareCapabilities, limits: JobLimits) -> Self {
+        Self::with_runner(capabilities, limits, Arc::new(SystemFfmpegRunner))
+    }
+
+    /// Create a scheduler whose engines execute FFmpeg through `runner`
+    pub fn with_runner(capabilities: HardwareCapabilities, limits: JobLimits, runner: Arc<dyn FfmpegRunner>) -> Self {
+        Self {
+            capabilities,
+            runner,
+            limits,
+            job_slots: Arc::new(Semaphore::new(limits.max_jobs)),
+            nvenc_sessions: Arc::new(Semaphore::new(limits.nvenc_sessions)),
+            engines: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn limits(&self) -> JobLimits {
+        self.limits
+    }
+
+    /// Plan an encode of `input_path` without running it
+    pub async fn plan(&self, input_path: &Path, settings: &CompressionSettings) -> Result<EncodingPlan> {
+        let engine = self.take_engine();
+        let plan = engine.plan(input_path, settings).await;
+        self.return_engine(engine);
+        plan
+    }
+
+    /// Resolve the plan, fallbacks and FFmpeg invocations of `job` without running it
+    pub async fn dry_run(&self, job: &CompressionJob) -> Result<DryRun> {
+        let engine = self.take_engine();
+        let dry_run = engine.dry_run(&job.input, job.output.as_deref(), &job.settings).await;
+        self.return_engine(engine);
+        dry_run
+    }
+
+    /// Run `job` once a slot (and an NVENC session, if needed) is free.
+    ///
+    /// A job still waiting when `cancel` is cancelled never starts and
+    /// returns [`Cancelled`].
+    pub async fn run(
+        &self,
+        job: &CompressionJob,
+        progress_tx: Option<ProgressSender>,
+        cancel: &CancellationToken,
+    ) -> Result<CompressionResult> {
+        let _permit = self.acquire(job.settings.hardware_encoder).await?;
+
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+
+        let mut engine = self.take_engine();
+        let result = engine.compress_cancellable(
+            &job.input, job.output.as_deref(), &job.settings, progress_tx, cancel,
+        ).await;
+        self.return_engine(engine);
+
+        result
+    }
+
+    /// Run all `jobs` concurrently within the limits.
+    ///
+    /// `on_finished` is called with each job's index as it completes; the
+    /// results are returned in the order of `jobs`.
+    pub async fn run_all<F>(
+        &self,
+        jobs: Vec<CompressionJob>,
+        cancel: &CancellationToken,
+        mut on_finished: F,
+    ) -> Vec<Result<CompressionResult>>
+    where
+        F: FnMut(usize, &Result<CompressionResult>),
+    {
+        let mut results: Vec<Option<Result<CompressionResult>>> = jobs.iter().map(|_| None).collect();
+
+        let mut tasks = JoinSet::new();
+        for (index, job) in jobs.into_iter().enumerate() {
+            let scheduler = self.clone();
+            let cancel = cancel.clone();
+            tasks.spawn(async move {
+                let result = scheduler.run(&job, None, &cancel).await;
+                (index, result)
+            });
+        }
+
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((index, result)) => {
+                    on_finished(index, &result);
+                    results[index] = Some(result);
+                }
+                Err(e) => error!("Compression task failed to complete: {}", e),
+            }
+        }
+
+        results.into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow!("Compression task failed to complete"))))
+            .collect()
+    }
+
+    /// Wait until a job using `encoder` may start
+    async fn acquire(&self, encoder: HardwareEncoder) -> Result<JobPermit> {
+        // Sessions first: a job waiting for the GPU shouldn't hold a CPU slot
+        let session = if encoder.is_nvenc() {
+            Some(self.nvenc_sessions.clone().acquire_owned().await?)
+        } else {
+            None
+        };
+        let slot = self.job_slots.clone().acquire_owned().await?;
+
+        Ok(JobPermit { _slot: slot, _session: session })
+    }
+
+    fn take_engine(&self) -> CompressionEngine {
+        self.engines.lock().unwrap().pop()
+            .unwrap_or_else(|| CompressionEngine::with_runner(self.capabilities.clone(), self.runner.clone()))
+    }
+
+    fn return_engine(&self, engine: CompressionEngine) {
+        self.engines.lock().unwrap().push(engine);
+    }
+}
+
+/// Held while a job runs; dropping it lets the next job start
+struct JobPermit {
+    _slot: OwnedSemaphorePermit,
+    _session: Option<OwnedSemaphorePermit>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::hardware::CudaDevice;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn nvenc_capabilities(sessions: u32) -> HardwareCapabilities {
+        let mut capabilities = HardwareCapabilities::software_only();
+        capabilities.cuda_devices.push(CudaDevice {
+            id: 0,
+            name: "Test GPU".to_string(),
+            compute_capability: (8, 6),
+            memory_mb: 8192,
+            nvenc_support: true,
+            max_concurrent_sessions: sessions,
+        });
+        capabilities
+    }
+
+    #[test]
+    fn test_limits_from_gpus() {
+        let limits = JobLimits::new(4, &nvenc_capabilities(3));
+        assert_eq!(limits, JobLimits { max_jobs: 4, nvenc_sessions: 3 });
+
+        let limits = JobLimits::new(0, &HardwareCapabilities::software_only());
+        assert_eq!(limits, JobLimits::sequential());
+    }
+
+    /// Peak number of jobs holding a permit at once, overall and for NVENC
+    async fn peak_concurrency(scheduler: &JobScheduler, encoders: &[HardwareEncoder]) -> (usize, usize) {
+        #[derive(Default)]
+        struct Counters {
+            running: AtomicUsize,
+            running_nvenc: AtomicUsize,
+            peak: AtomicUsize,
+            peak_nvenc: AtomicUsize,
+        }
+        let counters = Arc::new(Counters::default());
+
+        let mut tasks = JoinSet::new();
+        for &encoder in encoders {
+            let scheduler = scheduler.clone();
+            let counters = counters.clone();
+            tasks.spawn(async move {
+                let _permit = scheduler.acquire(encoder).await.unwrap();
+                let running = counters.running.fetch_add(1, Ordering::SeqCst) + 1;
+                counters.peak.fetch_max(running, Ordering::SeqCst);
+                if encoder.is_nvenc() {
+                    let running = counters.running_nvenc.fetch_add(1, Ordering::SeqCst) + 1;
+                    counters.peak_nvenc.fetch_max(running, Ordering::SeqCst);
+                }
+
+                tokio::time::sleep(Duration::from_millis(20)).await;
+
+                if encoder.is_nvenc() {
+                    counters.running_nvenc.fetch_sub(1, Ordering::SeqCst);
+                }
+                counters.running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+
+        (counters.peak.load(Ordering::SeqCst), counters.peak_nvenc.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_jobs_respect_cpu_and_session_limits() {
+        let capabilities = nvenc_capabilities(2);
+        let scheduler = JobScheduler::new(capabilities.clone(), JobLimits::new(3, &capabilities));
+
+        let (peak, peak_nvenc) = peak_concurrency(&scheduler, &[HardwareEncoder::NvencH264; 6]).await;
+        assert_eq!((peak, peak_nvenc), (2, 2));
+
+        let mut encoders = vec![HardwareEncoder::NvencH265; 4];
+        encoders.extend([HardwareEncoder::Software; 4]);
+        let (peak, peak_nvenc) = peak_concurrency(&scheduler, &encoders).await;
+        assert_eq!(peak, 3);
+        assert!(peak_nvenc <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_run_all_returns_results_in_job_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let scheduler = JobScheduler::new(HardwareCapabilities::software_only(), JobLimits { max_jobs: 2, nvenc_sessions: 1 });
+        let jobs: Vec<_> = (0..3)
+            .map(|index| CompressionJob {
+                input: dir.path().join(format!("missing-{}.mov", index)),
+                output: None,
+                settings: CompressionSettings::default(),
+            })
+            .collect();
+
+        let mut finished = Vec::new();
+        let results = scheduler.run_all(jobs, &CancellationToken::new(), |index, _| finished.push(index)).await;
+
+        finished.sort();
+        assert_eq!(finished, [0, 1, 2]);
+        for (index, result) in results.iter().enumerate() {
+            let error = result.as_ref().unwrap_err().to_string();
+            assert!(error.contains(&format!("missing-{}.mov", index)), "{}", error);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs_do_not_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.mov");
+        std::fs::write(&input, vec![0u8; 4000]).unwrap();
+        let scheduler = JobScheduler::new(HardwareCapabilities::software_only(), JobLimits::sequential());
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let job = CompressionJob { input, output: None, settings: CompressionSettings::default() };
+
+        let error = scheduler.run(&job, None, &cancel).await.unwrap_err();
+        assert!(error.is::<Cancelled>());
+    }
+}
@@ -0,0 +1,21 @@
+//! Serde helpers shared by the compression types.
+
+/// Serialize `$type` through its `Display` impl and deserialize it through
+/// `FromStr`, so it reads and writes as the same string the CLI accepts.
+macro_rules! string_serde {
+    ($type:ty) => {
+        impl serde::Serialize for $type {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $type {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <String as serde::Deserialize>::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+pub(crate) use string_serde;
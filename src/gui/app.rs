@@ -2,17 +2,24 @@ use eframe::egui::{self};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
-use crate::compression::{CompressionEngine, TargetSize};
-use crate::compression::hardware::HardwareCapabilities;
-use super::components::{DropZone, SizeSlider, PreviewPanel, ProgressBar};
-use super::state::{AppState, CompressionStatus};
-use super::{GuiConfig, Language};
+use small_mp4::compression::{CancellationToken, CompressionJob, CompressionSettings, FileSize, JobLimits, JobScheduler, TargetSize, VideoEdit, VideoMetadata, DEFAULT_QUALITY_LEVEL, MAX_QUALITY_LEVEL};
+use small_mp4::compression::hardware::{HardwareCapabilities, HardwareQuality};
+use super::components::{output_timestamp, DropZone, EditOutcome, EditPanel, SizeSlider, PreviewPanel, ProgressBar, ThumbnailGenerator};
+use super::settings::SavedSettings;
+use super::queue::{ItemStatus, QueueItem};
+use super::state::{AppState, CompressionStage, CompressionStatus};
+use super::{GuiConfig, Theme};
+use crate::cli::CompressionCliSettings;
+use crate::i18n;
 
 pub struct SmallMp4App {
     pub config: GuiConfig,
     pub state: Arc<Mutex<AppState>>,
     pub hardware_capabilities: Arc<Mutex<Option<HardwareCapabilities>>>,
-    pub compression_engine: Arc<Mutex<Option<CompressionEngine>>>,
+    pub scheduler: Option<JobScheduler>,
+    
+    // Config file profiles a queue item can use instead of the current settings
+    profiles: Vec<(String, CompressionSettings)>,
     
     // UI Components
     drop_zone: DropZone,
@@ -20,59 +27,121 @@ pub struct SmallMp4App {
     preview_panel: PreviewPanel,
     progress_bar: ProgressBar,
     
+    // Text typed into the custom target size field
+    custom_size_input: String,
+    
     // Advanced settings (hidden by default)
     show_advanced: bool,
     show_about: bool,
     
+    // Input and, once compressed, output shown in the comparison window,
+    // with the edit the output was made with
+    preview: Option<(PathBuf, Option<PathBuf>, VideoEdit)>,
+    // Trim, crop and rotation of the queue item being edited
+    edit_panel: Option<EditPanel>,
+    
     // Drag and drop support
     dropped_files: Vec<PathBuf>,
+    
+    // Settings from the config file, restored by "reset to defaults"
+    default_settings: SavedSettings,
+    // Where the GUI's own settings are remembered, if anywhere
+    settings_path: Option<PathBuf>,
 }
 
 impl Default for SmallMp4App {
     fn default() -> Self {
+        let config = GuiConfig::default();
+        let state = AppState::default();
+        let default_settings = SavedSettings::capture(&config, &state);
         Self {
-            config: GuiConfig::default(),
-            state: Arc::new(Mutex::new(AppState::default())),
+            config,
+            state: Arc::new(Mutex::new(state)),
             hardware_capabilities: Arc::new(Mutex::new(None)),
-            compression_engine: Arc::new(Mutex::new(None)),
+            scheduler: None,
+            profiles: Vec::new(),
             drop_zone: DropZone::default(),
             size_slider: SizeSlider::default(),
             preview_panel: PreviewPanel::default(),
             progress_bar: ProgressBar::default(),
+            custom_size_input: String::new(),
             show_advanced: false,
             show_about: false,
+            preview: None,
+            edit_panel: None,
             dropped_files: Vec::new(),
+            default_settings,
+            settings_path: None,
         }
     }
 }
 
 impl SmallMp4App {
-    pub fn new(hw_capabilities: HardwareCapabilities) -> Self {
-        let app = Self::default();
+    /// With `restore_saved`, the settings of the last session replace those
+    /// from the config file
+    pub fn new(
+        hw_capabilities: HardwareCapabilities,
+        settings: &CompressionCliSettings,
+        profiles: &[(String, CompressionCliSettings)],
+        restore_saved: bool,
+    ) -> Self {
+        let mut app = Self::default();
         
         // Set hardware capabilities
         *app.hardware_capabilities.lock().unwrap() = Some(hw_capabilities.clone());
         
-        // Initialize compression engine
-        *app.compression_engine.lock().unwrap() = Some(CompressionEngine::new(hw_capabilities.clone()));
+        // Jobs share one scheduler so they stay within the CPU and NVENC limits
+        app.scheduler = Some(JobScheduler::new(hw_capabilities.clone(), JobLimits::detect(&hw_capabilities)));
+        
+        // Start from the config file's settings, on the available hardware by default
+        if let Ok(mut state_guard) = app.state.lock() {
+            state_guard.compression_settings = settings.apply(CompressionSettings::builder())
+                .build_with_capabilities(&hw_capabilities);
+            log::info!("Setting default hardware encoder to: {:?}", state_guard.compression_settings.hardware_encoder);
+        }
+        app.profiles = profiles.iter()
+            .map(|(name, profile)| {
+                let settings = profile.apply(CompressionSettings::builder()).build_with_capabilities(&hw_capabilities);
+                (name.clone(), settings)
+            })
+            .collect();
+        if let Some(language) = &settings.lang {
+            app.config.language = language.clone();
+        }
         
-        // Update compression settings to use available hardware by default
         if let Ok(mut state_guard) = app.state.lock() {
-            if let Some(preferred_encoder) = &hw_capabilities.preferred_encoder {
-                log::info!("Setting default hardware encoder to: {:?}", preferred_encoder);
-                state_guard.compression_settings.hardware_encoder = preferred_encoder.clone();
-                state_guard.compression_settings.enable_hardware_accel = true;
+            app.default_settings = SavedSettings::capture(&app.config, &state_guard);
+            
+            app.settings_path = SavedSettings::default_path();
+            let saved = app.settings_path.as_deref().map(SavedSettings::load).transpose();
+            match saved {
+                Ok(Some(Some(saved))) if restore_saved => {
+                    saved.restore(&mut app.config, &mut state_guard, &hw_capabilities);
+                    log::info!("Restored the settings of the last session");
+                }
+                // A profile chosen on the command line wins, but stays unremembered
+                Ok(Some(Some(saved))) => app.config.remember_settings = saved.remember_settings,
+                Ok(_) => {}
+                Err(e) => log::warn!("{}", e),
             }
         }
         
         app
     }
 
-    pub fn new_with_context(cc: &eframe::CreationContext<'_>, hw_capabilities: HardwareCapabilities) -> Self {
+    pub fn new_with_context(
+        cc: &eframe::CreationContext<'_>,
+        hw_capabilities: HardwareCapabilities,
+        settings: &CompressionCliSettings,
+        profiles: &[(String, CompressionCliSettings)],
+        restore_saved: bool,
+    ) -> Self {
         // Configure fonts for international support
         Self::setup_fonts(&cc.egui_ctx);
         
-        Self::new(hw_capabilities)
+        let app = Self::new(hw_capabilities, settings, profiles, restore_saved);
+        cc.egui_ctx.set_theme(app.config.theme.preference());
+        app
     }
     
     fn setup_fonts(ctx: &egui::Context) {
@@ -121,104 +190,51 @@ impl SmallMp4App {
     }
     
     fn get_text(&self, key: &str) -> String {
-        match self.config.language {
-            Language::Korean => match key {
-                "title" => "작은mp4 - 동영상 압축기".to_string(),
-                "drag_drop" => "동영상 파일을 선택해주세요".to_string(),
-                "browse" => "찾아보기".to_string(),
-                "target_size" => "목표 크기:".to_string(),
-                "auto" => "자동".to_string(),
-                "compress" => "압축하기".to_string(),
-                "stop" => "중지".to_string(),
-                "cancel" => "취소".to_string(),
-                "original" => "원본".to_string(),
-                "preview" => "미리보기".to_string(),
-                "advanced" => "고급 설정".to_string(),
-                "about" => "정보".to_string(),
-                "language" => "언어".to_string(),
-                "hardware_acceleration" => "하드웨어 가속:".to_string(),
-                "available_encoders" => "사용 가능한 인코더".to_string(),
-                "recommended" => "권장".to_string(),
-                "detecting_hardware" => "🔍 하드웨어 감지 중...".to_string(),
-                "hardware_detection_progress" => "⚙️ 하드웨어 감지 진행 중...".to_string(),
-                "enable_hardware_accel" => "하드웨어 가속 활성화".to_string(),
-                "memory_optimization" => "메모리 최적화".to_string(),
-                "advanced_settings" => "고급 설정".to_string(),
-                "compatibility_mode" => "호환성 모드 (x264 only)".to_string(),
-                "remove_audio" => "사운드 제거".to_string(),
-                _ => key.to_string(),
-            },
-            Language::Japanese => match key {
-                "title" => "小さなmp4 - 動画圧縮ツール".to_string(), 
-                "drag_drop" => "動画ファイルを選択してください".to_string(),
-                "browse" => "参照".to_string(),
-                "target_size" => "目標サイズ:".to_string(),
-                "auto" => "自動".to_string(),
-                "compress" => "圧縮".to_string(),
-                "stop" => "停止".to_string(), 
-                "cancel" => "キャンセル".to_string(),
-                "original" => "元の動画".to_string(),
-                "preview" => "プレビュー".to_string(),
-                "advanced" => "詳細設定".to_string(),
-                "about" => "について".to_string(),
-                "language" => "言語".to_string(),
-                "hardware_acceleration" => "ハードウェアアクセラレーション:".to_string(),
-                "available_encoders" => "利用可能エンコーダー".to_string(),
-                "recommended" => "推奨".to_string(),
-                "detecting_hardware" => "🔍 ハードウェア検出中...".to_string(),
-                "hardware_detection_progress" => "⚙️ ハードウェア検出進行中...".to_string(),
-                "enable_hardware_accel" => "ハードウェアアクセラレーション有効化".to_string(),
-                "memory_optimization" => "メモリ最適化".to_string(),
-                "advanced_settings" => "詳細設定".to_string(),
-                "compatibility_mode" => "互換性モード (x264のみ)".to_string(),
-                "remove_audio" => "音声を削除".to_string(),
-                _ => key.to_string(),
-            },
-            Language::English => match key {
-                "title" => "Small MP4 - Video Compressor".to_string(),
-                "drag_drop" => "Please select a video file".to_string(),
-                "browse" => "Browse...".to_string(),
-                "target_size" => "Target Size:".to_string(),
-                "auto" => "Auto".to_string(),
-                "compress" => "Compress".to_string(),
-                "stop" => "Stop".to_string(),
-                "cancel" => "Cancel".to_string(), 
-                "original" => "Original".to_string(),
-                "preview" => "Preview".to_string(),
-                "advanced" => "Advanced".to_string(),
-                "about" => "About".to_string(),
-                "language" => "Language".to_string(),
-                "hardware_acceleration" => "Hardware Acceleration:".to_string(),
-                "available_encoders" => "Available Encoders".to_string(),
-                "recommended" => "Recommended".to_string(),
-                "detecting_hardware" => "🔍 Detecting hardware...".to_string(),
-                "hardware_detection_progress" => "⚙️ Hardware detection in progress...".to_string(),
-                "enable_hardware_accel" => "Enable hardware acceleration".to_string(),
-                "memory_optimization" => "Memory optimization".to_string(),
-                "advanced_settings" => "Advanced Settings".to_string(),
-                "compatibility_mode" => "Compatibility mode (x264 only)".to_string(),
-                "remove_audio" => "Remove audio".to_string(),
-                _ => key.to_string(),
-            },
+        i18n::text_in(&self.config.language, key, &[])
+    }
+    
+    /// Remember the current settings for the next launch
+    fn save_settings(&self) {
+        let Some(path) = &self.settings_path else { return };
+        let Ok(state_guard) = self.state.lock() else { return };
+        
+        // Without remember_settings only the flag itself is kept
+        let saved = if self.config.remember_settings {
+            SavedSettings::capture(&self.config, &state_guard)
+        } else {
+            SavedSettings { remember_settings: false, ..self.default_settings.clone() }
+        };
+        if let Err(e) = saved.save(path) {
+            log::warn!("Could not save the GUI settings: {}", e);
         }
     }
+    
+    /// Back to the settings the app started with, before restoring the last session
+    fn reset_settings(&mut self, ctx: &egui::Context) {
+        let capabilities = self.hardware_capabilities.lock().ok()
+            .and_then(|caps| caps.clone())
+            .unwrap_or_else(HardwareCapabilities::software_only);
+        if let Ok(mut state_guard) = self.state.lock() {
+            self.default_settings.restore(&mut self.config, &mut state_guard, &capabilities);
+        }
+        self.custom_size_input.clear();
+        ctx.set_theme(self.config.theme.preference());
+        log::info!("Settings reset to defaults");
+    }
 }
 
 impl eframe::App for SmallMp4App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Handle dropped files
-        ctx.input(|i| {
-            if !i.raw.dropped_files.is_empty() {
-                for file in &i.raw.dropped_files {
-                    if let Some(path) = &file.path {
-                        if let Ok(mut state_guard) = self.state.lock() {
-                            state_guard.input_file = Some(path.clone());
-                        }
-                        log::info!("File dropped: {:?}", path);
-                    }
-                }
-            }
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw.dropped_files.iter().filter_map(|file| file.path.clone()).collect()
         });
+        if !dropped.is_empty() {
+            log::info!("Dropped: {:?}", dropped);
+            if let Ok(mut state_guard) = self.state.lock() {
+                state_guard.add_inputs(&dropped);
+            }
+        }
         
         // Main UI
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -235,6 +251,16 @@ impl eframe::App for SmallMp4App {
             self.draw_about_window(ctx);
         }
         
+        // Original and compressed frames
+        if self.preview.is_some() {
+            self.draw_preview_window(ctx);
+        }
+        
+        // Trim, crop and rotation of a queue item
+        if self.edit_panel.is_some() {
+            self.draw_edit_window(ctx);
+        }
+        
         // Completion popup
         self.draw_completion_popup(ctx);
         
@@ -245,6 +271,10 @@ impl eframe::App for SmallMp4App {
             }
         }
     }
+    
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_settings();
+    }
 }
 
 impl SmallMp4App {
@@ -270,11 +300,18 @@ impl SmallMp4App {
             
             ui.add_space(10.0);
             
+            // Files waiting, running and done
+            ui.group(|ui| {
+                self.draw_queue_section(ui);
+            });
+            
+            ui.add_space(10.0);
+            
             // Progress and controls
             self.draw_controls_section(ui);
             
             // Add flexible space
-            ui.add_space(ui.available_height() - 50.0);
+            ui.add_space((ui.available_height() - 50.0).max(0.0));
             
             // Menu bar at bottom
             ui.separator();
@@ -285,33 +322,31 @@ impl SmallMp4App {
     
     fn draw_input_section(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
-            // Input file
-            ui.label("Input:");
+            // Files and folders to queue; dropping them anywhere works too
+            ui.label(self.get_text("drag_drop"));
             ui.horizontal(|ui| {
-                // File path display
-                let file_text = {
-                    if let Ok(state_guard) = self.state.lock() {
-                        state_guard.input_file
-                            .as_ref()
-                            .map(|p| p.to_string_lossy().to_string())
-                            .unwrap_or_else(|| self.get_text("drag_drop").to_string())
-                    } else {
-                        self.get_text("drag_drop").to_string()
+                let input_folder = self.state.lock().ok().and_then(|state_guard| state_guard.input_folder.clone());
+                let dialog = || {
+                    let dialog = rfd::FileDialog::new();
+                    match &input_folder {
+                        Some(folder) => dialog.set_directory(folder),
+                        None => dialog,
                     }
                 };
-                    
-                ui.add_sized([350.0, 25.0], egui::TextEdit::singleline(&mut file_text.as_str())
-                    .interactive(false));
                 
-                // Browse button
-                if ui.button(self.get_text("browse")).clicked() {
-                    if let Some(path) = rfd::FileDialog::new()
-                        .add_filter("Video files", &["mp4", "avi", "mov", "mkv", "flv", "wmv"])
-                        .pick_file() 
-                    {
-                        if let Ok(mut state_guard) = self.state.lock() {
-                            state_guard.set_input_file(path);
-                        }
+                let mut picked = Vec::new();
+                if ui.button(format!("🎞️ {}", self.get_text("add_files"))).clicked() {
+                    picked = dialog()
+                        .add_filter(self.get_text("video_files"), &["mp4", "avi", "mov", "mkv", "flv", "wmv"])
+                        .pick_files()
+                        .unwrap_or_default();
+                }
+                if ui.button(format!("📂 {}", self.get_text("add_folder"))).clicked() {
+                    picked = dialog().pick_folder().into_iter().collect();
+                }
+                if !picked.is_empty() {
+                    if let Ok(mut state_guard) = self.state.lock() {
+                        state_guard.add_inputs(&picked);
                     }
                 }
             });
@@ -319,7 +354,7 @@ impl SmallMp4App {
             ui.add_space(5.0);
             
             // Output options
-            ui.label("Output:");
+            ui.label(self.get_text("output"));
             ui.horizontal(|ui| {
                 let (same_folder, output_folder) = {
                     if let Ok(state_guard) = self.state.lock() {
@@ -330,7 +365,7 @@ impl SmallMp4App {
                 };
                 
                 let mut same_folder_checkbox = same_folder;
-                ui.checkbox(&mut same_folder_checkbox, "Same folder");
+                ui.checkbox(&mut same_folder_checkbox, self.get_text("same_folder"));
                 
                 if same_folder_checkbox != same_folder {
                     if let Ok(mut state_guard) = self.state.lock() {
@@ -345,13 +380,17 @@ impl SmallMp4App {
                     let folder_text = output_folder
                         .as_ref()
                         .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or_else(|| "Select output folder...".to_string());
+                        .unwrap_or_else(|| self.get_text("select_output_folder"));
                     
                     ui.add_sized([200.0, 25.0], egui::TextEdit::singleline(&mut folder_text.as_str())
                         .interactive(false));
                     
-                    if ui.button("📁 Choose").clicked() {
-                        if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                    if ui.button(format!("📁 {}", self.get_text("choose_folder"))).clicked() {
+                        let mut dialog = rfd::FileDialog::new();
+                        if let Some(folder) = &output_folder {
+                            dialog = dialog.set_directory(folder);
+                        }
+                        if let Some(folder) = dialog.pick_folder() {
                             if let Ok(mut state_guard) = self.state.lock() {
                                 state_guard.output_folder = Some(folder);
                             }
@@ -378,18 +417,19 @@ impl SmallMp4App {
             
             let current_target_size = {
                 if let Ok(state_guard) = self.state.lock() {
-                    state_guard.compression_settings.target_size.clone()
+                    state_guard.compression_settings.target_size
                 } else {
-                    TargetSize::Size10MB
+                    TargetSize::Size10MB.into()
                 }
             };
             
             for (size, label) in sizes_row1 {
-                let selected = current_target_size == size;
+                let selected = current_target_size == FileSize::from(size);
                 if ui.selectable_label(selected, label).clicked() {
                     if let Ok(mut state_guard) = self.state.lock() {
-                        state_guard.compression_settings.target_size = size;
+                        state_guard.compression_settings.target_size = size.into();
                     }
+                    self.custom_size_input.clear();
                 }
             }
         });
@@ -405,17 +445,54 @@ impl SmallMp4App {
             
             let current_target_size = {
                 if let Ok(state_guard) = self.state.lock() {
-                    state_guard.compression_settings.target_size.clone()
+                    state_guard.compression_settings.target_size
                 } else {
-                    TargetSize::Size10MB
+                    TargetSize::Size10MB.into()
                 }
             };
             
             for (size, label) in sizes_row2 {
-                let selected = current_target_size == size;
+                let selected = current_target_size == FileSize::from(size);
                 if ui.selectable_label(selected, label).clicked() {
                     if let Ok(mut state_guard) = self.state.lock() {
-                        state_guard.compression_settings.target_size = size;
+                        state_guard.compression_settings.target_size = size.into();
+                    }
+                    self.custom_size_input.clear();
+                }
+            }
+        });
+        
+        // Any other size, e.g. "8 MB" or "10 MiB"
+        ui.horizontal(|ui| {
+            ui.label(self.get_text("custom_size"));
+            
+            let current_target_size = {
+                if let Ok(state_guard) = self.state.lock() {
+                    state_guard.compression_settings.target_size
+                } else {
+                    TargetSize::Size10MB.into()
+                }
+            };
+            
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.custom_size_input)
+                    .hint_text(current_target_size.to_string())
+                    .desired_width(90.0)
+            );
+            
+            let input = self.custom_size_input.trim();
+            if !input.is_empty() {
+                match input.parse::<FileSize>() {
+                    Ok(size) => {
+                        if response.changed() {
+                            if let Ok(mut state_guard) = self.state.lock() {
+                                state_guard.compression_settings.target_size = size;
+                            }
+                        }
+                        ui.label(egui::RichText::new(format!("= {}", size)).weak());
+                    }
+                    Err(e) => {
+                        ui.colored_label(egui::Color32::from_rgb(220, 80, 80), e.to_string());
                     }
                 }
             }
@@ -457,57 +534,225 @@ impl SmallMp4App {
     }
     
     
-    fn draw_controls_section(&mut self, ui: &mut egui::Ui) {
-        let (status, progress, has_input_file, eta) = {
-            if let Ok(state_guard) = self.state.lock() {
-                (
-                    state_guard.status.clone(), 
-                    state_guard.progress, 
-                    state_guard.input_file.is_some(),
-                    state_guard.estimated_time.clone()
-                )
-            } else {
-                (CompressionStatus::Idle, 0.0, false, None)
+    fn draw_queue_section(&mut self, ui: &mut egui::Ui) {
+        let language = self.config.language.clone();
+        let text = |key: &str| i18n::text_in(&language, key, &[]);
+        let Ok(mut state_guard) = self.state.lock() else { return };
+        let state = &mut *state_guard;
+        
+        ui.horizontal(|ui| {
+            ui.strong(format!("{} ({})", text("queue"), state.queue.items().len()));
+            ui.checkbox(&mut state.queue.parallel, text("run_parallel"));
+            if ui.small_button(text("apply_to_all")).clicked() {
+                state.queue.apply_to_all(&state.compression_settings);
             }
-        };
+            if ui.small_button(text("clear_finished")).clicked() {
+                state.queue.clear_finished();
+            }
+        });
         
-        // Progress bar - always visible
-        let progress_text = if status != CompressionStatus::Idle {
-            if let Some(time_left) = eta {
-                let seconds = time_left.as_secs();
-                let mins = seconds / 60;
-                let secs = seconds % 60;
-                if mins > 0 {
-                    format!("{}% ({}:{:02} 남음)", (progress * 100.0) as u32, mins, secs)
-                } else {
-                    format!("{}% ({}초 남음)", (progress * 100.0) as u32, secs)
+        if state.queue.items().is_empty() {
+            ui.weak(text("queue_empty"));
+            return;
+        }
+        
+        let mut actions = Vec::new();
+        egui::ScrollArea::vertical()
+            .max_height((ui.available_height() - 110.0).max(80.0))
+            .auto_shrink([false, true])
+            .show(ui, |ui| {
+                for item in state.queue.items() {
+                    self.draw_queue_item(ui, item, &text, &mut actions);
+                    ui.separator();
+                }
+            });
+        
+        let mut preview = None;
+        let mut edit = None;
+        for action in actions {
+            match action {
+                QueueAction::Shift(id, offset) => state.queue.shift(id, offset),
+                QueueAction::Remove(id) => {
+                    state.queue.remove(id);
+                }
+                QueueAction::Retry(id) => state.queue.retry(id),
+                QueueAction::Cancel(id) => state.queue.cancel(id),
+                QueueAction::Preview(id) => {
+                    preview = state.queue.items().iter().find(|item| item.id == id).map(|item| {
+                        let output = match &item.status {
+                            ItemStatus::Done { output, .. } => Some(output.clone()),
+                            _ => None,
+                        };
+                        (item.input.clone(), output, item.settings.edit)
+                    });
+                }
+                QueueAction::Edit(id) => {
+                    edit = state.queue.items().iter().find(|item| item.id == id)
+                        .map(|item| EditPanel::open(id, &item.input, item.settings.edit));
+                }
+                QueueAction::SetSize(id, size) => {
+                    if let Some(item) = state.queue.item_mut(id) {
+                        item.settings.target_size = size;
+                    }
                 }
+                QueueAction::SetProfile(id, profile) => {
+                    let settings = match &profile {
+                        Some(name) => self.profiles.iter().find(|(profile, _)| profile == name).map(|(_, settings)| settings.clone()),
+                        None => Some(state.compression_settings.clone()),
+                    };
+                    if let (Some(item), Some(settings)) = (state.queue.item_mut(id), settings) {
+                        item.settings = CompressionSettings { edit: item.settings.edit, ..settings };
+                        item.profile = profile;
+                    }
+                }
+            }
+        }
+        drop(state_guard);
+        
+        if let Some((input, output, edit)) = preview {
+            self.open_preview(input, output, edit);
+        }
+        if edit.is_some() {
+            self.edit_panel = edit;
+        }
+    }
+    
+    /// One queue row: name, settings, buttons, then progress or result
+    fn draw_queue_item(&self, ui: &mut egui::Ui, item: &QueueItem, text: &dyn Fn(&str) -> String, actions: &mut Vec<QueueAction>) {
+        let id = item.id;
+        
+        ui.horizontal(|ui| {
+            let icon = match &item.status {
+                ItemStatus::Waiting => "⏳",
+                ItemStatus::Running => "▶️",
+                ItemStatus::Done { target_met: true, .. } => "✅",
+                ItemStatus::Done { .. } => "⚠️",
+                ItemStatus::Failed(_) => "❌",
+                ItemStatus::Cancelled => "⏹️",
+            };
+            ui.label(icon);
+            ui.label(item.file_name()).on_hover_text(item.input.display().to_string());
+            
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if item.status == ItemStatus::Running {
+                    if ui.small_button("✖").on_hover_text(text("cancel")).clicked() {
+                        actions.push(QueueAction::Cancel(id));
+                    }
+                } else if ui.small_button("✖").on_hover_text(text("remove")).clicked() {
+                    actions.push(QueueAction::Remove(id));
+                }
+                if item.status.is_retryable() && ui.small_button("🔁").on_hover_text(text("retry")).clicked() {
+                    actions.push(QueueAction::Retry(id));
+                }
+                if ui.small_button("⬇").on_hover_text(text("move_down")).clicked() {
+                    actions.push(QueueAction::Shift(id, 1));
+                }
+                if ui.small_button("⬆").on_hover_text(text("move_up")).clicked() {
+                    actions.push(QueueAction::Shift(id, -1));
+                }
+                if ui.small_button("🔍").on_hover_text(text("compare")).clicked() {
+                    actions.push(QueueAction::Preview(id));
+                }
+                
+                if item.is_editable() && ui.small_button("✂").on_hover_text(text("edit")).clicked() {
+                    actions.push(QueueAction::Edit(id));
+                }
+                
+                let profile_label = item.profile.clone().unwrap_or_else(|| text("current_settings"));
+                if !item.is_editable() {
+                    ui.weak(format!("{} · {}", item.settings.target_size, profile_label));
+                    return;
+                }
+                
+                egui::ComboBox::from_id_salt(("queue_profile", id))
+                    .selected_text(profile_label)
+                    .width(110.0)
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(item.profile.is_none(), text("current_settings")).clicked() {
+                            actions.push(QueueAction::SetProfile(id, None));
+                        }
+                        for (name, _) in &self.profiles {
+                            if ui.selectable_label(item.profile.as_ref() == Some(name), name).clicked() {
+                                actions.push(QueueAction::SetProfile(id, Some(name.clone())));
+                            }
+                        }
+                    });
+                egui::ComboBox::from_id_salt(("queue_size", id))
+                    .selected_text(item.settings.target_size.to_string())
+                    .width(80.0)
+                    .show_ui(ui, |ui| {
+                        for size in TargetSize::ALL.iter().map(|&preset| FileSize::from(preset)) {
+                            if ui.selectable_label(item.settings.target_size == size, size.to_string()).clicked() {
+                                actions.push(QueueAction::SetSize(id, size));
+                            }
+                        }
+                    });
+            });
+        });
+        
+        if !item.settings.edit.is_empty() {
+            ui.weak(format!("✂ {}", item.settings.edit));
+        }
+        
+        match &item.status {
+            ItemStatus::Running => {
+                let progress = &item.progress;
+                let mut details = vec![
+                    format!("{}%", (progress.overall_progress * 100.0) as u32),
+                    text(stage_text_key(&progress.stage)),
+                ];
+                if let Some(speed) = progress.speed {
+                    details.push(format!("{:.2}x", speed));
+                }
+                if let Some(time_left) = progress.estimated_remaining {
+                    let seconds = time_left.as_secs();
+                    let time = format!("{}:{:02}", seconds / 60, seconds % 60);
+                    details.push(i18n::text_in(&self.config.language, "time_left", &[("time", &time)]));
+                }
+                ui.add(egui::ProgressBar::new(progress.overall_progress).text(details.join(" · ")));
+            }
+            ItemStatus::Done { output, input_size_mb, output_size_mb, .. } => {
+                ui.label(format!("{:.1} MB → {:.1} MB", input_size_mb, output_size_mb))
+                    .on_hover_text(output.display().to_string());
+                if let Some(plan) = &item.plan {
+                    ui.weak(plan.summary());
+                }
+            }
+            ItemStatus::Failed(error) => {
+                let message = i18n::text_in(&self.config.language, "compression_failed", &[("error", &error.lines().next().unwrap_or_default())]);
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), message)
+                    .on_hover_text(error);
+            }
+            ItemStatus::Waiting | ItemStatus::Cancelled => {}
+        }
+    }
+    
+    fn draw_controls_section(&mut self, ui: &mut egui::Ui) {
+        let (status, progress, has_waiting) = {
+            if let Ok(state_guard) = self.state.lock() {
+                (state_guard.status.clone(), state_guard.queue.progress(), state_guard.queue.has_waiting())
             } else {
-                format!("{}%", (progress * 100.0) as u32)
+                (CompressionStatus::Idle, 0.0, false)
             }
-        } else {
-            "0%".to_string()
         };
         
-        ui.add(egui::ProgressBar::new(progress).text(progress_text));
+        // Progress of the whole queue
+        ui.add(egui::ProgressBar::new(progress).text(format!("{}%", (progress * 100.0) as u32)));
         
         ui.add_space(5.0);
         
-        // Control buttons - not horizontally centered anymore
         match status {
-            CompressionStatus::Idle => {
-                let compress_enabled = has_input_file;
-                if ui.add_enabled(compress_enabled, 
-                    egui::Button::new(format!("🎬 {}", self.get_text("compress")))
-                        .min_size(egui::vec2(200.0, 35.0))
-                ).clicked() {
-                    self.start_compression();
-                }
-            },
-            CompressionStatus::Processing => {
+            CompressionStatus::Processing | CompressionStatus::Paused => {
                 ui.horizontal(|ui| {
-                    if ui.button(format!("⏹️ {}", self.get_text("stop"))).clicked() {
-                        self.stop_compression();
+                    if status == CompressionStatus::Paused {
+                        if ui.button(format!("▶️ {}", self.get_text("resume"))).clicked() {
+                            self.resume_compression();
+                        }
+                    } else if ui.add_enabled(
+                        CancellationToken::supports_pause(),
+                        egui::Button::new(format!("⏸️ {}", self.get_text("pause"))),
+                    ).clicked() {
+                        self.pause_compression();
                     }
                     
                     ui.add_space(10.0);
@@ -517,7 +762,17 @@ impl SmallMp4App {
                     }
                 });
             },
-            _ => {}
+            _ => {
+                if let CompressionStatus::Error(error) = &status {
+                    ui.colored_label(egui::Color32::from_rgb(220, 80, 80), error);
+                }
+                if ui.add_enabled(has_waiting, 
+                    egui::Button::new(format!("🎬 {}", self.get_text("compress")))
+                        .min_size(egui::vec2(200.0, 35.0))
+                ).clicked() {
+                    self.start_compression();
+                }
+            }
         }
     }
     
@@ -531,17 +786,13 @@ impl SmallMp4App {
                 self.show_about = !self.show_about;
             }
             
-            // Language selector
+            // Language selector, one entry per catalog in locales/
             egui::ComboBox::from_label(self.get_text("language"))
-                .selected_text(match self.config.language {
-                    Language::English => "English",
-                    Language::Korean => "한국어", 
-                    Language::Japanese => "日本語",
-                })
+                .selected_text(self.get_text("language_name"))
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.config.language, Language::English, "English");
-                    ui.selectable_value(&mut self.config.language, Language::Korean, "한국어");
-                    ui.selectable_value(&mut self.config.language, Language::Japanese, "日本語");
+                    for (code, name) in i18n::languages() {
+                        ui.selectable_value(&mut self.config.language, code.to_string(), name);
+                    }
                 });
         });
     }
@@ -556,6 +807,22 @@ impl SmallMp4App {
         let detection_progress_text = self.get_text("hardware_detection_progress");
         let enable_hw_accel_text = self.get_text("enable_hardware_accel");
         let memory_opt_text = self.get_text("memory_optimization");
+        let keep_resolution_text = self.get_text("keep_resolution");
+        let rate_control_text = self.get_text("rate_control");
+        let quality_level_text = self.get_text("quality_level");
+        let mode_labels: Vec<(HardwareQuality, String)> = HardwareQuality::ALL.iter()
+            .map(|mode| (*mode, self.get_text(rc_text_key(mode))))
+            .collect();
+        let theme_text = self.get_text("theme");
+        let theme_labels: Vec<(Theme, String)> = Theme::ALL.iter()
+            .map(|theme| (*theme, self.get_text(theme.text_key())))
+            .collect();
+        let remember_settings_text = self.get_text("remember_settings");
+        let reset_settings_text = self.get_text("reset_settings");
+        
+        let mut theme = self.config.theme;
+        let mut remember_settings = self.config.remember_settings;
+        let mut reset = false;
         
         egui::Window::new(window_title)
             .open(&mut self.show_advanced)
@@ -578,24 +845,77 @@ impl SmallMp4App {
                 
                 ui.separator();
                 
-                let (mut enable_hw_accel, mut memory_opt) = {
+                let (mut enable_hw_accel, mut memory_opt, mut keep_resolution, mut rate_control, mut quality_level) = {
                     if let Ok(state_guard) = self.state.lock() {
                         (state_guard.compression_settings.enable_hardware_accel,
-                         state_guard.compression_settings.memory_optimization)
+                         state_guard.compression_settings.memory_optimization,
+                         state_guard.compression_settings.keep_resolution,
+                         state_guard.compression_settings.hardware_quality,
+                         state_guard.compression_settings.quality_level)
                     } else {
-                        (true, false)
+                        (true, false, false, HardwareQuality::Auto, DEFAULT_QUALITY_LEVEL)
                     }
                 };
                 
                 ui.checkbox(&mut enable_hw_accel, &enable_hw_accel_text);
                 ui.checkbox(&mut memory_opt, &memory_opt_text);
+                ui.checkbox(&mut keep_resolution, &keep_resolution_text);
+                
+                ui.separator();
+                
+                let selected_label = mode_labels.iter()
+                    .find(|(mode, _)| *mode == rate_control)
+                    .map(|(_, label)| label.clone())
+                    .unwrap_or_default();
+                egui::ComboBox::from_label(&rate_control_text)
+                    .selected_text(selected_label)
+                    .show_ui(ui, |ui| {
+                        for (mode, label) in &mode_labels {
+                            ui.selectable_value(&mut rate_control, *mode, label);
+                        }
+                    });
+                
+                if rate_control.uses_quality_level() {
+                    ui.add(egui::Slider::new(&mut quality_level, 0..=MAX_QUALITY_LEVEL).text(&quality_level_text));
+                }
                 
                 // Update state if changed
                 if let Ok(mut state_guard) = self.state.lock() {
                     state_guard.compression_settings.enable_hardware_accel = enable_hw_accel;
                     state_guard.compression_settings.memory_optimization = memory_opt;
+                    state_guard.compression_settings.keep_resolution = keep_resolution;
+                    state_guard.compression_settings.hardware_quality = rate_control;
+                    state_guard.compression_settings.quality_level = quality_level;
+                }
+                
+                ui.separator();
+                
+                let selected_theme = theme_labels.iter()
+                    .find(|(option, _)| *option == theme)
+                    .map(|(_, label)| label.clone())
+                    .unwrap_or_default();
+                egui::ComboBox::from_label(&theme_text)
+                    .selected_text(selected_theme)
+                    .show_ui(ui, |ui| {
+                        for (option, label) in &theme_labels {
+                            ui.selectable_value(&mut theme, *option, label);
+                        }
+                    });
+                
+                ui.checkbox(&mut remember_settings, &remember_settings_text);
+                if ui.button(&reset_settings_text).clicked() {
+                    reset = true;
                 }
             });
+        
+        self.config.remember_settings = remember_settings;
+        if theme != self.config.theme {
+            self.config.theme = theme;
+            ctx.set_theme(theme.preference());
+        }
+        if reset {
+            self.reset_settings(ctx);
+        }
     }
     
     fn draw_about_window(&mut self, ctx: &egui::Context) {
@@ -611,18 +931,11 @@ impl SmallMp4App {
                     // Show description in selected language only
                     ui.group(|ui| {
                         ui.vertical(|ui| {
-                            let (flag, description) = match self.config.language {
-                                Language::Korean => ("🇰🇷", "동영상 공유를 위해서 영상을 꾸겨줍니다"),
-                                Language::Japanese => ("🇯🇵", "動画共有のために映像を圧縮します"),
-                                Language::English => ("🇺🇸", "Squeeze your videos for easy sharing"),
-                            };
-                            
-                            ui.label(format!("{} {}", flag, match self.config.language {
-                                Language::Korean => "한국어",
-                                Language::Japanese => "日本語", 
-                                Language::English => "English",
-                            }));
-                            ui.label(description);
+                            let language = &self.config.language;
+                            ui.label(format!("{} {}",
+                                i18n::text_in(language, "language_flag", &[]),
+                                i18n::text_in(language, "language_name", &[])));
+                            ui.label(i18n::text_in(language, "tagline", &[]));
                         });
                     });
                     
@@ -632,163 +945,258 @@ impl SmallMp4App {
             });
     }
     
-    fn start_compression(&mut self) {
-        // Validate input file exists
-        let (input_file, output_path) = {
-            let mut state_guard = self.state.lock().unwrap();
-            let input_file = match &state_guard.input_file {
-                Some(file) => file.clone(),
-                None => {
-                    state_guard.set_error("No input file selected".to_string());
-                    return;
-                }
-            };
-            
-            // Get output path
-            let output_path = match state_guard.get_output_path() {
-                Some(path) => path,
-                None => {
-                    state_guard.set_error("Could not determine output path".to_string());
-                    return;
-                }
-            };
-            
-            (input_file, output_path)
+    fn draw_preview_window(&mut self, ctx: &egui::Context) {
+        let language = self.config.language.clone();
+        let text = |key: &str| i18n::text_in(&language, key, &[]);
+        let title = match &self.preview {
+            Some((input, _, _)) => format!("{} - {}", text("preview"), input.file_name().unwrap_or_default().to_string_lossy()),
+            None => return,
         };
         
-        {
-            let mut state_guard = self.state.lock().unwrap();
-            state_guard.status = CompressionStatus::Processing;
-            state_guard.progress = 0.0;
+        let mut open = true;
+        let mut request = None;
+        egui::Window::new(title)
+            .id(egui::Id::new("preview_window"))
+            .open(&mut open)
+            .default_width(640.0)
+            .show(ctx, |ui| {
+                if let Ok(state_guard) = self.state.lock() {
+                    request = self.preview_panel.show(
+                        ui,
+                        &text,
+                        &state_guard.original_preview,
+                        &state_guard.compressed_preview,
+                        state_guard.preview_error.as_deref(),
+                    );
+                }
+            });
+        
+        if !open {
+            self.preview = None;
+        } else if let Some(timestamp) = request {
+            self.request_preview_frames(timestamp);
         }
-        log::info!("Starting compression...");
-        log::info!("Input: {}", input_file.display());
-        log::info!("Output: {}", output_path.display());
+    }
+    
+    fn draw_edit_window(&mut self, ctx: &egui::Context) {
+        let language = self.config.language.clone();
+        let text = |key: &str| i18n::text_in(&language, key, &[]);
+        let Some(panel) = &mut self.edit_panel else { return };
+        let title = format!("{} - {}", text("edit"), panel.input.file_name().unwrap_or_default().to_string_lossy());
         
-        // Clone what we need for the async task
-        let settings = {
-            let state_guard = self.state.lock().unwrap();
-            state_guard.compression_settings.clone()
+        let mut open = true;
+        let mut outcome = None;
+        egui::Window::new(title)
+            .id(egui::Id::new("edit_window"))
+            .open(&mut open)
+            .default_width(480.0)
+            .show(ctx, |ui| {
+                outcome = panel.show(ui, &text);
+            });
+        
+        if let Some(EditOutcome::Apply(edit)) = &outcome {
+            if let Ok(mut state_guard) = self.state.lock() {
+                // The item may have started while the window was open
+                if let Some(item) = state_guard.queue.item_mut(panel.item).filter(|item| item.is_editable()) {
+                    item.settings.edit = *edit;
+                }
+            }
+        }
+        if !open || outcome.is_some() {
+            self.edit_panel = None;
+        }
+    }
+    
+    /// Show `input` and its compressed `output` in the comparison window
+    fn open_preview(&mut self, input: PathBuf, output: Option<PathBuf>, edit: VideoEdit) {
+        let request = {
+            let Ok(mut state_guard) = self.state.lock() else { return };
+            state_guard.original_preview = None;
+            state_guard.compressed_preview = None;
+            state_guard.preview_error = None;
+            state_guard.preview_request += 1;
+            state_guard.preview_request
         };
+        self.preview_panel.reset();
+        self.preview = Some((input.clone(), output.clone(), edit));
         
-        // Get compression engine and state (clone the Arc to move into async context)
-        let engine = self.compression_engine.clone();
         let app_state = self.state.clone();
-        
-        // Spawn compression task in a separate thread using std::thread
         std::thread::spawn(move || {
-            // Use a blocking runtime for this thread
             let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
             rt.block_on(async move {
-                // Try to get a lock on the engine
-                let result = {
-                    let mut engine_guard = match engine.lock() {
-                        Ok(guard) => guard,
-                        Err(_) => {
-                            log::error!("Could not lock compression engine (poisoned)");
-                            return;
-                        }
-                    };
-                    
-                    if let Some(ref mut compression_engine) = *engine_guard {
-                        // Create progress channel
-                        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
-                        
-                        // Spawn task to receive progress updates
-                        let app_state_progress = app_state.clone();
-                        tokio::task::spawn(async move {
-                            while let Some((progress, eta)) = progress_rx.recv().await {
-                                if let Ok(mut state_guard) = app_state_progress.lock() {
-                                    state_guard.progress = progress;
-                                    state_guard.estimated_time = eta;
-                                }
-                            }
-                        });
-                        
-                        compression_engine.compress(&input_file, Some(&output_path), &settings, Some(progress_tx)).await
-                    } else {
-                        log::error!("Compression engine not initialized");
-                        return;
+                let generator = ThumbnailGenerator::new();
+                let original = generator.get_video_info(&input).await;
+                let compressed = match &output {
+                    Some(output) => generator.get_video_info(output).await.map(Some),
+                    None => Ok(None),
+                };
+                
+                let Ok(mut state_guard) = app_state.lock() else { return };
+                if state_guard.preview_request != request {
+                    return;
+                }
+                match (original, compressed) {
+                    (Ok(original), Ok(compressed)) => {
+                        state_guard.original_preview = Some(original);
+                        state_guard.compressed_preview = compressed;
                     }
+                    (Err(e), _) | (_, Err(e)) => {
+                        log::warn!("Could not read the preview videos: {:#}", e);
+                        state_guard.preview_error = Some(format!("{:#}", e));
+                    }
+                }
+            });
+        });
+    }
+    
+    /// Decode the frames at `timestamp` of the previewed videos
+    fn request_preview_frames(&mut self, timestamp: f64) {
+        let Some((input, output, edit)) = self.preview.clone() else { return };
+        let (request, size, compressed_frame) = {
+            let Ok(mut state_guard) = self.state.lock() else { return };
+            let Some(original) = &state_guard.original_preview else { return };
+            let generator = ThumbnailGenerator::new();
+            let size = generator.frame_size(original.width, original.height);
+            // The output holds only the kept part, cropped and rotated
+            let compressed_frame = state_guard.compressed_preview.as_ref().map(|compressed| {
+                let source = VideoMetadata {
+                    width: original.width,
+                    height: original.height,
+                    duration_seconds: original.duration as f32,
+                    ..VideoMetadata::default_hd()
+                };
+                let (width, height) = edit.apply(&source)
+                    .map_or((compressed.width, compressed.height), |edited| (edited.width, edited.height));
+                (output_timestamp(&edit, timestamp, compressed.duration), generator.frame_size(width, height))
+            });
+            state_guard.preview_error = None;
+            state_guard.preview_request += 1;
+            (state_guard.preview_request, size, compressed_frame)
+        };
+        
+        let app_state = self.state.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+            rt.block_on(async move {
+                let generator = ThumbnailGenerator::new();
+                let original = generator.generate_thumbnail(&input, timestamp, size).await;
+                let compressed = match (&output, compressed_frame) {
+                    (Some(output), Some((timestamp, size))) => generator.generate_thumbnail(output, timestamp, size).await.map(Some),
+                    _ => Ok(None),
                 };
                 
-                match result {
-                    Ok(result) => {
-                        log::info!("Compression completed successfully!");
-                        log::info!("Input size: {:.1} MB, Output size: {:.1} MB", 
-                            result.input_size_mb, result.output_size_mb);
-                        
-                        // Update GUI state to idle and show completion
-                        if let Ok(mut state_guard) = app_state.lock() {
-                            state_guard.status = CompressionStatus::Idle;
-                            state_guard.progress = 1.0; // 100% complete
-                            state_guard.last_compression_result = Some((result.input_size_mb, result.output_size_mb));
-                            state_guard.show_completion_popup = true; // Show completion popup
+                let Ok(mut state_guard) = app_state.lock() else { return };
+                // A newer position was picked meanwhile
+                if state_guard.preview_request != request {
+                    return;
+                }
+                match (original, compressed) {
+                    (Ok(original), Ok(compressed)) => {
+                        if let Some(preview) = &mut state_guard.original_preview {
+                            preview.thumbnail = Some(original);
                         }
-                    }
-                    Err(e) => {
-                        log::error!("Compression failed: {}", e);
-                        
-                        // Update GUI state to idle with error
-                        if let Ok(mut state_guard) = app_state.lock() {
-                            state_guard.status = CompressionStatus::Idle;
-                            state_guard.progress = 0.0;
-                            state_guard.set_error(format!("Compression failed: {}", e));
+                        if let Some(preview) = &mut state_guard.compressed_preview {
+                            preview.thumbnail = compressed;
                         }
                     }
+                    (Err(e), _) | (_, Err(e)) => {
+                        log::warn!("Could not extract preview frames: {:#}", e);
+                        state_guard.preview_error = Some(format!("{:#}", e));
+                    }
                 }
             });
         });
     }
     
-    fn stop_compression(&mut self) {
-        if let Ok(mut state_guard) = self.state.lock() {
-            state_guard.status = CompressionStatus::Paused;
+    fn start_compression(&mut self) {
+        let Some(scheduler) = self.scheduler.clone() else {
+            log::error!("Compression scheduler not initialized");
+            return;
+        };
+        
+        let concurrency = {
+            let mut state_guard = self.state.lock().unwrap();
+            if !state_guard.queue.has_waiting() {
+                state_guard.set_error(self.get_text("no_input_file"));
+                return;
+            }
+            if !state_guard.has_output_location() {
+                state_guard.set_error(self.get_text("no_output_path"));
+                return;
+            }
+            
+            state_guard.clear_error();
+            state_guard.status = CompressionStatus::Processing;
+            state_guard.queue.stop_requested = false;
+            if state_guard.queue.parallel { scheduler.limits().max_jobs } else { 1 }
+        };
+        log::info!("Starting the queue, up to {} file(s) at a time", concurrency);
+        
+        // Remember the choices that went into this run even if the app is killed
+        self.save_settings();
+        
+        let app_state = self.state.clone();
+        
+        // Spawn the queue in a separate thread using std::thread
+        std::thread::spawn(move || {
+            // Use a blocking runtime for this thread
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+            rt.block_on(run_queue(app_state, scheduler, concurrency));
+        });
+    }
+    
+    fn pause_compression(&mut self) {
+        let Ok(mut state_guard) = self.state.lock() else { return };
+        for token in state_guard.queue.running_tokens() {
+            if let Err(e) = token.pause() {
+                log::warn!("Could not pause compression: {}", e);
+                return;
+            }
         }
-        log::info!("Compression stopped");
+        state_guard.status = CompressionStatus::Paused;
+        log::info!("Compression paused");
+    }
+    
+    fn resume_compression(&mut self) {
+        let Ok(mut state_guard) = self.state.lock() else { return };
+        for token in state_guard.queue.running_tokens() {
+            if let Err(e) = token.resume() {
+                log::warn!("Could not resume compression: {}", e);
+                return;
+            }
+        }
+        state_guard.status = CompressionStatus::Processing;
+        log::info!("Compression resumed");
     }
     
     fn cancel_compression(&mut self) {
+        // Kills FFmpeg; the workers remove partial outputs and the queue stops
         if let Ok(mut state_guard) = self.state.lock() {
-            state_guard.status = CompressionStatus::Idle;
-            state_guard.progress = 0.0;
+            state_guard.queue.stop_requested = true;
+            for token in state_guard.queue.running_tokens() {
+                token.cancel();
+            }
         }
         log::info!("Compression cancelled");
     }
     
     fn draw_completion_popup(&mut self, ctx: &egui::Context) {
-        let (show_popup, compression_result) = {
+        let (show_popup, compression_result, encoding_plan) = {
             if let Ok(state_guard) = self.state.lock() {
-                (state_guard.show_completion_popup, state_guard.last_compression_result)
+                (state_guard.show_completion_popup, state_guard.last_compression_result, state_guard.last_encoding_plan.clone())
             } else {
-                (false, None)
+                (false, None, None)
             }
         };
         
         if show_popup {
-            let (title, message, size_before_text, size_after_text, button_text) = match self.config.language {
-                Language::Korean => (
-                    "압축 완료!",
-                    "✅ 비디오 압축이 완료되었습니다!",
-                    "압축 전:",
-                    "압축 후:",
-                    "확인"
-                ),
-                Language::Japanese => (
-                    "圧縮完了！",
-                    "✅ ビデオ圧縮が完了しました！",
-                    "圧縮前:",
-                    "圧縮後:",
-                    "OK"
-                ),
-                Language::English => (
-                    "Compression Complete!",
-                    "✅ Video compression completed successfully!",
-                    "Before:",
-                    "After:",
-                    "OK"
-                ),
-            };
+            let title = self.get_text("complete_title");
+            let message = self.get_text("complete_message");
+            let size_before_text = self.get_text("size_before");
+            let size_after_text = self.get_text("size_after");
+            let button_text = self.get_text("ok");
+            let infeasible_text = self.get_text("target_too_small");
+            let language = self.config.language.clone();
             
             egui::Window::new(title)
                 .collapsible(false)
@@ -817,11 +1225,24 @@ impl SmallMp4App {
                             // Show compression ratio
                             let compression_ratio = (1.0 - output_size / input_size) * 100.0;
                             ui.add_space(5.0);
-                            ui.label(format!("({:.1}% smaller)", compression_ratio));
+                            let percent = format!("{:.1}", compression_ratio);
+                            ui.label(i18n::text_in(&language, "percent_smaller", &[("percent", &percent)]));
                             
                             ui.add_space(5.0);
                         }
                         
+                        // Resolution/frame rate/audio the planner picked
+                        if let Some(plan) = &encoding_plan {
+                            ui.label(egui::RichText::new(plan.summary()).weak());
+                            if !plan.feasible {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(220, 160, 60),
+                                    format!("{} {}", infeasible_text, plan.minimum_size),
+                                );
+                            }
+                            ui.add_space(5.0);
+                        }
+                        
                         ui.separator();
                         ui.add_space(5.0);
                         
@@ -838,3 +1259,135 @@ impl SmallMp4App {
         }
     }
 }
+
+/// A change to the queue picked in the queue panel, applied after drawing it
+enum QueueAction {
+    Shift(u64, isize),
+    Remove(u64),
+    Retry(u64),
+    Cancel(u64),
+    Preview(u64),
+    Edit(u64),
+    SetSize(u64, FileSize),
+    SetProfile(u64, Option<String>),
+}
+
+/// Run the waiting queue items, `concurrency` at a time, until none are left
+/// or the queue is stopped
+async fn run_queue(app_state: Arc<Mutex<AppState>>, scheduler: JobScheduler, concurrency: usize) {
+    let mut tasks = tokio::task::JoinSet::new();
+    let mut completed = 0;
+    
+    loop {
+        let next = if tasks.len() < concurrency {
+            let mut state_guard = app_state.lock().unwrap();
+            let state = &mut *state_guard;
+            if state.queue.stop_requested || state.status == CompressionStatus::Paused {
+                None
+            } else {
+                let outputs: Vec<_> = state.queue.items().iter()
+                    .map(|item| (item.id, state.output_path_for(item)))
+                    .collect();
+                state.queue.start_next(|item| {
+                    outputs.iter().find(|(id, _)| *id == item.id).and_then(|(_, output)| output.clone())
+                })
+            }
+        } else {
+            None
+        };
+        
+        if let Some((id, job, cancel)) = next {
+            log::info!("Compressing {} -> {:?}", job.input.display(), job.output);
+            tasks.spawn(run_item(app_state.clone(), scheduler.clone(), id, job, cancel));
+            continue;
+        }
+        
+        let paused = app_state.lock().map(|state| state.status == CompressionStatus::Paused).unwrap_or(false);
+        if paused && tasks.is_empty() {
+            // Nothing is running; wait for resume or cancel
+            let stopped = app_state.lock().map(|state| state.queue.stop_requested).unwrap_or(true);
+            if stopped {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            continue;
+        }
+        
+        match tasks.join_next().await {
+            Some(Ok(true)) => completed += 1,
+            Some(_) => {}
+            None => break,
+        }
+    }
+    
+    if let Ok(mut state_guard) = app_state.lock() {
+        state_guard.status = CompressionStatus::Idle;
+        state_guard.queue.stop_requested = false;
+        // A popup per file would get in the way of a longer queue
+        state_guard.show_completion_popup = completed == 1;
+    }
+    log::info!("Queue finished, {} file(s) compressed", completed);
+}
+
+/// Compress one queue item, reporting into it; `true` if it succeeded
+async fn run_item(
+    app_state: Arc<Mutex<AppState>>,
+    scheduler: JobScheduler,
+    id: u64,
+    job: CompressionJob,
+    cancel: CancellationToken,
+) -> bool {
+    let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+    
+    // Forward progress updates to the item
+    let app_state_progress = app_state.clone();
+    let forward = tokio::task::spawn(async move {
+        while let Some(event) = progress_rx.recv().await {
+            if let Ok(mut state_guard) = app_state_progress.lock() {
+                if let Some(item) = state_guard.queue.item_mut(id) {
+                    item.progress.apply(&event);
+                }
+            }
+        }
+    });
+    
+    let result = scheduler.run(&job, Some(progress_tx), &cancel).await;
+    let _ = forward.await;
+    
+    match &result {
+        Ok(result) => log::info!("Compressed {}: {:.1} MB -> {:.1} MB",
+            job.input.display(), result.input_size_mb, result.output_size_mb),
+        Err(e) => log::warn!("Compression of {} did not finish: {:#}", job.input.display(), e),
+    }
+    
+    let Ok(mut state_guard) = app_state.lock() else { return false };
+    state_guard.queue.finish(id, &result);
+    match result {
+        Ok(result) => {
+            state_guard.last_compression_result = Some((result.input_size_mb, result.output_size_mb));
+            state_guard.last_encoding_plan = Some(result.plan);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Translation key for a rate control mode label
+fn rc_text_key(mode: &HardwareQuality) -> &'static str {
+    match mode {
+        HardwareQuality::Auto => "rc_auto",
+        HardwareQuality::Variable => "rc_variable",
+        HardwareQuality::Constant => "rc_constant",
+        HardwareQuality::Constrained => "rc_constrained",
+    }
+}
+
+/// Translation key for a progress stage label
+fn stage_text_key(stage: &CompressionStage) -> &'static str {
+    match stage {
+        CompressionStage::Initializing => "stage_initializing",
+        CompressionStage::Analyzing => "stage_analyzing",
+        CompressionStage::Encoding => "stage_encoding",
+        CompressionStage::Finalizing => "stage_finalizing",
+    }
+}
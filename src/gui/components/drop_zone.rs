@@ -1,5 +1,5 @@
 use eframe::egui::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Default)]
 pub struct DropZone {
@@ -113,7 +113,7 @@ impl DropZone {
         dropped_file
     }
     
-    fn is_video_file(&self, path: &PathBuf) -> bool {
+    fn is_video_file(&self, path: &Path) -> bool {
         if let Some(extension) = path.extension() {
             let ext = extension.to_string_lossy().to_lowercase();
             self.accepted_extensions.contains(&ext)
@@ -0,0 +1,230 @@
+use eframe::egui::*;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use small_mp4::compression::{format_timestamp, parse_timestamp, CropRect, Rotation, VideoEdit, VideoMetadata};
+use crate::gui::components::ThumbnailGenerator;
+use crate::gui::state::PreviewData;
+
+/// Shortest part of a video the timeline keeps, in seconds
+const MIN_KEPT_SECONDS: f64 = 0.1;
+
+/// What the user decided in the edit window
+pub enum EditOutcome {
+    Apply(VideoEdit),
+    Cancel,
+}
+
+/// Trim, crop and rotation of one queued video
+pub struct EditPanel {
+    /// Queue item being edited
+    pub item: u64,
+    pub input: PathBuf,
+    /// In and out points, in seconds; the full video until it is known
+    start: f64,
+    end: Option<f64>,
+    crop: Option<CropRect>,
+    rotation: Rotation,
+    /// Length and frame size of the video, read in the background
+    info: Arc<Mutex<Option<Result<PreviewData, String>>>>,
+}
+
+impl EditPanel {
+    /// Edit `input`, starting from its current `edit`
+    pub fn open(item: u64, input: &Path, edit: VideoEdit) -> Self {
+        let info = Arc::new(Mutex::new(None));
+        
+        let loaded = info.clone();
+        let path = input.to_path_buf();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+            let result = rt.block_on(ThumbnailGenerator::new().get_video_info(&path))
+                .map_err(|e| format!("{:#}", e));
+            if let Ok(mut info) = loaded.lock() {
+                *info = Some(result);
+            }
+        });
+        
+        Self {
+            item,
+            input: input.to_path_buf(),
+            start: edit.start.unwrap_or(0.0),
+            end: edit.end,
+            crop: edit.crop,
+            rotation: edit.rotation,
+            info,
+        }
+    }
+    
+    pub fn show(&mut self, ui: &mut Ui, text: &dyn Fn(&str) -> String) -> Option<EditOutcome> {
+        let info = self.info.lock().ok().and_then(|info| info.clone());
+        let info = match info {
+            Some(Ok(info)) => info,
+            Some(Err(error)) => {
+                ui.colored_label(Color32::from_rgb(220, 80, 80), error);
+                return ui.button(text("cancel")).clicked().then_some(EditOutcome::Cancel);
+            }
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(text("loading_preview"));
+                });
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+                return None;
+            }
+        };
+        
+        let duration = info.duration.max(MIN_KEPT_SECONDS);
+        let mut end = self.end.unwrap_or(duration).min(duration);
+        self.start = self.start.clamp(0.0, (end - MIN_KEPT_SECONDS).max(0.0));
+        
+        // Trim
+        ui.strong(text("trim"));
+        timeline(ui, &mut self.start, &mut end, duration);
+        ui.horizontal(|ui| {
+            ui.label(text("trim_start"));
+            ui.add(time_field(&mut self.start, 0.0..=end - MIN_KEPT_SECONDS));
+            ui.label(text("trim_end"));
+            let start = self.start;
+            ui.add(time_field(&mut end, start + MIN_KEPT_SECONDS..=duration));
+        });
+        self.end = Some(end);
+        
+        ui.add_space(8.0);
+        
+        // Crop, in source pixels
+        let mut cropping = self.crop.is_some();
+        if ui.checkbox(&mut cropping, text("crop")).changed() {
+            self.crop = cropping.then_some(CropRect { width: info.width, height: info.height, x: 0, y: 0 });
+        }
+        if let Some(crop) = &mut self.crop {
+            ui.horizontal(|ui| {
+                ui.label(text("crop_size"));
+                ui.add(DragValue::new(&mut crop.width).range(2..=info.width));
+                ui.label("×");
+                ui.add(DragValue::new(&mut crop.height).range(2..=info.height));
+                ui.label(text("crop_offset"));
+                ui.add(DragValue::new(&mut crop.x).range(0..=info.width - crop.width));
+                ui.label(",");
+                ui.add(DragValue::new(&mut crop.y).range(0..=info.height - crop.height));
+            });
+        }
+        
+        ui.add_space(8.0);
+        
+        // Rotation, after cropping
+        ui.horizontal(|ui| {
+            ui.label(text("rotation"));
+            ComboBox::from_id_salt("edit_rotation")
+                .selected_text(format!("{}°", self.rotation))
+                .show_ui(ui, |ui| {
+                    for rotation in Rotation::ALL {
+                        ui.selectable_value(&mut self.rotation, rotation, format!("{}°", rotation));
+                    }
+                });
+        });
+        
+        ui.add_space(8.0);
+        ui.separator();
+        
+        // What the encoder gets, as the size target sees it
+        let edit = self.edit(duration);
+        let source = VideoMetadata {
+            width: info.width,
+            height: info.height,
+            duration_seconds: info.duration as f32,
+            ..VideoMetadata::default_hd()
+        };
+        let valid = match edit.apply(&source) {
+            Ok(edited) => {
+                ui.label(format!("{} {}×{}, {}", text("edit_result"), edited.width, edited.height,
+                    format_timestamp(edited.duration_seconds as f64)));
+                true
+            }
+            Err(e) => {
+                ui.colored_label(Color32::from_rgb(220, 80, 80), e.to_string());
+                false
+            }
+        };
+        
+        let mut outcome = None;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(valid, Button::new(text("apply"))).clicked() {
+                outcome = Some(EditOutcome::Apply(edit));
+            }
+            if ui.button(text("cancel")).clicked() {
+                outcome = Some(EditOutcome::Cancel);
+            }
+            if ui.button(text("reset_edit")).clicked() {
+                self.start = 0.0;
+                self.end = None;
+                self.crop = None;
+                self.rotation = Rotation::None;
+            }
+        });
+        outcome
+    }
+    
+    /// The edit as chosen; trimming to the whole video is no trimming
+    fn edit(&self, duration: f64) -> VideoEdit {
+        VideoEdit {
+            start: Some(self.start).filter(|&start| start > 0.0),
+            end: self.end.filter(|&end| end < duration),
+            crop: self.crop,
+            rotation: self.rotation,
+        }
+    }
+}
+
+/// A `m:ss.t` field for a position in the video
+fn time_field(value: &mut f64, range: std::ops::RangeInclusive<f64>) -> DragValue<'_> {
+    DragValue::new(value)
+        .range(range)
+        .speed(0.1)
+        .custom_formatter(|seconds, _| format_timestamp(seconds))
+        .custom_parser(|text| parse_timestamp(text).ok())
+}
+
+/// Bar over the whole video with draggable in and out handles
+fn timeline(ui: &mut Ui, start: &mut f64, end: &mut f64, duration: f64) -> Response {
+    let (rect, response) = ui.allocate_exact_size(vec2(ui.available_width(), 28.0), Sense::click_and_drag());
+    let to_x = |seconds: f64| rect.left() + (seconds / duration) as f32 * rect.width();
+    let to_seconds = |x: f32| ((x - rect.left()) / rect.width()).clamp(0.0, 1.0) as f64 * duration;
+    
+    // A drag moves the handle nearest to where it started
+    if let Some(pointer) = response.interact_pointer_pos() {
+        let nearest_is_end = (pointer.x - to_x(*end)).abs() < (pointer.x - to_x(*start)).abs();
+        if response.drag_started() {
+            ui.data_mut(|data| data.insert_temp(response.id, nearest_is_end));
+        }
+        let moves_end = if response.dragged() {
+            ui.data(|data| data.get_temp(response.id)).unwrap_or(nearest_is_end)
+        } else {
+            nearest_is_end
+        };
+        
+        if response.dragged() || response.clicked() {
+            let seconds = to_seconds(pointer.x);
+            if moves_end {
+                *end = seconds.max(*start + MIN_KEPT_SECONDS).min(duration);
+            } else {
+                *start = seconds.min(*end - MIN_KEPT_SECONDS).max(0.0);
+            }
+        }
+    }
+    
+    let visuals = ui.style().visuals.clone();
+    let painter = ui.painter_at(rect);
+    let track = rect.shrink2(vec2(0.0, 8.0));
+    painter.rect_filled(track, 3.0, visuals.extreme_bg_color);
+    
+    let kept = Rect::from_x_y_ranges(to_x(*start)..=to_x(*end), track.y_range());
+    painter.rect_filled(kept, 3.0, visuals.selection.bg_fill);
+    
+    for seconds in [*start, *end] {
+        let handle = Rect::from_center_size(pos2(to_x(seconds), rect.center().y), vec2(8.0, rect.height()));
+        painter.rect_filled(handle, 2.0, visuals.selection.stroke.color);
+    }
+    
+    response.on_hover_cursor(CursorIcon::ResizeHorizontal)
+}
@@ -2,8 +2,10 @@ pub mod drop_zone;
 pub mod size_slider;
 pub mod preview_panel;
 pub mod progress_bar;
+pub mod edit_panel;
 
 pub use drop_zone::DropZone;
 pub use size_slider::SizeSlider;
-pub use preview_panel::PreviewPanel;
+pub use preview_panel::{output_timestamp, PreviewPanel, ThumbnailGenerator};
 pub use progress_bar::ProgressBar;
+pub use edit_panel::{EditOutcome, EditPanel};
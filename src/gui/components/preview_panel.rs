@@ -1,112 +1,235 @@
+use anyhow::{Result, anyhow};
 use eframe::egui::*;
-use crate::gui::state::PreviewData;
+use std::path::Path;
+use std::process::Stdio;
 
-#[derive(Debug, Default)]
+use small_mp4::compression::{format_timestamp, get_video_metadata, FfmpegInvocation, FilterChain, FramePipeline, ScaleMode, VideoEdit, VideoFilter};
+use crate::gui::state::{PreviewData, Thumbnail};
+
+/// How the original and compressed frames are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareMode {
+    /// Next to each other
+    #[default]
+    SideBySide,
+    /// On top of each other, split by a divider that can be dragged
+    Wipe,
+}
+
+/// Compares frames of the original and the compressed video at the same
+/// timestamp
 pub struct PreviewPanel {
-    pub loading: bool,
     pub show_details: bool,
+    pub mode: CompareMode,
+    /// Seconds into the video of the shown frames; `None` until its length is known
+    pub position: Option<f64>,
+    /// Wipe divider, from 0 (all compressed) to 1 (all original)
+    pub split: f32,
+    /// Position of the frames last asked for
+    requested: Option<f64>,
+    /// Uploaded frames of the original and the compressed video, with their timestamps
+    textures: [Option<(f64, TextureHandle)>; 2],
 }
 
-impl PreviewPanel {
-    pub fn new() -> Self {
+impl Default for PreviewPanel {
+    fn default() -> Self {
         Self {
-            loading: false,
             show_details: false,
+            mode: CompareMode::default(),
+            position: None,
+            split: 0.5,
+            requested: None,
+            textures: [None, None],
         }
     }
+}
+
+impl PreviewPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
     
-    pub fn show(&mut self, ui: &mut Ui, original: &Option<PreviewData>, compressed: &Option<PreviewData>) {
+    /// Forget the frames and position of the previous video
+    pub fn reset(&mut self) {
+        *self = Self { show_details: self.show_details, mode: self.mode, ..Self::default() };
+    }
+    
+    /// Draw the comparison. Returns the timestamp to extract new frames at,
+    /// if the position changed.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        text: &dyn Fn(&str) -> String,
+        original: &Option<PreviewData>,
+        compressed: &Option<PreviewData>,
+        error: Option<&str>,
+    ) -> Option<f64> {
+        if let Some(error) = error {
+            ui.colored_label(Color32::from_rgb(220, 80, 80), error);
+        }
+        
+        let Some(original) = original else {
+            if error.is_none() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label(text("loading_preview"));
+                });
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+            }
+            return None;
+        };
+        
+        self.update_textures(ui.ctx(), Some(original), compressed.as_ref());
+        
+        // Start in the middle, where the content usually is
+        let mut request = None;
+        let last_frame = (original.duration - 0.1).max(0.0);
+        let mut position = *self.position.get_or_insert_with(|| {
+            request = Some(last_frame / 2.0);
+            last_frame / 2.0
+        });
+        
         ui.horizontal(|ui| {
-            // Original preview
-            ui.group(|ui| {
-                self.show_preview_side(ui, "Original", original, true);
-            });
+            ui.selectable_value(&mut self.mode, CompareMode::SideBySide, text("side_by_side"));
+            ui.selectable_value(&mut self.mode, CompareMode::Wipe, text("wipe"));
             
-            ui.add_space(10.0);
-            
-            // Compressed preview
-            ui.group(|ui| {
-                self.show_preview_side(ui, "Preview", compressed, false);
-            });
+            let loading = error.is_none()
+                && self.requested.is_some()
+                && self.textures[0].as_ref().map(|(timestamp, _)| *timestamp) != self.requested;
+            if loading {
+                ui.spinner();
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+            }
         });
         
+        ui.spacing_mut().slider_width = (ui.available_width() - 70.0).max(100.0);
+        let response = ui.add(
+            Slider::new(&mut position, 0.0..=last_frame)
+                .custom_formatter(|seconds, _| format_timestamp(seconds)),
+        );
+        self.position = Some(position);
+        // Decode once the handle is let go, not at every step of a drag
+        if response.drag_stopped() || (response.changed() && !response.dragged()) {
+            request = Some(position);
+        }
+        
+        ui.add_space(5.0);
+        self.show_frames(ui, text);
+        
         // Details toggle
         ui.add_space(5.0);
         ui.horizontal(|ui| {
-            if ui.small_button(if self.show_details { "Hide Details" } else { "Show Details" }).clicked() {
+            if ui.small_button(if self.show_details { text("hide_details") } else { text("show_details") }).clicked() {
                 self.show_details = !self.show_details;
             }
         });
         
         // Show detailed comparison if enabled
         if self.show_details {
-            self.show_comparison_details(ui, original, compressed);
+            self.show_comparison_details(ui, text, original, compressed);
         }
+        
+        if request.is_some() {
+            self.requested = request;
+        }
+        request
     }
     
-    fn show_preview_side(&mut self, ui: &mut Ui, title: &str, data: &Option<PreviewData>, is_original: bool) {
-        ui.vertical_centered(|ui| {
-            // Title
-            ui.strong(title);
-            ui.add_space(5.0);
-            
-            // Preview area (150x100)
-            let preview_size = [150.0, 100.0];
-            let (rect, _response) = ui.allocate_exact_size(preview_size.into(), Sense::hover());
+    /// Upload frames that changed since the last call
+    fn update_textures(&mut self, ctx: &Context, original: Option<&PreviewData>, compressed: Option<&PreviewData>) {
+        for (slot, data) in self.textures.iter_mut().zip([original, compressed]) {
+            let Some(thumbnail) = data.and_then(|data| data.thumbnail.as_ref()) else {
+                *slot = None;
+                continue;
+            };
+            if slot.as_ref().is_some_and(|(timestamp, _)| *timestamp == thumbnail.timestamp) {
+                continue;
+            }
             
-            // Background
+            let image = ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width as usize, thumbnail.height as usize],
+                &thumbnail.rgba,
+            );
+            *slot = Some((thumbnail.timestamp, ctx.load_texture("preview_frame", image, TextureOptions::LINEAR)));
+        }
+    }
+    
+    fn show_frames(&mut self, ui: &mut Ui, text: &dyn Fn(&str) -> String) {
+        let [original, compressed] = &self.textures;
+        let Some((_, original)) = original else {
+            let (rect, _) = ui.allocate_exact_size(vec2(ui.available_width(), 200.0), Sense::hover());
             ui.painter().rect_filled(rect, 4.0, ui.style().visuals.extreme_bg_color);
-            
-            if let Some(preview_data) = data {
-                // Draw thumbnail if available
-                if let Some(_thumbnail) = &preview_data.thumbnail {
-                    // TODO: Implement actual image rendering
-                    ui.painter().text(
-                        rect.center(),
-                        Align2::CENTER_CENTER,
-                        "🎬",
-                        FontId::proportional(32.0),
-                        ui.style().visuals.text_color()
-                    );
-                } else {
-                    // Loading or placeholder
-                    ui.painter().text(
-                        rect.center(),
-                        Align2::CENTER_CENTER,
-                        if self.loading { "⏳" } else { "📹" },
-                        FontId::proportional(32.0),
-                        ui.style().visuals.weak_text_color()
-                    );
+            ui.painter().text(rect.center(), Align2::CENTER_CENTER, "⏳", FontId::proportional(32.0), ui.style().visuals.weak_text_color());
+            return;
+        };
+        let aspect = original.aspect_ratio();
+        
+        match (self.mode, compressed) {
+            // Cropped or rotated outputs don't line up with the original
+            (CompareMode::Wipe, Some((_, compressed))) if (compressed.aspect_ratio() - aspect).abs() < 0.01 => {
+                let width = ui.available_width().min(MAX_FRAME_HEIGHT * aspect);
+                let (rect, response) = ui.allocate_exact_size(vec2(width, width / aspect), Sense::click_and_drag());
+                if let Some(pointer) = response.interact_pointer_pos() {
+                    self.split = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
                 }
+                response.on_hover_cursor(CursorIcon::ResizeHorizontal);
                 
-                ui.add_space(5.0);
-                
-                // Basic info
-                ui.label(format!("{}x{}", preview_data.width, preview_data.height));
-                ui.label(format!("{:.1}s", preview_data.duration));
-                ui.label(format!("{:.1} MB", preview_data.file_size as f64 / 1_048_576.0));
-            } else {
-                // No data state
-                let icon = if is_original { "📁" } else { "⏳" };
-                let text = if is_original { "No file selected" } else { "Ready to compress" };
-                
-                ui.painter().text(
-                    rect.center(),
-                    Align2::CENTER_CENTER,
-                    icon,
-                    FontId::proportional(32.0),
-                    ui.style().visuals.weak_text_color()
-                );
+                // The original left of the divider, the compressed video right of it
+                let divider = rect.left() + rect.width() * self.split;
+                let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+                let painter = ui.painter_at(rect);
+                painter.image(original.id(), rect, uv, Color32::WHITE);
+                painter.with_clip_rect(Rect::from_min_max(pos2(divider, rect.top()), rect.max))
+                    .image(compressed.id(), rect, uv, Color32::WHITE);
+                painter.vline(divider, rect.y_range(), Stroke::new(2.0, Color32::WHITE));
                 
-                ui.add_space(5.0);
-                ui.label(RichText::new(text)
-                    .size(11.0)
-                    .color(ui.style().visuals.weak_text_color()));
+                for (corner, anchor, label) in [
+                    (rect.left_top() + vec2(6.0, 6.0), Align2::LEFT_TOP, text("original")),
+                    (rect.right_top() + vec2(-6.0, 6.0), Align2::RIGHT_TOP, text("compressed")),
+                ] {
+                    let galley = painter.layout_no_wrap(label, FontId::proportional(13.0), Color32::WHITE);
+                    let label_rect = anchor.anchor_size(corner, galley.size());
+                    painter.rect_filled(label_rect.expand(3.0), 3.0, Color32::from_black_alpha(140));
+                    painter.galley(label_rect.min, galley, Color32::WHITE);
+                }
             }
-        });
+            // Side by side, or the original alone before it is compressed
+            _ => {
+                let width = ((ui.available_width() - ui.spacing().item_spacing.x) / 2.0).min(MAX_FRAME_HEIGHT * aspect);
+                let size = vec2(width, width / aspect);
+                ui.horizontal_top(|ui| {
+                    ui.vertical(|ui| {
+                        ui.strong(text("original"));
+                        ui.add(Image::new((original.id(), size)));
+                    });
+                    ui.vertical(|ui| {
+                        ui.strong(text("compressed"));
+                        match compressed {
+                            Some((_, compressed)) => {
+                                // Fit the output's own shape into the original's box
+                                let scale = (size.x / compressed.aspect_ratio()).min(size.y) / size.y;
+                                let size = vec2(size.y * compressed.aspect_ratio(), size.y) * scale;
+                                ui.add(Image::new((compressed.id(), size)));
+                            }
+                            None => {
+                                let (rect, _) = ui.allocate_exact_size(size, Sense::hover());
+                                ui.painter().rect_filled(rect, 4.0, ui.style().visuals.extreme_bg_color);
+                                ui.painter().text(
+                                    rect.center(),
+                                    Align2::CENTER_CENTER,
+                                    text("not_compressed_yet"),
+                                    FontId::proportional(13.0),
+                                    ui.style().visuals.weak_text_color(),
+                                );
+                            }
+                        }
+                    });
+                });
+            }
+        }
     }
     
-    fn show_comparison_details(&mut self, ui: &mut Ui, original: &Option<PreviewData>, compressed: &Option<PreviewData>) {
+    fn show_comparison_details(&mut self, ui: &mut Ui, text: &dyn Fn(&str) -> String, original: &PreviewData, compressed: &Option<PreviewData>) {
         ui.add_space(10.0);
         ui.separator();
         ui.add_space(5.0);
@@ -114,66 +237,62 @@ impl PreviewPanel {
         ui.columns(2, |columns| {
             // Original details
             columns[0].group(|ui| {
-                ui.strong("Original Details");
+                ui.strong(text("original_details"));
                 ui.add_space(5.0);
                 
-                if let Some(data) = original {
-                    self.show_detailed_info(ui, data);
-                } else {
-                    ui.label("No file loaded");
-                }
+                self.show_detailed_info(ui, text, original);
             });
             
             // Compressed details
             columns[1].group(|ui| {
-                ui.strong("Compressed Details");
+                ui.strong(text("compressed_details"));
                 ui.add_space(5.0);
                 
                 if let Some(data) = compressed {
-                    self.show_detailed_info(ui, data);
+                    self.show_detailed_info(ui, text, data);
                 } else {
-                    ui.label("Not compressed yet");
+                    ui.label(text("not_compressed_yet"));
                 }
             });
         });
         
         // Comparison stats
-        if let (Some(orig), Some(comp)) = (original, compressed) {
+        if let Some(comp) = compressed {
             ui.add_space(10.0);
-            self.show_comparison_stats(ui, orig, comp);
+            self.show_comparison_stats(ui, text, original, comp);
         }
     }
     
-    fn show_detailed_info(&mut self, ui: &mut Ui, data: &PreviewData) {
+    fn show_detailed_info(&mut self, ui: &mut Ui, text: &dyn Fn(&str) -> String, data: &PreviewData) {
         ui.horizontal(|ui| {
-            ui.label("Resolution:");
+            ui.label(text("resolution"));
             ui.label(format!("{}×{}", data.width, data.height));
         });
         
         ui.horizontal(|ui| {
-            ui.label("Duration:");
+            ui.label(text("duration"));
             ui.label(format!("{:.2}s", data.duration));
         });
         
         ui.horizontal(|ui| {
-            ui.label("File Size:");
+            ui.label(text("file_size"));
             ui.label(format!("{:.2} MB", data.file_size as f64 / 1_048_576.0));
         });
         
         ui.horizontal(|ui| {
-            ui.label("Codec:");
+            ui.label(text("codec"));
             ui.label(&data.codec);
         });
         
         ui.horizontal(|ui| {
-            ui.label("Bitrate:");
+            ui.label(text("bitrate"));
             ui.label(format!("{} kbps", data.bitrate));
         });
     }
     
-    fn show_comparison_stats(&mut self, ui: &mut Ui, original: &PreviewData, compressed: &PreviewData) {
+    fn show_comparison_stats(&mut self, ui: &mut Ui, text: &dyn Fn(&str) -> String, original: &PreviewData, compressed: &PreviewData) {
         ui.group(|ui| {
-            ui.strong("Compression Results");
+            ui.strong(text("compression_results"));
             ui.add_space(5.0);
             
             // Size reduction
@@ -183,7 +302,7 @@ impl PreviewPanel {
             let compression_ratio = orig_size / comp_size;
             
             ui.horizontal(|ui| {
-                ui.label("Size reduction:");
+                ui.label(text("size_reduction"));
                 let color = if reduction_percent > 0.0 {
                     Color32::DARK_GREEN
                 } else {
@@ -193,7 +312,7 @@ impl PreviewPanel {
             });
             
             ui.horizontal(|ui| {
-                ui.label("Compression ratio:");
+                ui.label(text("compression_ratio"));
                 ui.label(format!("{:.1}:1", compression_ratio));
             });
             
@@ -206,7 +325,7 @@ impl PreviewPanel {
             
             if bitrate_change.abs() > 5.0 {
                 ui.horizontal(|ui| {
-                    ui.label("Bitrate change:");
+                    ui.label(text("bitrate_change"));
                     let color = if bitrate_change < 0.0 {
                         Color32::ORANGE
                     } else {
@@ -219,33 +338,137 @@ impl PreviewPanel {
     }
 }
 
-/// Preview thumbnail generation (placeholder for actual implementation)
+/// Tallest the frames are drawn, in points
+const MAX_FRAME_HEIGHT: f32 = 360.0;
+
+/// Extracts frames and stream details of videos with FFmpeg
+#[derive(Debug, Clone, Copy)]
 pub struct ThumbnailGenerator {
-    // This would interface with FFmpeg to generate thumbnails
+    /// Largest frame extracted; smaller videos keep their size
+    pub max_width: u32,
+    pub max_height: u32,
+}
+
+impl Default for ThumbnailGenerator {
+    fn default() -> Self {
+        Self { max_width: 960, max_height: 540 }
+    }
 }
 
 impl ThumbnailGenerator {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+    
+    /// Size frames of a `width`x`height` video are extracted at: the same
+    /// shape, within the maximum, never upscaled, with even sides
+    pub fn frame_size(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = (self.max_width as f64 / width.max(1) as f64)
+            .min(self.max_height as f64 / height.max(1) as f64)
+            .min(1.0);
+        let even = |side: u32| ((side as f64 * scale / 2.0) as u32 * 2).max(2);
+        (even(width), even(height))
     }
     
-    pub async fn generate_thumbnail(&self, _video_path: &std::path::Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        // TODO: Implement actual thumbnail generation using FFmpeg
-        // For now, return a placeholder
-        Ok(vec![0; 150 * 100 * 4]) // RGBA placeholder
+    /// FFmpeg writing the frame at `timestamp` to stdout as raw RGBA pixels.
+    ///
+    /// Every video is scaled to the same `size`, so frames of the original
+    /// and the compressed video line up.
+    pub fn frame_invocation(&self, video_path: &Path, timestamp: f64, (width, height): (u32, u32)) -> FfmpegInvocation {
+        let mut invocation = FfmpegInvocation::new(video_path, "-");
+        invocation.global_arg("-v").global_arg("error").global_arg("-nostdin");
+        // Seeking before the input is fast, and still frame accurate
+        invocation.input_arg("-ss").input_arg(format!("{:.3}", timestamp));
+        let scale = FilterChain::from_iter([VideoFilter::Scale { width, height, mode: ScaleMode::Exact }]);
+        invocation.filter_chain(&scale, FramePipeline::Software);
+        invocation.codec_args(["-frames:v", "1", "-an"]);
+        invocation.outputs[0].options = ["-f", "rawvideo", "-pix_fmt", "rgba"].map(String::from).to_vec();
+        invocation
     }
     
-    pub async fn get_video_info(&self, _video_path: &std::path::Path) -> Result<PreviewData, Box<dyn std::error::Error>> {
-        // TODO: Implement actual video analysis using FFmpeg
-        // For now, return placeholder data
+    /// Decode the frame at `timestamp`, scaled to `size`
+    pub async fn generate_thumbnail(&self, video_path: &Path, timestamp: f64, size: (u32, u32)) -> Result<Thumbnail> {
+        let invocation = self.frame_invocation(video_path, timestamp, size);
+        let output = tokio::process::Command::new(&invocation.program)
+            .args(invocation.args())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| anyhow!("Failed to run FFmpeg: {}. Is ffmpeg installed?", e))?;
+        
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("FFmpeg could not decode {}: {}", video_path.display(), stderr.trim()));
+        }
+        
+        let (width, height) = size;
+        let mut rgba = output.stdout;
+        let frame_len = width as usize * height as usize * 4;
+        if rgba.len() < frame_len {
+            return Err(anyhow!("No frame at {} in {}", format_timestamp(timestamp), video_path.display()));
+        }
+        rgba.truncate(frame_len);
+        
+        Ok(Thumbnail { timestamp, width, height, rgba })
+    }
+    
+    /// Dimensions, length, size and codec of a video, without a frame yet
+    pub async fn get_video_info(&self, video_path: &Path) -> Result<PreviewData> {
+        let metadata = get_video_metadata(video_path).await?;
+        let file_size = std::fs::metadata(video_path)?.len();
+        
         Ok(PreviewData {
             thumbnail: None,
-            width: 1920,
-            height: 1080,
-            duration: 120.0,
-            file_size: 50_000_000, // 50 MB
-            codec: "H.264".to_string(),
-            bitrate: 5000,
+            width: metadata.width,
+            height: metadata.height,
+            duration: metadata.duration_seconds as f64,
+            file_size,
+            codec: metadata.codec,
+            bitrate: metadata.bitrate_kbps.unwrap_or_default(),
         })
     }
 }
+
+/// Time in an output trimmed by `edit` showing the frame at `timestamp` of
+/// the original, held at the first or last kept frame outside the kept part
+pub fn output_timestamp(edit: &VideoEdit, timestamp: f64, output_duration: f64) -> f64 {
+    let start = edit.start.unwrap_or(0.0).max(0.0);
+    let last_frame = (output_duration - 0.1).max(0.0);
+    (timestamp - start).clamp(0.0, last_frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_timestamp_follows_trim() {
+        let untrimmed = VideoEdit::default();
+        assert_eq!(output_timestamp(&untrimmed, 12.0, 60.0), 12.0);
+
+        let trimmed = VideoEdit { start: Some(10.0), end: Some(20.0), ..VideoEdit::default() };
+        assert_eq!(output_timestamp(&trimmed, 15.0, 10.0), 5.0);
+        assert_eq!(output_timestamp(&trimmed, 4.0, 10.0), 0.0);
+        assert_eq!(output_timestamp(&trimmed, 45.0, 10.0), 9.9);
+    }
+
+    #[test]
+    fn test_frame_size_keeps_shape_without_upscaling() {
+        let generator = ThumbnailGenerator::new();
+        assert_eq!(generator.frame_size(1920, 1080), (960, 540));
+        assert_eq!(generator.frame_size(1080, 1920), (302, 540));
+        assert_eq!(generator.frame_size(640, 360), (640, 360));
+        assert_eq!(generator.frame_size(721, 405), (720, 404));
+    }
+
+    #[test]
+    fn test_frame_invocation_writes_raw_rgba() {
+        let invocation = ThumbnailGenerator::new().frame_invocation(Path::new("clip.mov"), 12.5, (960, 540));
+        assert_eq!(
+            invocation.args().join(" "),
+            "-v error -nostdin -ss 12.500 -i clip.mov -vf scale=960:540 -frames:v 1 -an -f rawvideo -pix_fmt rgba -"
+        );
+        assert_eq!(format_timestamp(75.04), "1:15.0");
+    }
+}
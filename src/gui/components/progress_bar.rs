@@ -62,7 +62,7 @@ impl ProgressBar {
             
             // Progress fill
             if progress > 0.0 {
-                let fill_width = (bar_rect.width() * progress.clamp(0.0, 1.0)) as f32;
+                let fill_width = bar_rect.width() * progress.clamp(0.0, 1.0);
                 let fill_rect = Rect::from_min_size(
                     bar_rect.min,
                     [fill_width, bar_height].into()
@@ -124,11 +124,10 @@ impl ProgressBar {
             // Control buttons
             ui.horizontal(|ui| {
                 // Pause/Resume button
-                if progress > 0.0 && progress < 1.0 {
-                    if ui.small_button("⏸ Pause").clicked() {
+                if progress > 0.0 && progress < 1.0
+                    && ui.small_button("⏸ Pause").clicked() {
                         response.pause_clicked = true;
                     }
-                }
                 
                 // Cancel button
                 if progress > 0.0 && progress < 1.0 {
@@ -308,7 +307,7 @@ mod tests {
     
     #[test]
     fn test_progress_clamping() {
-        let mut bar = ProgressBar::new();
+        let _bar = ProgressBar::new();
         // Test that progress values are properly clamped in the UI logic
         assert_eq!((-0.5_f32).clamp(0.0, 1.0), 0.0);
         assert_eq!(1.5_f32.clamp(0.0, 1.0), 1.0);
@@ -1,5 +1,5 @@
 use eframe::egui::*;
-use crate::compression::TargetSize;
+use small_mp4::compression::{FileSize, TargetSize};
 
 #[derive(Debug, Default)]
 pub struct SizeSlider {
@@ -15,7 +15,7 @@ impl SizeSlider {
         }
     }
     
-    pub fn show(&mut self, ui: &mut Ui, current_size: &mut TargetSize, auto_enabled: bool) -> bool {
+    pub fn show(&mut self, ui: &mut Ui, current_size: &mut FileSize, auto_enabled: bool) -> bool {
         let mut changed = false;
         
         ui.horizontal(|ui| {
@@ -29,7 +29,7 @@ impl SizeSlider {
             ];
             
             for (i, (size, label, tooltip)) in sizes.iter().enumerate() {
-                let selected = *current_size == *size && !auto_enabled;
+                let selected = *current_size == FileSize::from(*size) && !auto_enabled;
                 
                 // Button styling
                 let button_color = if selected {
@@ -85,7 +85,7 @@ impl SizeSlider {
                 
                 // Handle click
                 if response.clicked() && !auto_enabled {
-                    *current_size = *size;
+                    *current_size = (*size).into();
                     changed = true;
                 }
                 
@@ -108,16 +108,18 @@ impl SizeSlider {
             ui.label("Size:");
             
             // Progress bar showing relative size
-            let progress = match current_size {
-                TargetSize::Size1MB => 0.1,
-                TargetSize::Size5MB => 0.2,
-                TargetSize::Size10MB => 0.3,
-                TargetSize::Size30MB => 0.4,
-                TargetSize::Size50MB => 0.5,
-                TargetSize::Size100MB => 0.6,
-                TargetSize::Size250MB => 0.7,
-                TargetSize::Size500MB => 0.8,
-                TargetSize::Size1000MB => 1.0,
+            let progress = match current_size.preset() {
+                Some(TargetSize::Size1MB) => 0.1,
+                Some(TargetSize::Size5MB) => 0.2,
+                Some(TargetSize::Size10MB) => 0.3,
+                Some(TargetSize::Size30MB) => 0.4,
+                Some(TargetSize::Size50MB) => 0.5,
+                Some(TargetSize::Size100MB) => 0.6,
+                Some(TargetSize::Size250MB) => 0.7,
+                Some(TargetSize::Size500MB) => 0.8,
+                Some(TargetSize::Size1000MB) => 1.0,
+                // Custom sizes: log scale from 1 MB to 1 GB
+                None => (current_size.as_mb().max(1.0).log10() / 3.0).clamp(0.05, 1.0) as f32,
             };
             
             let bar_rect = Rect::from_min_size(
@@ -156,7 +158,7 @@ impl SizeSlider {
             let size_text = if auto_enabled {
                 "Auto".to_string()
             } else {
-                format!("{} MB", current_size.as_mb() as u32)
+                current_size.to_string()
             };
             
             ui.label(RichText::new(size_text).strong());
@@ -1,75 +1,69 @@
 #![allow(dead_code)]
 pub mod app;
 pub mod components;
+pub mod queue;
+pub mod settings;
 pub mod state;
 pub mod utils;
 
 pub use app::SmallMp4App;
 
 // GUI module
+use eframe::egui;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Main GUI configuration and theme settings
 #[derive(Debug, Clone)]
 pub struct GuiConfig {
     pub theme: Theme,
-    pub language: Language,
+    /// Code of the message catalog, e.g. `ko`
+    pub language: String,
     pub auto_save_location: bool,
     pub remember_settings: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Theme {
     Light,
     Dark,
     Auto,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Language {
-    English,
-    Korean,
-    Japanese,
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Auto, Theme::Light, Theme::Dark];
+    
+    /// egui's equivalent; `Auto` follows the system
+    pub fn preference(&self) -> egui::ThemePreference {
+        match self {
+            Theme::Light => egui::ThemePreference::Light,
+            Theme::Dark => egui::ThemePreference::Dark,
+            Theme::Auto => egui::ThemePreference::System,
+        }
+    }
+    
+    /// Translation key for the theme's label
+    pub fn text_key(&self) -> &'static str {
+        match self {
+            Theme::Light => "theme_light",
+            Theme::Dark => "theme_dark",
+            Theme::Auto => "theme_auto",
+        }
+    }
 }
 
 impl Default for GuiConfig {
     fn default() -> Self {
         Self {
             theme: Theme::Auto,
-            language: Self::detect_system_language(),
+            language: crate::i18n::detect_system_language(),
             auto_save_location: true,
             remember_settings: true,
         }
     }
 }
 
-impl GuiConfig {
-    fn detect_system_language() -> Language {
-        // Get system language from environment
-        if let Ok(lang) = std::env::var("LANG") {
-            let lang = lang.to_lowercase();
-            if lang.starts_with("ko") || lang.contains("korean") {
-                return Language::Korean;
-            } else if lang.starts_with("ja") || lang.contains("japanese") {
-                return Language::Japanese;
-            }
-        }
-        
-        // Fallback to LANGUAGE variable
-        if let Ok(lang) = std::env::var("LANGUAGE") {
-            let lang = lang.to_lowercase();
-            if lang.starts_with("ko") {
-                return Language::Korean;
-            } else if lang.starts_with("ja") {
-                return Language::Japanese;
-            }
-        }
-        
-        // Default to English
-        Language::English
-    }
-}
-
 /// GUI-specific errors
 #[derive(thiserror::Error, Debug)]
 pub enum GuiError {
@@ -0,0 +1,382 @@
+//! The files the GUI compresses, in order.
+//!
+//! Items are started from the front of the queue, one at a time or several
+//! at once, and keep their own settings, progress and result. Anything that
+//! is not running can be reordered, removed or retried.
+
+use std::path::{Path, PathBuf};
+
+use small_mp4::compression::{CancellationToken, Cancelled, CompressionJob, CompressionResult, CompressionSettings, EncodingPlan};
+
+use super::state::ProgressState;
+use crate::cli::batch::BatchInput;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ItemStatus {
+    Waiting,
+    Running,
+    Done {
+        output: PathBuf,
+        input_size_mb: f64,
+        output_size_mb: f64,
+        target_met: bool,
+    },
+    Failed(String),
+    Cancelled,
+}
+
+impl ItemStatus {
+    /// Whether the item can be started again with [`Queue::retry`]
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ItemStatus::Failed(_) | ItemStatus::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueItem {
+    pub id: u64,
+    pub input: PathBuf,
+    /// Path below the folder it was dropped with, mirrored in the output folder
+    pub relative: PathBuf,
+    pub settings: CompressionSettings,
+    /// Config profile the settings came from; `None` for the main window's settings
+    pub profile: Option<String>,
+    pub status: ItemStatus,
+    pub progress: ProgressState,
+    pub plan: Option<EncodingPlan>,
+    /// Where the item is written, set when it starts
+    output: Option<PathBuf>,
+    cancel: Option<CancellationToken>,
+}
+
+impl QueueItem {
+    pub fn file_name(&self) -> String {
+        self.input.file_name().unwrap_or_default().to_string_lossy().into_owned()
+    }
+
+    /// Whether settings can still be changed
+    pub fn is_editable(&self) -> bool {
+        matches!(self.status, ItemStatus::Waiting) || self.status.is_retryable()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Queue {
+    items: Vec<QueueItem>,
+    next_id: u64,
+    /// Start several items at once, within the scheduler's limits
+    pub parallel: bool,
+    /// Set by cancel: start nothing new
+    pub stop_requested: bool,
+}
+
+impl Queue {
+    pub fn items(&self) -> &[QueueItem] {
+        &self.items
+    }
+
+    pub fn item_mut(&mut self, id: u64) -> Option<&mut QueueItem> {
+        self.items.iter_mut().find(|item| item.id == id)
+    }
+
+    fn position(&self, id: u64) -> Option<usize> {
+        self.items.iter().position(|item| item.id == id)
+    }
+
+    /// Append `input`, unless it is already waiting or running
+    pub fn add(&mut self, input: BatchInput, settings: CompressionSettings) -> bool {
+        let queued = self.items.iter()
+            .any(|item| item.input == input.path && matches!(item.status, ItemStatus::Waiting | ItemStatus::Running));
+        if queued {
+            return false;
+        }
+
+        self.next_id += 1;
+        self.items.push(QueueItem {
+            id: self.next_id,
+            input: input.path,
+            relative: input.relative,
+            settings,
+            profile: None,
+            status: ItemStatus::Waiting,
+            progress: ProgressState::default(),
+            plan: None,
+            output: None,
+            cancel: None,
+        });
+        true
+    }
+
+    /// Move an item one place towards the front (`-1`) or the back (`1`)
+    pub fn shift(&mut self, id: u64, offset: isize) {
+        let Some(from) = self.position(id) else { return };
+        let Some(to) = from.checked_add_signed(offset).filter(|&to| to < self.items.len()) else { return };
+        self.items.swap(from, to);
+    }
+
+    /// Drop an item that is not running
+    pub fn remove(&mut self, id: u64) -> bool {
+        match self.position(id) {
+            Some(index) if self.items[index].status != ItemStatus::Running => {
+                self.items.remove(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Queue a failed or cancelled item again
+    pub fn retry(&mut self, id: u64) {
+        if let Some(item) = self.item_mut(id).filter(|item| item.status.is_retryable()) {
+            item.status = ItemStatus::Waiting;
+            item.progress = ProgressState::default();
+        }
+    }
+
+    /// Give every item that has not finished `settings`, keeping the
+    /// trimming, cropping and rotation picked for each file
+    pub fn apply_to_all(&mut self, settings: &CompressionSettings) {
+        for item in self.items.iter_mut().filter(|item| item.is_editable()) {
+            item.settings = CompressionSettings { edit: item.settings.edit, ..settings.clone() };
+            item.profile = None;
+        }
+    }
+
+    /// Drop the items that compressed successfully
+    pub fn clear_finished(&mut self) {
+        self.items.retain(|item| !matches!(item.status, ItemStatus::Done { .. }));
+    }
+
+    pub fn has_waiting(&self) -> bool {
+        self.items.iter().any(|item| item.status == ItemStatus::Waiting)
+    }
+
+    /// Whether `path` is written by an item that is running or finished
+    pub fn is_output(&self, path: &Path) -> bool {
+        self.taken_outputs().any(|output| output == path)
+    }
+
+    fn taken_outputs(&self) -> impl Iterator<Item = &Path> {
+        self.items.iter()
+            .filter(|item| matches!(item.status, ItemStatus::Running | ItemStatus::Done { .. }))
+            .filter_map(|item| item.output.as_deref())
+    }
+
+    /// Mark the first waiting item as running and return its job and token.
+    ///
+    /// `output_for` picks the output path of an item. A path another running
+    /// or finished item writes, or that is queued as an input, gets a
+    /// `_2`, `_3`... suffix instead.
+    pub fn start_next(&mut self, output_for: impl Fn(&QueueItem) -> Option<PathBuf>) -> Option<(u64, CompressionJob, CancellationToken)> {
+        let index = self.items.iter().position(|item| item.status == ItemStatus::Waiting)?;
+        let output = output_for(&self.items[index]).map(|output| {
+            let taken: Vec<&Path> = self.taken_outputs()
+                .chain(self.items.iter().map(|item| item.input.as_path()))
+                .collect();
+            unique_output(output, &taken)
+        });
+
+        let item = &mut self.items[index];
+        let cancel = CancellationToken::new();
+        let job = CompressionJob {
+            input: item.input.clone(),
+            output: output.clone(),
+            settings: item.settings.clone(),
+        };
+
+        item.output = output;
+        item.status = ItemStatus::Running;
+        item.progress = ProgressState::default();
+        item.plan = None;
+        item.cancel = Some(cancel.clone());
+        Some((item.id, job, cancel))
+    }
+
+    /// Record how an item's job ended
+    pub fn finish(&mut self, id: u64, result: &anyhow::Result<CompressionResult>) {
+        let Some(item) = self.item_mut(id) else { return };
+        item.cancel = None;
+        item.status = match result {
+            Ok(result) => {
+                item.plan = Some(result.plan.clone());
+                ItemStatus::Done {
+                    output: result.output_path.clone(),
+                    input_size_mb: result.input_size_mb,
+                    output_size_mb: result.output_size_mb,
+                    target_met: result.target_met,
+                }
+            }
+            Err(e) if e.is::<Cancelled>() => ItemStatus::Cancelled,
+            Err(e) => ItemStatus::Failed(format!("{:#}", e)),
+        };
+    }
+
+    /// Tokens of the running items, to pause, resume or cancel them
+    pub fn running_tokens(&self) -> impl Iterator<Item = &CancellationToken> {
+        self.items.iter().filter_map(|item| item.cancel.as_ref())
+    }
+
+    /// Cancel one running item
+    pub fn cancel(&self, id: u64) {
+        if let Some(token) = self.items.iter().find(|item| item.id == id).and_then(|item| item.cancel.as_ref()) {
+            token.cancel();
+        }
+    }
+
+    /// Share of the queue that is finished, counting running items by their progress
+    pub fn progress(&self) -> f32 {
+        if self.items.is_empty() {
+            return 0.0;
+        }
+        let done: f32 = self.items.iter()
+            .map(|item| match item.status {
+                ItemStatus::Waiting => 0.0,
+                ItemStatus::Running => item.progress.overall_progress,
+                _ => 1.0,
+            })
+            .sum();
+        done / self.items.len() as f32
+    }
+}
+
+/// `<stem>_small.mp4` next to the input, or under `output_folder` keeping
+/// the item's relative path
+pub fn output_path(item: &QueueItem, output_folder: Option<&Path>) -> Option<PathBuf> {
+    let stem = item.input.file_stem()?.to_string_lossy();
+    let file_name = format!("{}_small.mp4", stem);
+    match output_folder {
+        Some(folder) => Some(folder.join(&item.relative).with_file_name(file_name)),
+        None => Some(item.input.with_file_name(file_name)),
+    }
+}
+
+/// `output`, or the first of `<stem>_2.mp4`, `<stem>_3.mp4`... not in `taken`
+fn unique_output(output: PathBuf, taken: &[&Path]) -> PathBuf {
+    if !taken.contains(&output.as_path()) {
+        return output;
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+    (2..)
+        .map(|n| output.with_file_name(format!("{}_{}.mp4", stem, n)))
+        .find(|candidate| !taken.contains(&candidate.as_path()))
+        .unwrap_or(output)
+}
+
+/// Whether `path` looks like the output of compressing one of `inputs`
+/// into the same folder, e.g. `a_small.mp4` or `a_small_2.mp4` next to `a.mov`
+pub fn is_previous_output(path: &Path, inputs: &[BatchInput]) -> bool {
+    if !path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("mp4")) {
+        return false;
+    }
+    let Some(stem) = path.file_stem().map(|stem| stem.to_string_lossy()) else { return false };
+
+    inputs.iter()
+        .filter(|input| input.path != path && input.path.parent() == path.parent())
+        .filter_map(|input| input.path.file_stem())
+        .any(|source| {
+            let Some(suffix) = stem.strip_prefix(&format!("{}_small", source.to_string_lossy())) else { return false };
+            suffix.is_empty() || suffix.strip_prefix('_').is_some_and(|n| n.parse::<u32>().is_ok())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use small_mp4::compression::VideoEdit;
+
+    fn input(path: &str) -> BatchInput {
+        BatchInput { path: PathBuf::from(path), relative: PathBuf::from(Path::new(path).file_name().unwrap()) }
+    }
+
+    fn names(queue: &Queue) -> Vec<String> {
+        queue.items().iter().map(QueueItem::file_name).collect()
+    }
+
+    #[test]
+    fn test_reorder_remove_and_duplicates() {
+        let mut queue = Queue::default();
+        assert!(queue.add(input("a.mov"), CompressionSettings::default()));
+        assert!(queue.add(input("b.mov"), CompressionSettings::default()));
+        assert!(queue.add(input("c.mov"), CompressionSettings::default()));
+        assert!(!queue.add(input("a.mov"), CompressionSettings::default()));
+
+        let c = queue.items()[2].id;
+        queue.shift(c, -1);
+        queue.shift(c, -1);
+        queue.shift(c, -1);
+        assert_eq!(names(&queue), ["c.mov", "a.mov", "b.mov"]);
+
+        let (id, job, _) = queue.start_next(|item| output_path(item, None)).unwrap();
+        assert_eq!(id, c);
+        assert_eq!(job.output, Some(PathBuf::from("c_small.mp4")));
+        assert!(!queue.remove(c), "running items stay");
+
+        let a = queue.items()[1].id;
+        assert!(queue.remove(a));
+        assert_eq!(names(&queue), ["c.mov", "b.mov"]);
+    }
+
+    #[test]
+    fn test_failed_items_retry_and_take_new_settings() {
+        let mut queue = Queue::default();
+        queue.add(input("a.mov"), CompressionSettings::default());
+        queue.add(input("b.mov"), CompressionSettings::default());
+
+        let (a, _, _) = queue.start_next(|_| None).unwrap();
+        queue.finish(a, &Err(anyhow!("ffmpeg exited with status 1")));
+        assert_eq!(queue.items()[0].status, ItemStatus::Failed("ffmpeg exited with status 1".to_string()));
+
+        let trimmed = VideoEdit { start: Some(5.0), ..VideoEdit::default() };
+        queue.item_mut(a).unwrap().settings.edit = trimmed;
+        let settings = CompressionSettings { remove_audio: true, ..CompressionSettings::default() };
+        queue.apply_to_all(&settings);
+        assert!(queue.items().iter().all(|item| item.settings.remove_audio));
+        assert_eq!(queue.items()[0].settings.edit, trimmed, "edits are per file");
+
+        queue.retry(a);
+        let (next, _, _) = queue.start_next(|_| None).unwrap();
+        assert_eq!(next, a, "a retried item keeps its place");
+        queue.finish(next, &Err(Cancelled.into()));
+        assert_eq!(queue.items()[0].status, ItemStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_output_mirrors_dropped_folders() {
+        let mut queue = Queue::default();
+        queue.add(
+            BatchInput { path: PathBuf::from("videos/trip/b.mkv"), relative: PathBuf::from("trip/b.mkv") },
+            CompressionSettings::default(),
+        );
+        let item = &queue.items()[0];
+        assert_eq!(output_path(item, Some(Path::new("out"))), Some(PathBuf::from("out/trip/b_small.mp4")));
+        assert_eq!(output_path(item, None), Some(PathBuf::from("videos/trip/b_small.mp4")));
+    }
+
+    #[test]
+    fn test_same_stem_inputs_get_distinct_outputs() {
+        let mut queue = Queue::default();
+        queue.add(input("vids/a.mov"), CompressionSettings::default());
+        queue.add(input("vids/a.mkv"), CompressionSettings::default());
+        queue.add(input("vids/a.avi"), CompressionSettings::default());
+
+        // The first two run side by side
+        let (first, first_job, _) = queue.start_next(|item| output_path(item, None)).unwrap();
+        let (_, second_job, _) = queue.start_next(|item| output_path(item, None)).unwrap();
+        assert_eq!(first_job.output, Some(PathBuf::from("vids/a_small.mp4")));
+        assert_eq!(second_job.output, Some(PathBuf::from("vids/a_small_2.mp4")));
+
+        // A finished item keeps its output too
+        queue.finish(first, &Err(anyhow!("ffmpeg exited with status 1")));
+        let (_, third_job, _) = queue.start_next(|item| output_path(item, None)).unwrap();
+        assert_eq!(third_job.output, Some(PathBuf::from("vids/a_small.mp4")), "failed items free their output");
+        assert!(queue.is_output(Path::new("vids/a_small_2.mp4")));
+    }
+
+    #[test]
+    fn test_previous_outputs_are_not_inputs() {
+        let inputs = [input("vids/a.mov"), input("vids/a_small.mp4"), input("vids/a_small_2.mp4"), input("vids/b_small.mp4")];
+        let kept: Vec<_> = inputs.iter().filter(|input| !is_previous_output(&input.path, &inputs)).map(|input| input.path.clone()).collect();
+        assert_eq!(kept, [PathBuf::from("vids/a.mov"), PathBuf::from("vids/b_small.mp4")]);
+    }
+}
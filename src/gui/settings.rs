@@ -0,0 +1,142 @@
+//! GUI choices remembered between sessions.
+//!
+//! Saved as JSON next to the shared config file (see
+//! [`cli::config::default_path`]), so the hand-written `config.toml` is never
+//! rewritten by the GUI.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use small_mp4::compression::hardware::{HardwareCapabilities, HardwareEncoder, HardwareQuality};
+use small_mp4::compression::FileSize;
+
+use super::state::AppState;
+use super::{GuiConfig, Theme};
+use crate::cli;
+
+pub const SETTINGS_FILE_NAME: &str = "gui-settings.json";
+
+/// What the GUI restores on startup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SavedSettings {
+    /// When off, only this flag is kept
+    pub remember_settings: bool,
+    pub language: String,
+    pub theme: Theme,
+    pub target_size: FileSize,
+    pub hardware_encoder: HardwareEncoder,
+    pub enable_hardware_accel: bool,
+    pub hardware_quality: HardwareQuality,
+    pub quality_level: u8,
+    pub memory_optimization: bool,
+    pub keep_resolution: bool,
+    pub compatibility_mode: bool,
+    pub remove_audio: bool,
+    pub same_folder: bool,
+    pub output_folder: Option<PathBuf>,
+    /// Where the file picker opens
+    pub input_folder: Option<PathBuf>,
+}
+
+impl SavedSettings {
+    /// `gui-settings.json` in the config directory
+    pub fn default_path() -> Option<PathBuf> {
+        cli::config::default_path().map(|path| path.with_file_name(SETTINGS_FILE_NAME))
+    }
+
+    pub fn capture(config: &GuiConfig, state: &AppState) -> Self {
+        let settings = &state.compression_settings;
+        Self {
+            remember_settings: config.remember_settings,
+            language: config.language.clone(),
+            theme: config.theme,
+            target_size: settings.target_size,
+            hardware_encoder: settings.hardware_encoder,
+            enable_hardware_accel: settings.enable_hardware_accel,
+            hardware_quality: settings.hardware_quality,
+            quality_level: settings.quality_level,
+            memory_optimization: settings.memory_optimization,
+            keep_resolution: settings.keep_resolution,
+            compatibility_mode: settings.compatibility_mode,
+            remove_audio: settings.remove_audio,
+            same_folder: state.same_folder,
+            output_folder: state.output_folder.clone(),
+            input_folder: state.input_folder.clone(),
+        }
+    }
+
+    /// Apply the saved choices. An encoder this machine no longer has keeps
+    /// the detected one.
+    pub fn restore(&self, config: &mut GuiConfig, state: &mut AppState, capabilities: &HardwareCapabilities) {
+        config.remember_settings = self.remember_settings;
+        if !self.remember_settings {
+            return;
+        }
+
+        config.language = self.language.clone();
+        config.theme = self.theme;
+
+        let settings = &mut state.compression_settings;
+        settings.target_size = self.target_size;
+        if self.hardware_encoder == HardwareEncoder::Software
+            || capabilities.available_encoders.contains(&self.hardware_encoder)
+        {
+            settings.hardware_encoder = self.hardware_encoder;
+        }
+        settings.enable_hardware_accel = self.enable_hardware_accel;
+        settings.hardware_quality = self.hardware_quality;
+        settings.quality_level = self.quality_level;
+        settings.memory_optimization = self.memory_optimization;
+        settings.keep_resolution = self.keep_resolution;
+        settings.compatibility_mode = self.compatibility_mode;
+        settings.remove_audio = self.remove_audio;
+
+        state.same_folder = self.same_folder;
+        state.output_folder = self.output_folder.clone().filter(|folder| folder.is_dir());
+        state.input_folder = self.input_folder.clone().filter(|folder| folder.is_dir());
+    }
+
+    /// The saved settings, or `None` if there are none yet
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        cli::json_file::load(path, "GUI settings file")
+    }
+
+    /// Write the settings, replacing the old file only once the new one is complete
+    pub fn save(&self, path: &Path) -> Result<()> {
+        cli::json_file::save(path, self, "GUI settings file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small-mp4").join(SETTINGS_FILE_NAME);
+        assert_eq!(SavedSettings::load(&path).unwrap(), None);
+
+        let mut config = GuiConfig { language: "ja".to_string(), theme: Theme::Dark, ..GuiConfig::default() };
+        let mut state = AppState { same_folder: false, output_folder: Some(dir.path().to_path_buf()), ..AppState::default() };
+        state.compression_settings.target_size = FileSize::from_mb(8.0);
+        state.compression_settings.hardware_encoder = HardwareEncoder::NvencH264;
+        state.compression_settings.remove_audio = true;
+        SavedSettings::capture(&config, &state).save(&path).unwrap();
+
+        config = GuiConfig::default();
+        state = AppState::default();
+        let saved = SavedSettings::load(&path).unwrap().unwrap();
+        saved.restore(&mut config, &mut state, &HardwareCapabilities::software_only());
+
+        assert_eq!(config.language, "ja");
+        assert_eq!(config.theme, Theme::Dark);
+        assert_eq!(state.compression_settings.target_size, FileSize::from_mb(8.0));
+        assert!(state.compression_settings.remove_audio);
+        assert_eq!(state.output_folder.as_deref(), Some(dir.path()));
+        // No NVENC on this machine
+        assert_eq!(state.compression_settings.hardware_encoder, HardwareEncoder::Software);
+    }
+}
@@ -1,27 +1,32 @@
-use std::path::PathBuf;
-use crate::compression::CompressionSettings;
+use std::path::{Path, PathBuf};
+use small_mp4::compression::{CompressionSettings, EncodeStage, EncodingPlan, ProgressEvent};
+
+use super::queue::{self, Queue, QueueItem};
+use crate::cli::batch::{collect_inputs, BatchInput};
 
 /// Application state management
 #[derive(Debug, Clone)]
 pub struct AppState {
     // File handling
-    pub input_file: Option<PathBuf>,
-    pub output_file: Option<PathBuf>,
+    pub queue: Queue,
     pub output_folder: Option<PathBuf>,
     pub same_folder: bool,
+    /// Folder of the last opened input, where the file picker starts
+    pub input_folder: Option<PathBuf>,
     
-    // Compression settings
+    // Settings given to newly queued files
     pub compression_settings: CompressionSettings,
     
     // UI state
     pub status: CompressionStatus,
-    pub progress: f32,
     pub current_operation: String,
-    pub estimated_time: Option<std::time::Duration>,
     
     // Preview state
     pub original_preview: Option<PreviewData>,
     pub compressed_preview: Option<PreviewData>,
+    /// Bumped by every frame request; frames of older requests are dropped
+    pub preview_request: u64,
+    pub preview_error: Option<String>,
     
     // Error handling
     pub last_error: Option<String>,
@@ -31,6 +36,7 @@ pub struct AppState {
     
     // Compression result info
     pub last_compression_result: Option<(f64, f64)>, // (input_size_mb, output_size_mb)
+    pub last_encoding_plan: Option<EncodingPlan>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -45,7 +51,7 @@ pub enum CompressionStatus {
 
 #[derive(Debug, Clone)]
 pub struct PreviewData {
-    pub thumbnail: Option<Vec<u8>>, // RGBA image data
+    pub thumbnail: Option<Thumbnail>,
     pub width: u32,
     pub height: u32,
     pub duration: f64,
@@ -54,70 +60,74 @@ pub struct PreviewData {
     pub bitrate: u32,
 }
 
+/// One decoded frame of a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thumbnail {
+    /// Seconds into the video
+    pub timestamp: f64,
+    pub width: u32,
+    pub height: u32,
+    /// `width * height` RGBA pixels
+    pub rgba: Vec<u8>,
+}
+
 impl Default for AppState {
     fn default() -> Self {
         Self {
-            input_file: None,
-            output_file: None,
+            queue: Queue::default(),
             output_folder: None,
             same_folder: true,
+            input_folder: None,
             compression_settings: CompressionSettings::default(),
             status: CompressionStatus::Idle,
-            progress: 0.0,
             current_operation: String::new(),
-            estimated_time: None,
             original_preview: None,
             compressed_preview: None,
+            preview_request: 0,
+            preview_error: None,
             last_error: None,
             show_completion_popup: false,
             last_compression_result: None,
+            last_encoding_plan: None,
         }
     }
 }
 
 impl AppState {
-    pub fn set_input_file(&mut self, path: PathBuf) {
-        self.input_file = Some(path.clone());
-        
-        // Auto-generate output file if same folder is selected
-        if self.same_folder {
-            if let Some(parent) = path.parent() {
-                if let Some(stem) = path.file_stem() {
-                    let output_name = format!("{}_small.mp4", stem.to_string_lossy());
-                    self.output_file = Some(parent.join(output_name));
-                }
+    /// Queue dropped or picked files and folders with the current settings.
+    /// Folders are searched for videos, including subfolders.
+    pub fn add_inputs(&mut self, paths: &[PathBuf]) -> usize {
+        let inputs = match collect_inputs(paths, true) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                log::warn!("{}", e);
+                return 0;
             }
-        }
+        };
         
-        // Reset preview data
-        self.original_preview = None;
-        self.compressed_preview = None;
-    }
-    
-    pub fn get_output_path(&self) -> Option<PathBuf> {
-        if let Some(ref output) = self.output_file {
-            Some(output.clone())
-        } else if let (Some(ref input), Some(ref folder)) = (&self.input_file, &self.output_folder) {
-            if let Some(stem) = input.file_stem() {
-                let output_name = format!("{}_small.mp4", stem.to_string_lossy());
-                Some(folder.join(output_name))
-            } else {
-                None
-            }
-        } else {
-            None
+        if let Some(folder) = paths.last().and_then(|path| if path.is_dir() { Some(path.as_path()) } else { path.parent() }) {
+            self.input_folder = Some(folder.to_path_buf());
         }
+        
+        // Outputs of earlier runs sit next to their inputs; they are not new inputs
+        let new_inputs: Vec<BatchInput> = inputs.iter()
+            .filter(|input| !queue::is_previous_output(&input.path, &inputs) && !self.queue.is_output(&input.path))
+            .cloned()
+            .collect();
+        new_inputs.into_iter()
+            .filter(|input| self.queue.add(input.clone(), self.compression_settings.clone()))
+            .count()
     }
     
-    pub fn is_ready_to_compress(&self) -> bool {
-        self.input_file.is_some() && 
-        self.status == CompressionStatus::Idle &&
-        self.get_output_path().is_some()
+    /// Where `item` is written, following the output folder choice
+    pub fn output_path_for(&self, item: &QueueItem) -> Option<PathBuf> {
+        let folder = if self.same_folder { None } else { Some(self.output_folder.as_deref()?) };
+        queue::output_path(item, folder)
     }
     
-    pub fn update_progress(&mut self, progress: f32, operation: String) {
-        self.progress = progress.clamp(0.0, 1.0);
-        self.current_operation = operation;
+    /// Whether outputs have somewhere to go
+    pub fn has_output_location(&self) -> bool {
+        self.same_folder || self.output_folder.as_deref().is_some_and(Path::is_dir)
     }
     
     pub fn set_error(&mut self, error: String) {
@@ -134,15 +144,13 @@ impl AppState {
     
     pub fn reset_compression(&mut self) {
         self.status = CompressionStatus::Idle;
-        self.progress = 0.0;
         self.current_operation.clear();
-        self.estimated_time = None;
         self.compressed_preview = None;
         self.clear_error();
     }
     
     pub fn get_target_size_mb(&self) -> f32 {
-        self.compression_settings.target_size.as_mb()
+        self.compression_settings.target_size.as_mb() as f32
     }
 }
 
@@ -163,6 +171,7 @@ pub struct ProgressState {
     pub overall_progress: f32,
     pub estimated_remaining: Option<std::time::Duration>,
     pub current_fps: Option<f32>,
+    pub speed: Option<f32>,
     pub frames_processed: u64,
     pub total_frames: Option<u64>,
 }
@@ -183,6 +192,7 @@ impl Default for ProgressState {
             overall_progress: 0.0,
             estimated_remaining: None,
             current_fps: None,
+            speed: None,
             frames_processed: 0,
             total_frames: None,
         }
@@ -227,4 +237,30 @@ impl ProgressState {
         self.stage = new_stage;
         self.stage_progress = 0.0;
     }
+    
+    /// Fold an engine progress event into this state
+    pub fn apply(&mut self, event: &ProgressEvent) {
+        match event {
+            ProgressEvent::Stage(stage) => self.advance_stage(CompressionStage::from(*stage)),
+            ProgressEvent::Progress(update) => {
+                self.overall_progress = update.fraction;
+                self.estimated_remaining = update.eta;
+                self.current_fps = update.fps;
+                self.speed = update.speed;
+                self.frames_processed = update.frames_processed;
+                self.total_frames = update.total_frames;
+            }
+        }
+    }
+}
+
+impl From<EncodeStage> for CompressionStage {
+    fn from(stage: EncodeStage) -> Self {
+        match stage {
+            EncodeStage::Probing | EncodeStage::FallbackRetry { .. } => CompressionStage::Initializing,
+            EncodeStage::FirstPass => CompressionStage::Analyzing,
+            EncodeStage::SecondPass | EncodeStage::Encoding => CompressionStage::Encoding,
+            EncodeStage::Finalizing => CompressionStage::Finalizing,
+        }
+    }
 }
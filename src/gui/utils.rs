@@ -80,6 +80,7 @@ pub mod theme {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
     
     #[test]
     fn test_file_size_formatting() {
@@ -0,0 +1,150 @@
+//! Message catalogs shared by the CLI and the GUI.
+//!
+//! Each language is a flat TOML file in `locales/`, named after its code
+//! (`ko.toml`), and is embedded at build time. Keys a catalog lacks fall back
+//! to English. Placeholders are written `{name}` and filled by [`t!`]:
+//!
+//! ```ignore
+//! println!("{}", t!("job_finished", done = 1, total = 3, input = path.display()));
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::sync::{OnceLock, RwLock};
+
+include!(concat!(env!("OUT_DIR"), "/locales.rs"));
+
+/// Language used for missing keys and when none is chosen
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+type Catalog = BTreeMap<String, String>;
+
+/// Language of [`t!`] messages; empty for the fallback
+static CURRENT_LANGUAGE: RwLock<String> = RwLock::new(String::new());
+
+fn catalogs() -> &'static BTreeMap<&'static str, Catalog> {
+    static CATALOGS: OnceLock<BTreeMap<&'static str, Catalog>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        CATALOG_SOURCES.iter()
+            .map(|(language, source)| {
+                let catalog = toml::from_str(source)
+                    .unwrap_or_else(|e| panic!("locales/{}.toml is not a valid catalog: {}", language, e));
+                (*language, catalog)
+            })
+            .collect()
+    })
+}
+
+/// Message `key` in `language`, with `{name}` placeholders replaced by `args`
+pub fn text_in(language: &str, key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let catalogs = catalogs();
+    let template = catalogs.get(language)
+        .and_then(|catalog| catalog.get(key))
+        .or_else(|| catalogs.get(FALLBACK_LANGUAGE).and_then(|catalog| catalog.get(key)));
+    let Some(template) = template else {
+        log::debug!("No message for '{}'", key);
+        return key.to_string();
+    };
+
+    args.iter().fold(template.clone(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), &value.to_string())
+    })
+}
+
+/// Message `key` in the current language; see [`t!`]
+pub fn text(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let language = CURRENT_LANGUAGE.read().map(|language| language.clone()).unwrap_or_default();
+    text_in(&language, key, args)
+}
+
+/// Switch the language of [`t!`] messages
+pub fn set_language(language: &str) {
+    if let Ok(mut current) = CURRENT_LANGUAGE.write() {
+        *current = language.to_string();
+    }
+}
+
+/// Code and native name of every embedded language
+pub fn languages() -> Vec<(&'static str, String)> {
+    catalogs().keys()
+        .map(|&language| (language, text_in(language, "language_name", &[])))
+        .collect()
+}
+
+/// Clap value parser and config check for `--lang`
+pub fn parse_language(language: &str) -> Result<String, String> {
+    if catalogs().contains_key(language) {
+        Ok(language.to_string())
+    } else {
+        let known: Vec<_> = catalogs().keys().copied().collect();
+        Err(format!("unknown language '{}'; available languages: {}", language, known.join(", ")))
+    }
+}
+
+/// Language from `LANG`, then `LANGUAGE`, if there is a catalog for it
+pub fn detect_system_language() -> String {
+    ["LANG", "LANGUAGE"].iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .flat_map(|value| {
+            value.split(':')
+                .map(|locale| locale.split(['_', '.', '-']).next().unwrap_or_default().to_lowercase())
+                .collect::<Vec<_>>()
+        })
+        .find(|language| catalogs().contains_key(language.as_str()))
+        .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string())
+}
+
+/// Translated message: `t!("key")` or `t!("key", name = value, ...)`
+macro_rules! t {
+    ($key:expr) => {
+        $crate::i18n::text($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::text($key, &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+])
+    };
+}
+pub(crate) use t;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template.split('{').skip(1).filter_map(|part| part.split_once('}')).map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn test_catalogs_have_every_english_key() {
+        let english = &catalogs()[FALLBACK_LANGUAGE];
+
+        for (language, catalog) in catalogs() {
+            let missing: Vec<_> = english.keys().filter(|key| !catalog.contains_key(*key)).collect();
+            assert!(missing.is_empty(), "locales/{}.toml is missing {:?}", language, missing);
+
+            let unknown: Vec<_> = catalog.keys().filter(|key| !english.contains_key(*key)).collect();
+            assert!(unknown.is_empty(), "locales/{}.toml has keys English lacks: {:?}", language, unknown);
+
+            for (key, template) in catalog {
+                assert_eq!(placeholders(template), placeholders(&english[key]),
+                    "locales/{}.toml: '{}' has different placeholders", language, key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_fallback_and_placeholders() {
+        assert_eq!(text_in("ko", "cancel", &[]), "취소");
+        assert_eq!(text_in("xx", "cancel", &[]), "Cancel");
+        assert_eq!(text_in("ko", "no_such_key", &[]), "no_such_key");
+
+        let text = text_in("en", "job_finished", &[("done", &1), ("total", &3), ("input", &"a.mov")]);
+        assert_eq!(text, "[1/3] Finished a.mov");
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(parse_language("ja").unwrap(), "ja");
+        assert!(parse_language("klingon").unwrap_err().contains("en, ja, ko"));
+    }
+}
@@ -0,0 +1,34 @@
+//! # small-mp4
+//!
+//! Squeeze videos down to a target file size with FFmpeg, using hardware
+//! encoders (NVENC, AMF, QuickSync, VAAPI, VideoToolbox) when they are
+//! available and falling back to software encoding when they are not.
+//!
+//! This crate is the engine behind the `small-mp4` binary. It can be embedded
+//! directly so tools don't have to shell out to the CLI:
+//!
+//! ```no_run
+//! use small_mp4::compression::{CompressionEngine, CompressionSettings, TargetSize};
+//! use small_mp4::compression::hardware::HardwareCapabilities;
+//! use std::path::Path;
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let capabilities = HardwareCapabilities::detect().await?;
+//! let settings = CompressionSettings::builder()
+//!     .target_size(TargetSize::Size10MB)
+//!     .remove_audio(true)
+//!     .build_with_capabilities(&capabilities);
+//!
+//! let mut engine = CompressionEngine::new(capabilities);
+//! let result = engine
+//!     .compress(Path::new("input.mov"), None, &settings, None)
+//!     .await?;
+//! println!("{}", result.summary());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! FFmpeg and ffprobe must be installed and on `PATH` at runtime.
+
+pub mod compression;
+pub mod utils;
@@ -1,18 +1,24 @@
 use anyhow::Result;
 use clap::Parser;
-use env_logger;
-use log::{info, warn};
+use log::{error, info, warn};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-mod compression;
 mod cli;
-mod utils;
+mod i18n;
 
 #[cfg(feature = "gui")]
 mod gui;
 
-use cli::Cli;
-use compression::hardware::HardwareCapabilities;
-use compression::{CompressionEngine, CompressionSettings};
+use cli::batch::{collect_inputs, output_paths, summary_table, BatchEntry, BatchOutcome};
+use cli::journal::{hash_file, hash_settings, BatchJournal, JournalEntry, JournalStatus, JOURNAL_FILE_NAME};
+use cli::watch::WatchOptions;
+use cli::config::Config;
+use cli::{Cli, CompressionCliSettings, OutputFormat};
+use i18n::t;
+use small_mp4::compression::hardware::HardwareCapabilities;
+use small_mp4::compression::{generate_output_path_avoiding, CancellationToken, Cancelled, CompressionJob, JobLimits, JobScheduler, VideoEdit};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -22,11 +28,32 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let format = cli.output_format();
+    
+    // Defaults and profiles shared with the GUI
+    let config = Config::load(cli.config.as_deref())?;
+    // Commands without compression settings follow --lang or the config file
+    if let Some(language) = cli.command.as_ref().and_then(cli::Commands::lang).or(config.defaults.lang.as_deref()) {
+        i18n::set_language(language);
+    }
 
     // Handle hardware listing command
-    if let Some(cli::Commands::ListHardware) = cli.command {
-        return list_hardware_capabilities().await;
+    if let Some(cli::Commands::ListHardware { .. }) = cli.command {
+        return list_hardware_capabilities(format).await;
     }
+    
+    // Probing needs no hardware detection
+    if let Some(cli::Commands::Probe { input, .. }) = &cli.command {
+        return cli::inspect::probe(input, format).await;
+    }
+    
+    let resolve = |flags: &CompressionCliSettings| -> Result<CompressionCliSettings> {
+        let settings = config.resolve(flags, cli.profile.as_deref())?;
+        if let Some(language) = &settings.lang {
+            i18n::set_language(language);
+        }
+        Ok(settings)
+    };
 
     // Detect hardware capabilities
     let hw_capabilities = match HardwareCapabilities::detect().await {
@@ -44,21 +71,48 @@ async fn main() -> Result<()> {
 
     // Execute compression based on CLI arguments
     match &cli.command {
-        Some(cli::Commands::Compress { input, output, settings }) => {
-            let compression_settings = CompressionSettings::from_cli_settings(settings, &hw_capabilities)?;
-            let mut engine = CompressionEngine::new(hw_capabilities);
-            
-            info!("Starting compression: {} -> {:?}", input.display(), output);
-            info!("Using encoder: {:?}", compression_settings.hardware_encoder);
-            
-            engine.compress(input, output.as_ref().map(|p| p.as_path()), &compression_settings, None).await?;
+        Some(cli::Commands::Compress { inputs, output, output_dir, recursive, jobs, journal, no_journal, dry_run, edit, settings }) => {
+            let options = BatchOptions {
+                output: output.as_deref(),
+                output_dir: output_dir.as_deref(),
+                recursive: *recursive,
+                jobs: jobs.map(usize::from),
+                journal: journal.as_deref(),
+                no_journal: *no_journal,
+                dry_run: *dry_run,
+                edit: edit.to_video_edit(),
+            };
+            return compress_files(inputs, options, &resolve(settings)?, hw_capabilities, format).await;
+        }
+        Some(cli::Commands::Watch {
+            dir, output_dir, recursive, originals, archive_dir, state_file, poll_interval, settle, settings,
+        }) => {
+            let mut options = WatchOptions::new(dir.clone());
+            options.recursive = *recursive;
+            options.originals = *originals;
+            options.poll_interval = Duration::from_secs(*poll_interval);
+            options.settle = Duration::from_secs(*settle);
+            if let Some(output_dir) = output_dir {
+                options.output_dir = output_dir.clone();
+            }
+            if let Some(archive_dir) = archive_dir {
+                options.archive_dir = archive_dir.clone();
+            }
+            if let Some(state_file) = state_file {
+                options.state_file = state_file.clone();
+            }
             
-            info!("Compression completed successfully!");
+            let compression_settings = resolve(settings)?.to_compression_settings(&hw_capabilities);
+            let cancel = cancel_on_ctrl_c();
+            return cli::watch::run(&options, &compression_settings, hw_capabilities, &cancel, format).await;
+        }
+        Some(cli::Commands::Estimate { input, sizes, keep_resolution, .. }) => {
+            return cli::inspect::estimate(input, sizes, *keep_resolution, &hw_capabilities, format).await;
         }
         #[cfg(feature = "gui")]
         Some(cli::Commands::Gui) => {
             info!("Launching GUI interface");
-            return launch_gui(hw_capabilities).await;
+            return launch_gui(hw_capabilities, resolve(&CompressionCliSettings::default())?, gui_profiles(&config)?, cli.profile.is_none()).await;
         }
         #[cfg(not(feature = "gui"))]
         Some(cli::Commands::Gui) => {
@@ -66,7 +120,7 @@ async fn main() -> Result<()> {
             eprintln!("Please use the CLI interface or recompile with --features gui");
             std::process::exit(1);
         }
-        Some(cli::Commands::ListHardware) => {
+        Some(cli::Commands::ListHardware { .. }) | Some(cli::Commands::Probe { .. }) => {
             // Already handled above
         }
         None => {
@@ -74,7 +128,7 @@ async fn main() -> Result<()> {
             info!("Starting Small MP4 in interactive mode");
             #[cfg(feature = "gui")]
             {
-                return launch_gui(hw_capabilities).await;
+                return launch_gui(hw_capabilities, resolve(&CompressionCliSettings::default())?, gui_profiles(&config)?, cli.profile.is_none()).await;
             }
             #[cfg(not(feature = "gui"))]
             {
@@ -86,14 +140,276 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Token that Ctrl-C cancels, killing FFmpeg and removing the partial
+/// output instead of leaving it behind
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let ctrl_c_token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n{}", t!("cancelling"));
+            ctrl_c_token.cancel();
+        }
+    });
+    cancel
+}
+
+/// How `compress` lays out and schedules its files
+struct BatchOptions<'a> {
+    output: Option<&'a Path>,
+    output_dir: Option<&'a Path>,
+    recursive: bool,
+    jobs: Option<usize>,
+    journal: Option<&'a Path>,
+    no_journal: bool,
+    /// Print what would run instead of compressing
+    dry_run: bool,
+    /// Trim, crop and rotation of every file
+    edit: VideoEdit,
+}
+
+impl BatchOptions<'_> {
+    /// Journal to use for `file_count` files; single files only get one on request
+    fn journal_path(&self, file_count: usize) -> Option<PathBuf> {
+        if self.no_journal {
+            return None;
+        }
+        match (self.journal, self.output_dir) {
+            (Some(journal), _) => Some(journal.to_path_buf()),
+            _ if file_count < 2 => None,
+            (None, Some(output_dir)) => Some(output_dir.join(JOURNAL_FILE_NAME)),
+            (None, None) => Some(PathBuf::from(JOURNAL_FILE_NAME)),
+        }
+    }
+}
+
+async fn compress_files(
+    inputs: &[PathBuf],
+    options: BatchOptions<'_>,
+    settings: &CompressionCliSettings,
+    hw_capabilities: HardwareCapabilities,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut files = collect_inputs(inputs, options.recursive)?;
+    
+    // Batches keep a journal so a re-run skips finished files and retries the rest
+    let mut journal = match options.journal_path(files.len()) {
+        Some(path) => Some(BatchJournal::open(&path)?),
+        None => None,
+    };
+    // Outputs of earlier runs sit next to their inputs; they are not new inputs
+    if let Some(journal) = &journal {
+        files.retain(|file| !journal.is_output(&file.path));
+    }
+    if files.is_empty() {
+        anyhow::bail!(t!("no_video_files"));
+    }
+    if options.output.is_some() && files.len() > 1 {
+        anyhow::bail!(t!("output_needs_single_file", count = files.len()));
+    }
+    // Where --output-dir puts each file, checked for clashes up front
+    let dir_outputs = match options.output_dir {
+        Some(dir) => Some(output_paths(&files, dir)?),
+        None => None,
+    };
+    
+    let mut compression_settings = settings.to_compression_settings(&hw_capabilities);
+    compression_settings.edit = options.edit;
+    info!("Using encoder: {:?}", compression_settings.hardware_encoder);
+    
+    let limits = match options.jobs {
+        Some(jobs) => JobLimits::new(jobs, &hw_capabilities),
+        None if files.len() == 1 => JobLimits::sequential(),
+        None => JobLimits::detect(&hw_capabilities),
+    };
+    let scheduler = JobScheduler::new(hw_capabilities, limits);
+    
+    // Paths a generated output name must not reuse: the inputs, and every
+    // output this batch or the journal already assigned
+    let mut taken: HashSet<PathBuf> = files.iter().map(|file| file.path.clone())
+        .chain(dir_outputs.iter().flatten().cloned())
+        .chain(journal.iter().flat_map(|journal| journal.outputs().map(Path::to_path_buf)))
+        .collect();
+    
+    if options.dry_run {
+        for (index, file) in files.iter().enumerate() {
+            let output = match options.output.map(Path::to_path_buf)
+                .or_else(|| dir_outputs.as_ref().map(|outputs| outputs[index].clone())) {
+                Some(path) => path,
+                None => generate_output_path_avoiding(&file.path, &taken)?,
+            };
+            taken.insert(output.clone());
+            let job = CompressionJob { input: file.path.clone(), output: Some(output), settings: compression_settings.clone() };
+            let dry_run = scheduler.dry_run(&job).await?;
+            
+            match format {
+                OutputFormat::Json => println!("{}", serde_json::to_string(&dry_run)?),
+                OutputFormat::Text => println!("{}", dry_run),
+            }
+        }
+        return Ok(());
+    }
+    
+    let settings_hash = hash_settings(&compression_settings);
+    
+    let mut entries: Vec<Option<BatchEntry>> = files.iter().map(|_| None).collect();
+    let mut jobs = Vec::with_capacity(files.len());
+    // Index into `files` of each job
+    let mut job_files = Vec::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        let mut output = options.output.map(Path::to_path_buf)
+            .or_else(|| dir_outputs.as_ref().map(|outputs| outputs[index].clone()));
+        
+        let content_hash = match journal.as_ref() {
+            Some(journal) => {
+                let path = file.path.clone();
+                let content_hash = tokio::task::spawn_blocking(move || hash_file(&path)).await??;
+                
+                if journal.is_done(&file.path, &content_hash, &settings_hash, output.as_deref()) {
+                    let output = journal.entry(&file.path).map(|entry| entry.output.clone()).unwrap_or_default();
+                    entries[index] = Some(BatchEntry { input: file.path.clone(), outcome: BatchOutcome::Skipped { output } });
+                    continue;
+                }
+                
+                // Retries overwrite the earlier output instead of adding a _small/_squeezed copy
+                output = output.or_else(|| journal.entry(&file.path).map(|entry| entry.output.clone()));
+                Some(content_hash)
+            }
+            None => None,
+        };
+        
+        // Named here, before any job starts, so parallel jobs never race
+        // for the same free name
+        let output = match output {
+            Some(path) => path,
+            None => generate_output_path_avoiding(&file.path, &taken)?,
+        };
+        taken.insert(output.clone());
+        if let (Some(journal), Some(content_hash)) = (journal.as_mut(), content_hash) {
+            journal.record(&file.path, JournalEntry {
+                content_hash,
+                settings_hash: settings_hash.clone(),
+                output: output.clone(),
+                status: JournalStatus::Pending,
+            });
+        }
+        
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        jobs.push(CompressionJob { input: file.path.clone(), output: Some(output), settings: compression_settings.clone() });
+        job_files.push(index);
+    }
+    if let Some(journal) = &journal {
+        journal.save()?;
+    }
+    
+    let skipped = files.len() - jobs.len();
+    // JSON mode keeps stdout for the records
+    if format == OutputFormat::Text {
+        if let (1, [job]) = (files.len(), jobs.as_slice()) {
+            info!("Starting compression: {} -> {:?}", job.input.display(), job.output);
+            if let Ok(plan) = scheduler.plan(&job.input, &compression_settings).await {
+                println!("{}", t!("output_plan", plan = plan.summary()));
+                if !plan.feasible {
+                    println!("{}", t!("target_below_minimum",
+                        target = compression_settings.target_size, minimum = plan.minimum_size));
+                }
+            }
+        } else if skipped > 0 {
+            println!("{}", t!("compressing_files_resumed", count = jobs.len(), jobs = limits.max_jobs, skipped = skipped));
+        } else {
+            println!("{}", t!("compressing_files", count = jobs.len(), jobs = limits.max_jobs));
+        }
+    }
+    
+    let cancel = cancel_on_ctrl_c();
+    
+    let total = jobs.len();
+    let mut finished = 0;
+    let outcomes = scheduler.run_all(jobs, &cancel, |index, outcome| {
+        finished += 1;
+        let input = &files[job_files[index]].path;
+        let status = match outcome {
+            // Stays pending, so the next run retries it
+            Err(e) if e.is::<Cancelled>() => None,
+            Err(e) => {
+                error!("{}", t!("job_failed", done = finished, total = total, input = input.display(), error = e));
+                Some(JournalStatus::Failed { error: e.to_string() })
+            }
+            Ok(result) => {
+                if result.target_met {
+                    info!("{}", t!("job_finished", done = finished, total = total, input = input.display()));
+                } else {
+                    warn!("{}", t!("job_over_target",
+                        done = finished, total = total, output = result.output_path.display(),
+                        size = format!("{:.2}", result.output_size_mb), target = result.target_size,
+                        encodes = result.size_iterations));
+                }
+                Some(JournalStatus::Done { output_size_mb: result.output_size_mb })
+            }
+        };
+        
+        if let (Some(journal), Some(status)) = (journal.as_mut(), status) {
+            journal.set_status(input, status);
+            if let Err(e) = journal.save() {
+                warn!("{}", e);
+            }
+        }
+    }).await;
+    
+    if cancel.is_cancelled() {
+        eprintln!("{}", t!("cancelled"));
+        std::process::exit(130);
+    }
+    
+    for (index, outcome) in job_files.into_iter().zip(outcomes) {
+        entries[index] = Some(BatchEntry { input: files[index].path.clone(), outcome: outcome.into() });
+    }
+    let entries: Vec<_> = entries.into_iter().flatten().collect();
+    
+    if format == OutputFormat::Json {
+        for entry in &entries {
+            println!("{}", entry.json_record());
+        }
+    } else {
+        // A single file keeps the plain error reporting
+        if let [BatchEntry { outcome: BatchOutcome::Failed(e), .. }] = entries.as_slice() {
+            return Err(anyhow::anyhow!("{}", e));
+        }
+        
+        println!("\n{}", summary_table(&entries, settings.strict()));
+    }
+    
+    if entries.iter().any(|entry| entry.is_failure(settings.strict())) {
+        std::process::exit(1);
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "gui")]
+/// Every config file profile, resolved over the defaults, for the GUI's queue
+fn gui_profiles(config: &Config) -> Result<Vec<(String, CompressionCliSettings)>> {
+    config.profiles.keys()
+        .map(|name| Ok((name.clone(), config.resolve(&CompressionCliSettings::default(), Some(name))?)))
+        .collect()
+}
+
 #[cfg(feature = "gui")]
-async fn launch_gui(hw_capabilities: HardwareCapabilities) -> Result<()> {
+/// With `restore_saved`, the GUI reopens with the settings of its last session
+async fn launch_gui(
+    hw_capabilities: HardwareCapabilities,
+    settings: CompressionCliSettings,
+    profiles: Vec<(String, CompressionCliSettings)>,
+    restore_saved: bool,
+) -> Result<()> {
     use gui::SmallMp4App;
     
     let options = eframe::NativeOptions {
         viewport: eframe::egui::ViewportBuilder::default()
-            .with_inner_size([500.0, 600.0])
-            .with_min_inner_size([400.0, 550.0])
+            .with_inner_size([560.0, 760.0])
+            .with_min_inner_size([460.0, 600.0])
             .with_icon(
                 eframe::icon_data::from_png_bytes(include_bytes!("../assets/icon.png"))
                     .expect("Failed to load icon"),
@@ -104,7 +420,7 @@ async fn launch_gui(hw_capabilities: HardwareCapabilities) -> Result<()> {
     if let Err(e) = eframe::run_native(
         "Small MP4 - Video Compressor",
         options,
-        Box::new(move |cc| Ok(Box::new(SmallMp4App::new_with_context(cc, hw_capabilities)))),
+        Box::new(move |cc| Ok(Box::new(SmallMp4App::new_with_context(cc, hw_capabilities, &settings, &profiles, restore_saved)))),
     ) {
         eprintln!("Failed to run GUI: {}", e);
         return Err(anyhow::anyhow!("GUI failed to start: {}", e));
@@ -113,44 +429,52 @@ async fn launch_gui(hw_capabilities: HardwareCapabilities) -> Result<()> {
     Ok(())
 }
 
-async fn list_hardware_capabilities() -> Result<()> {
-    println!("🔍 Detecting hardware acceleration capabilities...\n");
+async fn list_hardware_capabilities(format: OutputFormat) -> Result<()> {
+    if format == OutputFormat::Json {
+        let capabilities = HardwareCapabilities::detect().await.unwrap_or_else(|e| {
+            warn!("Hardware detection failed: {}", e);
+            HardwareCapabilities::software_only()
+        });
+        println!("{}", serde_json::to_string(&capabilities)?);
+        return Ok(());
+    }
+    
+    println!("{}\n", t!("hw_detecting"));
     
     match HardwareCapabilities::detect().await {
         Ok(capabilities) => {
-            println!("✅ Hardware Detection Results:\n");
+            println!("{}\n", t!("hw_results"));
             
             // CUDA devices
             if !capabilities.cuda_devices.is_empty() {
-                println!("🔥 NVIDIA CUDA Devices:");
+                println!("{}", t!("hw_cuda_devices"));
                 for (i, device) in capabilities.cuda_devices.iter().enumerate() {
-                    println!("  [{}] {} - {}MB VRAM (Compute {}.{})", 
-                        i, device.name, device.memory_mb, 
-                        device.compute_capability.0, device.compute_capability.1);
-                    println!("      NVENC Support: {}", if device.nvenc_support { "✅" } else { "❌" });
-                    println!("      Max Sessions: {}", device.max_concurrent_sessions);
+                    println!("  {}", t!("hw_cuda_device", index = i, name = device.name, memory = device.memory_mb,
+                        major = device.compute_capability.0, minor = device.compute_capability.1));
+                    println!("      {}", t!("hw_nvenc_support", supported = if device.nvenc_support { "✅" } else { "❌" }));
+                    println!("      {}", t!("hw_max_sessions", sessions = device.max_concurrent_sessions));
                 }
                 println!();
             }
             
             // Available encoders
-            println!("⚡ Available Hardware Encoders:");
+            println!("{}", t!("hw_available_encoders"));
             for encoder in &capabilities.available_encoders {
                 let speed_boost = capabilities.speed_improvement(encoder);
-                println!("  • {:?} - {:.1}x faster encoding", encoder, speed_boost);
+                println!("  {}", t!("hw_encoder_speed", encoder = format!("{:?}", encoder), speed = format!("{:.1}", speed_boost)));
             }
             println!();
             
             // Preferred encoder
             if let Some(preferred) = &capabilities.preferred_encoder {
-                println!("🎯 Recommended Encoder: {:?}", preferred);
-                println!("   Memory Usage: {}MB", capabilities.memory_usage_mb);
-                println!("   Speed Multiplier: {:.1}x", capabilities.encoding_speed_multiplier);
+                println!("{}", t!("hw_recommended", encoder = format!("{:?}", preferred)));
+                println!("   {}", t!("hw_memory_usage", memory = capabilities.memory_usage_mb));
+                println!("   {}", t!("hw_speed_multiplier", speed = format!("{:.1}", capabilities.encoding_speed_multiplier)));
             }
         },
         Err(e) => {
-            eprintln!("❌ Hardware detection failed: {:?}", e);
-            println!("💻 Falling back to software encoding only");
+            eprintln!("{}", t!("hw_detection_failed", error = format!("{:?}", e)));
+            println!("{}", t!("hw_software_only"));
         }
     }
     
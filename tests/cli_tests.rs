@@ -38,6 +38,15 @@ fn test_cli_list_hardware() {
         .stdout(predicate::str::contains("Detecting hardware acceleration"));
 }
 
+#[test]
+fn test_cli_list_hardware_follows_lang() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.args(["list-hw", "--lang", "ko"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("하드웨어 가속 기능을 확인하는 중"));
+}
+
 #[test]
 fn test_cli_compress_basic() {
     let input_path = get_test_video_path("test_720p_10s.mp4");
@@ -86,8 +95,8 @@ fn test_cli_compress_different_sizes() {
     fs::create_dir_all("test_videos/output").unwrap();
 
     // Test different size presets
-    let sizes = vec!["1mb", "5mb", "10mb"];
-    let expected_sizes = vec![1.1, 5.25, 10.5];
+    let sizes = ["1mb", "5mb", "10mb"];
+    let expected_sizes = [1.1, 5.25, 10.5];
 
     for (size_preset, max_size) in sizes.iter().zip(expected_sizes.iter()) {
         let output_path = get_output_path(&format!("cli_test_720p_{}.mp4", size_preset));
@@ -137,4 +146,268 @@ fn test_cli_compress_invalid_size() {
         .arg("invalid")
         .assert()
         .failure();
-}
\ No newline at end of file
+}
+#[test]
+fn test_cli_rejects_unknown_size_unit() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress")
+        .arg("nonexistent_file.mp4")
+        .arg("--size")
+        .arg("10parsecs")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown size unit"));
+}
+
+#[test]
+fn test_cli_rejects_zero_max_iterations() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress")
+        .arg("nonexistent_file.mp4")
+        .arg("--max-iterations")
+        .arg("0")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_cli_rejects_out_of_range_quality() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress")
+        .arg("nonexistent_file.mp4")
+        .arg("--hw-quality")
+        .arg("constant")
+        .arg("--quality")
+        .arg("52")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_cli_rejects_invalid_edits() {
+    for (flag, value) in [("--rotate", "45"), ("--start", "abc"), ("--crop", "1280x720")] {
+        let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+        cmd.arg("compress")
+            .arg("nonexistent_file.mp4")
+            .arg(flag)
+            .arg(value)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains(flag));
+    }
+}
+
+#[test]
+fn test_cli_compress_directory_without_videos() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("notes.txt"), b"not a video").unwrap();
+
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("No video files found"));
+}
+
+#[test]
+fn test_cli_output_requires_single_input() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.mp4"), b"").unwrap();
+    fs::write(dir.path().join("b.mp4"), b"").unwrap();
+
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress")
+        .arg(dir.path())
+        .arg("-o")
+        .arg(dir.path().join("out.mp4"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--output-dir"));
+}
+
+#[test]
+fn test_cli_watch_missing_folder() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("watch")
+        .arg("nonexistent_folder")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Watch folder does not exist"));
+}
+
+#[test]
+fn test_cli_list_hardware_json() {
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    let output = cmd.arg("list-hw").arg("--json").output().unwrap();
+    assert!(output.status.success());
+
+    let capabilities: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(capabilities["available_encoders"].is_array());
+    assert!(capabilities["encoder_performance"].is_object());
+}
+
+#[test]
+fn test_cli_probe_and_estimate_missing_file() {
+    for subcommand in ["probe", "estimate"] {
+        let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+        cmd.arg(subcommand).arg("nonexistent.mp4");
+        cmd.assert().failure();
+    }
+}
+
+#[test]
+fn test_cli_unknown_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[profiles.work-chat]\nsize = \"8mb\"\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress").arg("input.mov")
+        .arg("--config").arg(&config)
+        .arg("--profile").arg("discord");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown profile 'discord'; available profiles: work-chat"));
+}
+
+#[test]
+fn test_cli_unknown_platform() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "[platforms.team-wiki]\nmax-size = \"20mb\"\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("small-mp4").unwrap();
+    cmd.arg("compress").arg("input.mov")
+        .arg("--config").arg(&config)
+        .arg("--platform").arg("fax");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Unknown platform 'fax'; available platforms: chat-8mb, email-25mb, short-video, team-wiki"));
+}
+
+/// Writes `ffprobe` and `ffmpeg` stand-ins into `dir`: ffprobe describes a
+/// 10 s 720p clip, and ffmpeg logs each encode to `encodes.log` and writes a
+/// small file to its output argument.
+#[cfg(unix)]
+fn write_stub_ffmpeg(dir: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let ffprobe = r#"#!/bin/sh
+echo '{"streams":[{"codec_type":"video","codec_name":"h264","width":1280,"height":720,"r_frame_rate":"30/1"}],"format":{"duration":"10.0","bit_rate":"4000000"}}'
+"#;
+    let ffmpeg = format!(r#"#!/bin/sh
+case " $* " in *" -i "*) ;; *) exit 0 ;; esac
+echo "$*" >> "{}"
+for last; do :; done
+[ "$last" = /dev/null ] || head -c 1000 /dev/zero > "$last"
+"#, dir.join("encodes.log").display());
+
+    for (name, script) in [("ffprobe", ffprobe.to_string()), ("ffmpeg", ffmpeg)] {
+        let path = dir.join(name);
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_parallel_jobs_get_distinct_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    write_stub_ffmpeg(&bin);
+    fs::write(dir.path().join("a.mov"), b"first clip").unwrap();
+    fs::write(dir.path().join("a.mkv"), b"second clip").unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+    Command::cargo_bin("small-mp4").unwrap()
+        .env("PATH", &path)
+        .arg("compress").arg(dir.path().join("a.mov")).arg(dir.path().join("a.mkv"))
+        .arg("--no-journal").arg("-j").arg("2").arg("--force-software")
+        .assert()
+        .success();
+
+    let log = fs::read_to_string(bin.join("encodes.log")).unwrap();
+    let mut outputs: Vec<_> = log.lines()
+        .map(|line| line.rsplit(' ').next().unwrap().to_string())
+        .filter(|output| output != "/dev/null")
+        .collect();
+    outputs.sort();
+    outputs.dedup();
+    assert_eq!(outputs, [
+        dir.path().join("a_compressed.mp4").display().to_string(),
+        dir.path().join("a_small.mp4").display().to_string(),
+    ]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_rerun_skips_journaled_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    let videos = dir.path().join("vids");
+    fs::create_dir_all(&bin).unwrap();
+    fs::create_dir_all(&videos).unwrap();
+    write_stub_ffmpeg(&bin);
+    fs::write(videos.join("a.mov"), b"first clip").unwrap();
+    fs::write(videos.join("b.mov"), b"second clip").unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+    let encodes = || fs::read_to_string(bin.join("encodes.log")).unwrap_or_default().lines().count();
+    let run = || {
+        Command::cargo_bin("small-mp4").unwrap()
+            .current_dir(dir.path())
+            .env("PATH", &path)
+            .arg("compress").arg("vids").arg("--force-software")
+            .assert()
+            .success();
+    };
+
+    run();
+    let first_run = encodes();
+    assert!(first_run > 0);
+    assert!(videos.join("a_compressed.mp4").exists());
+    assert!(videos.join("b_compressed.mp4").exists());
+
+    // The outputs now sit in vids/, but the journal knows them
+    run();
+    assert_eq!(encodes(), first_run);
+    let mut names: Vec<_> = fs::read_dir(&videos).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["a.mov", "a_compressed.mp4", "b.mov", "b_compressed.mp4"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_cli_watch_keeps_going_when_archiving_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let bin = dir.path().join("bin");
+    let watched = dir.path().join("inbox");
+    fs::create_dir_all(&bin).unwrap();
+    fs::create_dir_all(&watched).unwrap();
+    write_stub_ffmpeg(&bin);
+    fs::write(watched.join("a.mov"), b"first clip").unwrap();
+    fs::write(watched.join("b.mov"), b"second clip").unwrap();
+    // A folder already sits where a.mov would be archived
+    fs::create_dir_all(watched.join("originals/a.mov/taken")).unwrap();
+
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+    let _ = Command::cargo_bin("small-mp4").unwrap()
+        .env("PATH", &path)
+        .arg("watch").arg(&watched)
+        .arg("--originals").arg("move")
+        .arg("--poll-interval").arg("1").arg("--settle").arg("0")
+        .arg("--force-software")
+        .timeout(std::time::Duration::from_secs(4))
+        .output();
+
+    let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(watched.join(".small-mp4-watch.json")).unwrap()).unwrap();
+    assert_eq!(state["files"]["a.mov"]["status"], "failed");
+    assert!(state["files"]["a.mov"]["error"].as_str().unwrap().contains("Failed to move"));
+    // The daemon carried on with the next file
+    assert_eq!(state["files"]["b.mov"]["status"], "done");
+    assert!(watched.join("originals/b.mov").exists());
+}
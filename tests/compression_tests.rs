@@ -1,4 +1,4 @@
-use small_mp4::compression::{CompressionEngine, CompressionSettings, TargetSize};
+use small_mp4::compression::{CompressionEngine, CompressionSettings, FileSize, SizeEstimator, TargetSize, VideoMetadata};
 use small_mp4::compression::hardware::{HardwareCapabilities, HardwareEncoder};
 use std::path::Path;
 use anyhow::Result;
@@ -16,9 +16,10 @@ async fn test_target_sizes() {
 #[tokio::test]
 async fn test_compression_settings_default() {
     let settings = CompressionSettings::default();
-    assert_eq!(settings.target_size, TargetSize::Size10MB);
+    assert_eq!(settings.target_size, FileSize::from(TargetSize::Size10MB));
     assert!(settings.enable_hardware_accel);
-    assert!(settings.memory_optimization);
+    assert!(!settings.memory_optimization);
+    assert!(settings.compatibility_mode);
 }
 
 #[tokio::test]
@@ -28,11 +29,57 @@ async fn test_hardware_capabilities_software_only() {
     assert_eq!(hw_caps.available_encoders[0], HardwareEncoder::Software);
 }
 
+#[tokio::test]
+async fn test_compression_settings_builder() {
+    let settings = CompressionSettings::builder()
+        .target_size(TargetSize::Size5MB)
+        .remove_audio(true)
+        .compatibility_mode(false)
+        .build_with_capabilities(&HardwareCapabilities::software_only());
+    
+    assert_eq!(settings.target_size, FileSize::from(TargetSize::Size5MB));
+    assert!(settings.remove_audio);
+    assert!(!settings.compatibility_mode);
+    assert_eq!(settings.hardware_encoder, HardwareEncoder::Software);
+}
+
+#[tokio::test]
+async fn test_compression_settings_custom_size() {
+    let size: FileSize = "8MB".parse().unwrap();
+    let settings = CompressionSettings::builder()
+        .target_size(size)
+        .build();
+    
+    assert_eq!(settings.target_size.as_bytes(), 8_000_000);
+    assert_eq!(settings.get_effective_target_mb(), Some(8.0));
+    assert_eq!(settings.target_size.preset(), None);
+}
+
+#[test]
+fn test_estimate_targets_without_encoding() {
+    let metadata = VideoMetadata::default_hd();
+    let targets = [FileSize::from_mb(1.0), FileSize::from_mb(30.0)];
+    let encoders = [HardwareEncoder::NvencH264, HardwareEncoder::Software];
+    
+    let estimates = SizeEstimator::new().estimate_targets(&metadata, &CompressionSettings::default(), &targets, &encoders);
+    
+    assert_eq!(estimates.len(), 2);
+    let (tight, roomy) = (&estimates[0], &estimates[1]);
+    assert_eq!(tight.target_size, targets[0]);
+    assert!(!tight.plan.feasible);
+    assert!(roomy.plan.feasible);
+    assert!(roomy.plan.video_bitrate_kbps > tight.plan.video_bitrate_kbps);
+    assert!(roomy.plan.estimated_quality >= tight.plan.estimated_quality);
+    
+    // Hardware encoders are estimated faster than software
+    assert_eq!(roomy.encode_times.len(), 2);
+    assert!(roomy.encode_times[0].seconds < roomy.encode_times[1].seconds);
+}
+
 #[cfg(test)]
 mod integration_tests {
     use super::*;
     use std::fs;
-    use std::time::Duration;
 
     fn get_test_video_path(filename: &str) -> std::path::PathBuf {
         std::path::Path::new("test_videos/samples").join(filename)
@@ -60,12 +107,13 @@ mod integration_tests {
         let hw_caps = HardwareCapabilities::software_only();
         let mut engine = CompressionEngine::new(hw_caps);
         
-        let mut settings = CompressionSettings::default();
-        settings.target_size = TargetSize::Size1MB;
-        settings.enable_hardware_accel = false; // Use software for consistent testing
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size1MB)
+            .force_software(true) // Use software for consistent testing
+            .build();
 
         // Compress
-        engine.compress(&input_path, Some(&output_path), &settings).await?;
+        engine.compress(&input_path, Some(&output_path), &settings, None).await?;
 
         // Verify output exists and is under target size
         assert!(output_path.exists());
@@ -93,11 +141,12 @@ mod integration_tests {
         let hw_caps = HardwareCapabilities::software_only();
         let mut engine = CompressionEngine::new(hw_caps);
         
-        let mut settings = CompressionSettings::default();
-        settings.target_size = TargetSize::Size5MB;
-        settings.enable_hardware_accel = false;
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size5MB)
+            .force_software(true) // Use software for consistent testing
+            .build();
 
-        engine.compress(&input_path, Some(&output_path), &settings).await?;
+        engine.compress(&input_path, Some(&output_path), &settings, None).await?;
 
         assert!(output_path.exists());
         let metadata = fs::metadata(&output_path)?;
@@ -123,11 +172,12 @@ mod integration_tests {
         let hw_caps = HardwareCapabilities::software_only();
         let mut engine = CompressionEngine::new(hw_caps);
         
-        let mut settings = CompressionSettings::default();
-        settings.target_size = TargetSize::Size1MB;
-        settings.enable_hardware_accel = false;
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size1MB)
+            .force_software(true) // Use software for consistent testing
+            .build();
 
-        engine.compress(&input_path, Some(&output_path), &settings).await?;
+        engine.compress(&input_path, Some(&output_path), &settings, None).await?;
 
         assert!(output_path.exists());
         let metadata = fs::metadata(&output_path)?;
@@ -154,11 +204,12 @@ mod integration_tests {
         let hw_caps = HardwareCapabilities::software_only();
         let mut engine = CompressionEngine::new(hw_caps);
         
-        let mut settings = CompressionSettings::default();
-        settings.target_size = TargetSize::Size10MB;
-        settings.enable_hardware_accel = false;
+        let settings = CompressionSettings::builder()
+            .target_size(TargetSize::Size10MB)
+            .force_software(true) // Use software for consistent testing
+            .build();
 
-        engine.compress(&input_path, Some(&output_path), &settings).await?;
+        engine.compress(&input_path, Some(&output_path), &settings, None).await?;
 
         assert!(output_path.exists());
         let metadata = fs::metadata(&output_path)?;
@@ -170,7 +221,6 @@ mod integration_tests {
     }
 
     #[tokio::test]
-    #[should_panic(expected = "FileNotFound")]
     async fn test_compress_nonexistent_file() {
         let input_path = Path::new("nonexistent_video.mp4");
         let output_path = get_output_path("output.mp4");
@@ -179,7 +229,7 @@ mod integration_tests {
         let mut engine = CompressionEngine::new(hw_caps);
         let settings = CompressionSettings::default();
 
-        // This should panic or return an error
-        let _ = engine.compress(&input_path, Some(&output_path), &settings).await;
+        let result = engine.compress(input_path, Some(&output_path), &settings, None).await;
+        assert!(result.is_err());
     }
 }
\ No newline at end of file